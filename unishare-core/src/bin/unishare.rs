@@ -0,0 +1,790 @@
+//! Headless Unishare: the app's transports and discovery without the UI, for
+//! scripts, CI and SSH sessions.
+//!
+//! - `--json` prints one JSON object per line instead of text, and sends the
+//!   transports' progress logs to stderr so stdout stays parseable.
+//! - The exit code says how the command went; see [`Exit`].
+//! - While `unishare daemon` runs, `send`, `receive` and `devices` go
+//!   through it instead of starting transports and discovery of their own.
+//!   The daemon needs Unix domain sockets; elsewhere everything runs
+//!   in-process and the daemon commands report that they're unavailable.
+
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{sleep_until, Instant};
+
+#[cfg(unix)]
+use unishare_core::daemon::rpc::Event;
+#[cfg(unix)]
+use unishare_core::daemon::{self, Client, DaemonConfig, Direction, Transfer, TransferState};
+use unishare_core::device_discovery::beacon::{self, BeaconConfig};
+use unishare_core::device_discovery::{self, mdns, DeviceRegistry, DiscoveryEvent, KnownDevice};
+use unishare_core::protocols::protocol_manager::{resolve_route, send_file_via_best, send_via, start_receiver};
+use unishare_core::protocols::{capabilities, metered, mobiledata};
+use unishare_core::tools::connectivity::{connectivity_report, network_interfaces};
+use unishare_core::tools::internet::InternetConfig;
+
+const USAGE: &str = "\
+Usage: unishare [--json] <command> [options]
+
+Commands:
+  send <file> <destination>   Send a file to a device ID or name, an address,
+                              or a relay ticket
+      --via <transport>       Use webrtc, wifi-direct or bluetooth
+      --yes                   Allow large sends over a metered connection
+      --timeout <secs>        How long to look for a named device (default 3)
+  receive                     Wait for one file on the best available receiver,
+                              or accept the next file the daemon is offered
+      --relay                 Open a relay slot and print its ticket
+      --ticket <ticket>       Download from a ticket created elsewhere
+  discover                    Print devices as they appear and disappear
+      --timeout <secs>        Stop after this long (default: until Ctrl-C)
+  devices                     List the devices visible on the network
+      --timeout <secs>        How long to look (default 3)
+  status                      Show adapters, internet access and interfaces
+  daemon                      Keep receivers and discovery running and serve
+                              other commands on a local socket
+      --auto-accept           Accept incoming files without asking
+  transfers                   List the daemon's transfers
+  accept <id>                 Accept a file the daemon was offered
+  reject <id>                 Reject a file the daemon was offered
+  cancel <id>                 Stop one of the daemon's transfers
+
+While a daemon runs, send, receive and devices go through it.
+
+Exit codes: 0 success, 1 transfer failed, 2 usage error,
+3 destination not found or unreachable, or no daemon running.";
+
+/// What the daemon commands report where there is no daemon.
+#[cfg(not(unix))]
+const NO_DAEMON: &str = "The daemon needs Unix domain sockets, which this platform doesn't have";
+
+/// How long `send` and `devices` look for devices unless told otherwise.
+const DEFAULT_DISCOVERY: Duration = Duration::from_secs(3);
+
+/// Transports `send --via` accepts.
+const TRANSPORTS: [&str; 3] = ["webrtc", "wifi-direct", "bluetooth"];
+
+/// Process exit codes, so scripts can tell failures apart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Exit {
+    Success = 0,
+    /// The transfer started but didn't complete.
+    Failed = 1,
+    Usage = 2,
+    /// No device by that name, no transport both sides can use, or no
+    /// daemon to ask.
+    Unreachable = 3,
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Send {
+        file: String,
+        destination: String,
+        via: Option<String>,
+        yes: bool,
+        timeout: Duration,
+    },
+    Receive {
+        relay: bool,
+        ticket: Option<String>,
+    },
+    Discover {
+        timeout: Option<Duration>,
+    },
+    Devices {
+        timeout: Duration,
+    },
+    Status,
+    Daemon {
+        auto_accept: bool,
+    },
+    Transfers,
+    Accept {
+        id: String,
+    },
+    Reject {
+        id: String,
+    },
+    Cancel {
+        id: String,
+    },
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+struct Cli {
+    json: bool,
+    command: Command,
+}
+
+/// Parses the arguments after the program name. `--json` and `--help` may
+/// appear anywhere; options take their value as the next argument or after `=`.
+fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+    let mut json = false;
+    let mut help = false;
+    let mut positional = Vec::new();
+    let mut options: Vec<(String, Option<String>)> = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" | "help" => help = true,
+            "--yes" | "-y" | "--relay" | "--auto-accept" => options.push((arg.trim_start_matches('-').to_string(), None)),
+            _ if arg.starts_with("--") => {
+                let (name, value) = match arg[2..].split_once('=') {
+                    Some((name, value)) => (name.to_string(), value.to_string()),
+                    None => {
+                        let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+                        (arg[2..].to_string(), value)
+                    }
+                };
+                options.push((name, Some(value)));
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if help || positional.is_empty() {
+        return Ok(Cli { json, command: Command::Help });
+    }
+
+    let name = positional.remove(0);
+    let allowed: &[&str] = match name.as_str() {
+        "send" => &["via", "yes", "y", "timeout"],
+        "receive" => &["relay", "ticket"],
+        "discover" | "devices" => &["timeout"],
+        "daemon" => &["auto-accept"],
+        "status" | "transfers" | "accept" | "reject" | "cancel" => &[],
+        other => return Err(format!("Unknown command '{}'", other)),
+    };
+    if let Some((option, _)) = options.iter().find(|(option, _)| !allowed.contains(&option.as_str())) {
+        return Err(format!("'{}' doesn't take --{}", name, option));
+    }
+    let option = |wanted: &str| options.iter().find(|(option, _)| option == wanted).and_then(|(_, value)| value.clone());
+    let flag = |wanted: &str| options.iter().any(|(option, _)| option == wanted);
+    let timeout = match option("timeout") {
+        Some(secs) => Some(
+            secs.parse::<f64>()
+                .ok()
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                .ok_or_else(|| format!("Invalid --timeout '{}'", secs))?,
+        ),
+        None => None,
+    };
+
+    let expected = match name.as_str() {
+        "send" => 2,
+        "accept" | "reject" | "cancel" => 1,
+        _ => 0,
+    };
+    if positional.len() != expected {
+        return Err(format!("'{}' takes {} arguments, got {}", name, expected, positional.len()));
+    }
+    let command = match name.as_str() {
+        "send" => {
+            let via = option("via");
+            if let Some(via) = via.as_deref().filter(|via| !TRANSPORTS.contains(via)) {
+                return Err(format!("Unknown transport '{}'; use one of {}", via, TRANSPORTS.join(", ")));
+            }
+            let destination = positional.pop().unwrap_or_default();
+            Command::Send {
+                file: positional.pop().unwrap_or_default(),
+                destination,
+                via,
+                yes: flag("yes") || flag("y"),
+                timeout: timeout.unwrap_or(DEFAULT_DISCOVERY),
+            }
+        }
+        "receive" => {
+            let ticket = option("ticket");
+            if flag("relay") && ticket.is_some() {
+                return Err("Use either --relay or --ticket".to_string());
+            }
+            Command::Receive {
+                relay: flag("relay"),
+                ticket,
+            }
+        }
+        "discover" => Command::Discover { timeout },
+        "devices" => Command::Devices {
+            timeout: timeout.unwrap_or(DEFAULT_DISCOVERY),
+        },
+        "daemon" => Command::Daemon {
+            auto_accept: flag("auto-accept"),
+        },
+        "transfers" => Command::Transfers,
+        "accept" => Command::Accept { id: positional.remove(0) },
+        "reject" => Command::Reject { id: positional.remove(0) },
+        "cancel" => Command::Cancel { id: positional.remove(0) },
+        _ => Command::Status,
+    };
+    Ok(Cli { json, command })
+}
+
+/// Where results go: text or JSON lines on stdout.
+struct Output {
+    json: bool,
+    out: Box<dyn Write + Send>,
+}
+
+impl Output {
+    /// In JSON mode, the transports' `println!` logs are moved to stderr so
+    /// only results reach stdout.
+    fn new(json: bool) -> Self {
+        let out: Box<dyn Write + Send> = if json { results_only_stdout() } else { Box::new(io::stdout()) };
+        Output { json, out }
+    }
+
+    /// Prints `text` in text mode, or `value` as one JSON line.
+    fn emit(&mut self, text: &str, value: serde_json::Value) {
+        let line = if self.json { value.to_string() } else { text.to_string() };
+        let _ = writeln!(self.out, "{}", line);
+        let _ = self.out.flush();
+    }
+
+    fn result(&mut self, text: &str, value: impl Serialize) -> Exit {
+        self.emit(text, json!({ "ok": true, "result": value }));
+        Exit::Success
+    }
+
+    fn fail(&mut self, exit: Exit, error: &str) -> Exit {
+        if self.json {
+            self.emit("", json!({ "ok": false, "error": error, "exitCode": exit as u8 }));
+        } else {
+            eprintln!("Error: {}", error);
+        }
+        exit
+    }
+}
+
+/// Points fd 1 at stderr and returns a handle on the original stdout.
+#[cfg(unix)]
+fn results_only_stdout() -> Box<dyn Write + Send> {
+    use std::os::fd::FromRawFd;
+
+    let _ = io::stdout().flush();
+    // SAFETY: dup and dup2 on the standard descriptors, checked for failure;
+    // the duplicate is owned by the returned File alone.
+    unsafe {
+        let original = libc::dup(libc::STDOUT_FILENO);
+        if original < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Box::new(io::stdout());
+        }
+        Box::new(std::fs::File::from_raw_fd(original))
+    }
+}
+
+#[cfg(not(unix))]
+fn results_only_stdout() -> Box<dyn Write + Send> {
+    Box::new(io::stdout())
+}
+
+fn describe(known: &KnownDevice) -> String {
+    format!(
+        "{}  {}  {}  [{}]",
+        known.device.id,
+        known.device.name,
+        known.device.addresses.join(", "),
+        known.device.transports.join(", ")
+    )
+}
+
+/// Starts mDNS browsing and beacons, feeding a fresh registry.
+fn start_discovery() -> DeviceRegistry {
+    let registry = DeviceRegistry::new();
+    registry.spawn_expiry();
+    if let Err(e) = mdns::browse(registry.clone()) {
+        println!("mDNS browsing failed: {}", e);
+    }
+    let beacon_registry = registry.clone();
+    tokio::spawn(async move {
+        if let Err(e) = beacon::start(BeaconConfig::from_env(), beacon_registry).await {
+            println!("Beacon discovery unavailable: {}", e);
+        }
+    });
+    registry
+}
+
+/// Waits up to `timeout` for a device with this ID or name to show up.
+async fn find_device(registry: &DeviceRegistry, id_or_name: &str, timeout: Duration) -> Result<Option<KnownDevice>, String> {
+    let mut events = registry.subscribe();
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(known) = registry.resolve(id_or_name)? {
+            return Ok(Some(known));
+        }
+        tokio::select! {
+            event = events.recv() => {
+                if let Err(RecvError::Closed) = event {
+                    return Ok(None);
+                }
+            }
+            _ = sleep_until(deadline) => return registry.resolve(id_or_name),
+        }
+    }
+}
+
+async fn send(out: &mut Output, file: &str, destination: &str, via: Option<&str>, yes: bool, timeout: Duration) -> Exit {
+    if !std::path::Path::new(file).is_file() {
+        return out.fail(Exit::Usage, &format!("{} is not a file", file));
+    }
+    #[cfg(unix)]
+    if let Some(client) = Client::connect_running().await {
+        return send_with_daemon(out, client, file, destination, via, yes).await;
+    }
+    // Nobody is around to answer metered-connection prompts.
+    let mut confirmations = metered::guard().subscribe();
+    tokio::spawn(async move {
+        while let Ok(request) = confirmations.recv().await {
+            if !yes {
+                eprintln!(
+                    "Declining to send {} ({} bytes) over a {:?} connection; pass --yes to allow it.",
+                    request.file_name, request.size, request.metered
+                );
+            }
+            let _ = metered::guard().answer(&request.id, yes);
+        }
+    });
+
+    // Device IDs and names need discovery; addresses and tickets don't.
+    let is_address = destination.parse::<IpAddr>().is_ok() || mobiledata::is_ticket(destination);
+    let device = if is_address {
+        None
+    } else {
+        match find_device(&start_discovery(), destination, timeout).await {
+            Ok(Some(device)) => Some(device),
+            // Not a device we can see, but maybe a hostname.
+            Ok(None) if tokio::net::lookup_host((destination, 0)).await.is_ok() => None,
+            Ok(None) => {
+                return out.fail(Exit::Unreachable, &format!("No device or host named '{}' found", destination));
+            }
+            Err(e) => return out.fail(Exit::Unreachable, &e),
+        }
+    };
+
+    let result = match (device, via) {
+        (Some(known), _) => {
+            let route = match resolve_route(&known.device).await {
+                Ok(route) => route,
+                Err(e) => return out.fail(Exit::Unreachable, &e),
+            };
+            let transport = via.unwrap_or(route.transport);
+            send_via(transport, file, &route.address)
+                .await
+                .map(|via| format!("File sent to {} via {}", known.device.name, via))
+        }
+        (None, Some(transport)) => send_via(transport, file, destination)
+            .await
+            .map(|via| format!("File sent via {}", via)),
+        (None, None) => send_file_via_best(file, destination).await,
+    };
+    match result {
+        Ok(message) => out.result(&message, &message),
+        Err(e) => out.fail(Exit::Failed, &e.to_string()),
+    }
+}
+
+#[cfg(unix)]
+/// Hands the send to the daemon and follows it until it finishes.
+async fn send_with_daemon(
+    out: &mut Output,
+    mut client: Client,
+    file: &str,
+    destination: &str,
+    via: Option<&str>,
+    yes: bool,
+) -> Exit {
+    // The daemon resolves paths against its own working directory.
+    let file = match std::fs::canonicalize(file) {
+        Ok(file) => file.to_string_lossy().into_owned(),
+        Err(e) => return out.fail(Exit::Usage, &format!("{}: {}", file, e)),
+    };
+    let known = match client.devices().await {
+        Ok(devices) => devices.iter().any(|known| {
+            known.device.id == destination || known.device.name.eq_ignore_ascii_case(destination)
+        }),
+        Err(e) => return out.fail(Exit::Failed, &e.message),
+    };
+    let is_address = destination.parse::<IpAddr>().is_ok() || mobiledata::is_ticket(destination);
+    if !known && !is_address && tokio::net::lookup_host((destination, 0)).await.is_err() {
+        return out.fail(Exit::Unreachable, &format!("No device or host named '{}' found", destination));
+    }
+
+    if let Err(e) = client.subscribe().await {
+        return out.fail(Exit::Failed, &e.message);
+    }
+    let started = match client.send(&file, destination, via).await {
+        Ok(transfer) => transfer,
+        Err(e) => return out.fail(Exit::Failed, &e.message),
+    };
+    let transfer = loop {
+        match client.next_event().await {
+            Ok(Event::MeteredConfirmation { request }) if request.file_name == file => {
+                if !yes {
+                    eprintln!(
+                        "Declining to send {} ({} bytes) over a {:?} connection; pass --yes to allow it.",
+                        request.file_name, request.size, request.metered
+                    );
+                }
+                let _ = client.confirm(&request.id, yes).await;
+            }
+            Ok(Event::Transfer { transfer }) if transfer.id == started.id && transfer.state.is_finished() => {
+                break transfer;
+            }
+            Ok(_) => {}
+            Err(e) => return out.fail(Exit::Failed, &e.message),
+        }
+    };
+    finished(out, transfer)
+}
+
+#[cfg(unix)]
+/// Reports a finished transfer.
+fn finished(out: &mut Output, transfer: Transfer) -> Exit {
+    let message = transfer.message.clone().unwrap_or_default();
+    match transfer.state {
+        TransferState::Done => out.result(&message, &transfer),
+        _ => out.fail(Exit::Failed, &message),
+    }
+}
+
+#[cfg(unix)]
+fn describe_transfer(transfer: &Transfer) -> String {
+    let arrow = match transfer.direction {
+        Direction::Outgoing => "→",
+        Direction::Incoming => "←",
+    };
+    format!(
+        "{}  {:?}  {} {}  {} ({} bytes){}",
+        transfer.id,
+        transfer.state,
+        arrow,
+        transfer.peer,
+        transfer.name,
+        transfer.size,
+        transfer.message.as_ref().map(|message| format!("  {}", message)).unwrap_or_default()
+    )
+}
+
+#[cfg(unix)]
+/// Accepts the next file the daemon is offered and waits for it to arrive.
+async fn receive_with_daemon(out: &mut Output, mut client: Client) -> Exit {
+    out.emit("Waiting for the daemon to be offered a file...", json!({ "event": "waiting" }));
+    match client.receive_next().await {
+        Ok(transfer) => finished(out, transfer),
+        Err(e) => out.fail(Exit::Failed, &e.message),
+    }
+}
+
+#[cfg(unix)]
+async fn run_daemon(out: &mut Output, auto_accept: bool) -> Exit {
+    let mut config = DaemonConfig::from_env();
+    config.auto_accept |= auto_accept;
+    match daemon::run(config).await.map_err(|e| e.to_string()) {
+        Ok(()) => Exit::Success,
+        Err(e) => out.fail(Exit::Failed, &e),
+    }
+}
+
+#[cfg(unix)]
+/// Connects to the running daemon, or reports that there isn't one.
+async fn daemon_client(out: &mut Output) -> Result<Client, Exit> {
+    Client::connect_running()
+        .await
+        .ok_or_else(|| out.fail(Exit::Unreachable, "No daemon running; start one with 'unishare daemon'"))
+}
+
+#[cfg(unix)]
+async fn transfers(out: &mut Output) -> Exit {
+    let mut client = match daemon_client(out).await {
+        Ok(client) => client,
+        Err(exit) => return exit,
+    };
+    match client.transfers().await {
+        Ok(transfers) if transfers.is_empty() => out.result("No transfers.", &transfers),
+        Ok(transfers) => {
+            let text = transfers.iter().map(describe_transfer).collect::<Vec<_>>().join("\n");
+            out.result(&text, &transfers)
+        }
+        Err(e) => out.fail(Exit::Failed, &e.message),
+    }
+}
+
+#[cfg(unix)]
+/// Accepts, rejects or cancels one of the daemon's transfers.
+async fn answer(out: &mut Output, command: Command) -> Exit {
+    let mut client = match daemon_client(out).await {
+        Ok(client) => client,
+        Err(exit) => return exit,
+    };
+    let result = match command {
+        Command::Accept { id } => client.accept(&id).await,
+        Command::Reject { id } => client.reject(&id).await,
+        Command::Cancel { id } => client.cancel(&id).await,
+        _ => unreachable!("not a transfer command"),
+    };
+    match result {
+        Ok(transfer) => out.result(&describe_transfer(&transfer), &transfer),
+        Err(e) => out.fail(Exit::Failed, &e.message),
+    }
+}
+
+async fn receive(out: &mut Output, relay: bool, ticket: Option<String>) -> Exit {
+    // The daemon's receivers hold the ports; take the file from it instead.
+    #[cfg(unix)]
+    if !relay && ticket.is_none() {
+        if let Some(client) = Client::connect_running().await {
+            return receive_with_daemon(out, client).await;
+        }
+    }
+    // Let senders find us and ask which receivers are running.
+    if let Err(e) = mdns::advertise() {
+        println!("mDNS advertising failed: {}", e);
+    }
+    tokio::spawn(async {
+        if let Err(e) = capabilities::serve_hello().await {
+            println!("Capability queries unavailable: {}", e);
+        }
+    });
+
+    let ticket = match ticket {
+        Some(ticket) => Some(ticket),
+        None if relay => match mobiledata::create_ticket().await {
+            Ok(ticket) => {
+                out.emit(&format!("Ticket: {}", ticket), json!({ "ticket": ticket }));
+                Some(ticket)
+            }
+            Err(e) => return out.fail(Exit::Failed, &e.to_string()),
+        },
+        None => None,
+    };
+    match ticket {
+        Some(ticket) => match mobiledata::receive_file(&ticket).await {
+            Ok(received) => out.result(&format!("Saved {}", received.path.display()), &received),
+            Err(e) => out.fail(Exit::Failed, &e.to_string()),
+        },
+        None => match start_receiver().await {
+            Ok(received) => out.result(
+                &format!("{}; saved {}", received.message, received.file.path.display()),
+                &received,
+            ),
+            Err(e) => out.fail(Exit::Failed, &e.to_string()),
+        },
+    }
+}
+
+async fn discover(out: &mut Output, timeout: Option<Duration>) -> Exit {
+    let registry = start_discovery();
+    let mut events = registry.subscribe();
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(DiscoveryEvent::Discovered(device)) => {
+                    let text = format!("+ {}  {}  {}", device.id, device.name, device.addresses.join(", "));
+                    out.emit(&text, json!({ "event": "discovered", "device": device }));
+                }
+                Ok(DiscoveryEvent::Lost { id }) => {
+                    out.emit(&format!("- {}", id), json!({ "event": "lost", "id": id }));
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            _ = async { sleep_until(deadline.unwrap()).await }, if deadline.is_some() => break,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    Exit::Success
+}
+
+async fn devices(out: &mut Output, timeout: Duration) -> Exit {
+    // A running daemon has been watching all along, so there's no need to wait.
+    #[cfg(unix)]
+    if let Some(mut client) = Client::connect_running().await {
+        return match client.devices().await {
+            Ok(devices) => list_devices(out, &devices),
+            Err(e) => out.fail(Exit::Failed, &e.message),
+        };
+    }
+    let registry = start_discovery();
+    tokio::time::sleep(timeout).await;
+    list_devices(out, &registry.devices())
+}
+
+fn list_devices(out: &mut Output, devices: &[KnownDevice]) -> Exit {
+    let text = if devices.is_empty() {
+        "No devices found.".to_string()
+    } else {
+        devices.iter().map(describe).collect::<Vec<_>>().join("\n")
+    };
+    out.result(&text, devices)
+}
+
+async fn status(out: &mut Output) -> Exit {
+    let report = match connectivity_report(&InternetConfig::from_env()).await {
+        Ok(report) => report,
+        Err(e) => return out.fail(Exit::Failed, &e),
+    };
+    let interfaces = tokio::task::spawn_blocking(network_interfaces).await.unwrap_or_default();
+    let local = device_discovery::local_device();
+
+    let mut text = vec![
+        format!("Device: {} ({})", local.name, local.id),
+        format!("Internet: {:?}", report.internet.state),
+        format!("Wi‑Fi Direct: {}", if report.adapters.wifi_direct { "available" } else { "unavailable" }),
+    ];
+    for adapter in &report.adapters.bluetooth {
+        text.push(format!(
+            "Bluetooth {}: {}",
+            adapter.name,
+            if adapter.available { "on" } else { "off" }
+        ));
+    }
+    for interface in &interfaces {
+        let addresses: Vec<String> = interface.addresses.iter().map(|a| format!("{}/{}", a.address, a.prefix)).collect();
+        text.push(format!("Interface {} ({:?}): {}", interface.name, interface.kind, addresses.join(", ")));
+    }
+    let value = json!({
+        "device": { "id": local.id, "name": local.name, "transports": local.transports },
+        "connectivity": report,
+        "interfaces": interfaces,
+    });
+    out.result(&text.join("\n"), value)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = match parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(Exit::Usage as u8);
+        }
+    };
+    let mut out = Output::new(cli.json);
+    let exit = match cli.command {
+        Command::Send {
+            file,
+            destination,
+            via,
+            yes,
+            timeout,
+        } => send(&mut out, &file, &destination, via.as_deref(), yes, timeout).await,
+        Command::Receive { relay, ticket } => receive(&mut out, relay, ticket).await,
+        Command::Discover { timeout } => discover(&mut out, timeout).await,
+        Command::Devices { timeout } => devices(&mut out, timeout).await,
+        Command::Status => status(&mut out).await,
+        #[cfg(unix)]
+        Command::Daemon { auto_accept } => run_daemon(&mut out, auto_accept).await,
+        #[cfg(unix)]
+        Command::Transfers => transfers(&mut out).await,
+        #[cfg(unix)]
+        command @ (Command::Accept { .. } | Command::Reject { .. } | Command::Cancel { .. }) => {
+            answer(&mut out, command).await
+        }
+        #[cfg(not(unix))]
+        Command::Daemon { .. }
+        | Command::Transfers
+        | Command::Accept { .. }
+        | Command::Reject { .. }
+        | Command::Cancel { .. } => out.fail(Exit::Unreachable, NO_DAEMON),
+        Command::Help => {
+            println!("{}", USAGE);
+            Exit::Success
+        }
+    };
+    ExitCode::from(exit as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &str) -> Result<Cli, String> {
+        parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_send() {
+        let cli = parse_str("--json send notes.txt laptop --via=webrtc --timeout 1.5 -y").unwrap();
+        assert!(cli.json);
+        assert_eq!(
+            cli.command,
+            Command::Send {
+                file: "notes.txt".to_string(),
+                destination: "laptop".to_string(),
+                via: Some("webrtc".to_string()),
+                yes: true,
+                timeout: Duration::from_millis(1500),
+            }
+        );
+        let cli = parse_str("send a.bin 192.168.1.4").unwrap();
+        assert!(matches!(cli.command, Command::Send { via: None, yes: false, timeout: DEFAULT_DISCOVERY, .. }));
+    }
+
+    #[test]
+    fn parses_the_other_commands() {
+        assert_eq!(
+            parse_str("receive --relay --json").unwrap(),
+            Cli {
+                json: true,
+                command: Command::Receive { relay: true, ticket: None }
+            }
+        );
+        assert_eq!(
+            parse_str("discover --timeout 10").unwrap().command,
+            Command::Discover {
+                timeout: Some(Duration::from_secs(10))
+            }
+        );
+        assert_eq!(parse_str("discover").unwrap().command, Command::Discover { timeout: None });
+        assert_eq!(parse_str("devices").unwrap().command, Command::Devices { timeout: DEFAULT_DISCOVERY });
+        assert_eq!(parse_str("status").unwrap().command, Command::Status);
+        assert_eq!(parse_str("daemon --auto-accept").unwrap().command, Command::Daemon { auto_accept: true });
+        assert_eq!(parse_str("transfers").unwrap().command, Command::Transfers);
+        assert_eq!(
+            parse_str("accept incoming-3").unwrap().command,
+            Command::Accept {
+                id: "incoming-3".to_string()
+            }
+        );
+        assert_eq!(
+            parse_str("cancel outgoing-1").unwrap().command,
+            Command::Cancel {
+                id: "outgoing-1".to_string()
+            }
+        );
+        assert_eq!(parse_str("").unwrap().command, Command::Help);
+        assert_eq!(parse_str("send --help").unwrap().command, Command::Help);
+    }
+
+    #[test]
+    fn rejects_bad_usage() {
+        for args in [
+            "frobnicate",
+            "send only-a-file",
+            "send a b c",
+            "send a b --via carrier-pigeon",
+            "send a b --timeout soon",
+            "send a b --timeout",
+            "send a b --timeout 1e30",
+            "send a b --timeout -1",
+            "send a b --timeout NaN",
+            "status --relay",
+            "receive --relay --ticket abc",
+            "devices extra",
+            "accept",
+            "reject a b",
+            "daemon --relay",
+        ] {
+            assert!(parse_str(args).is_err(), "{}", args);
+        }
+    }
+}
@@ -0,0 +1,687 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::detect::{detect, SAMPLE_SIZE};
+
+const BUFFER_SIZE: usize = 64 * 1024;
+/// Written in place of characters the target encoding can't represent.
+const UNMAPPABLE: char = '?';
+
+/// Windows-1252 code points for bytes 0x80..=0x9F. The five bytes Windows
+/// leaves undefined map to the matching C1 control, as browsers do.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// Text encodings the converter reads and writes.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    #[serde(rename = "UTF-8")]
+    Utf8,
+    #[serde(rename = "UTF-16LE")]
+    Utf16Le,
+    #[serde(rename = "UTF-16BE")]
+    Utf16Be,
+    #[serde(rename = "UTF-32LE")]
+    Utf32Le,
+    #[serde(rename = "UTF-32BE")]
+    Utf32Be,
+    /// ISO-8859-1: each byte is the code point of the same value.
+    #[serde(rename = "ISO-8859-1")]
+    Latin1,
+    #[serde(rename = "windows-1252")]
+    Windows1252,
+    /// Cyrillic.
+    #[serde(rename = "windows-1251")]
+    Windows1251,
+    /// Japanese, as written by Windows (code page 932).
+    #[serde(rename = "Shift_JIS")]
+    ShiftJis,
+    /// Simplified Chinese (code page 936).
+    #[serde(rename = "GBK")]
+    Gbk,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Utf32Le => "UTF-32LE",
+            Encoding::Utf32Be => "UTF-32BE",
+            Encoding::Latin1 => "ISO-8859-1",
+            Encoding::Windows1252 => "windows-1252",
+            Encoding::Windows1251 => "windows-1251",
+            Encoding::ShiftJis => "Shift_JIS",
+            Encoding::Gbk => "GBK",
+        }
+    }
+
+    /// The `encoding_rs` codec behind the legacy encodings we don't
+    /// hand-roll.
+    fn legacy(self) -> Option<&'static encoding_rs::Encoding> {
+        match self {
+            Encoding::Windows1251 => Some(encoding_rs::WINDOWS_1251),
+            Encoding::ShiftJis => Some(encoding_rs::SHIFT_JIS),
+            Encoding::Gbk => Some(encoding_rs::GBK),
+            _ => None,
+        }
+    }
+
+    /// The byte order mark, for encodings that have one.
+    pub fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => &[0xEF, 0xBB, 0xBF],
+            Encoding::Utf16Le => &[0xFF, 0xFE],
+            Encoding::Utf16Be => &[0xFE, 0xFF],
+            Encoding::Utf32Le => &[0xFF, 0xFE, 0x00, 0x00],
+            Encoding::Utf32Be => &[0x00, 0x00, 0xFE, 0xFF],
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    /// Accepts common labels, ignoring case, `-` and `_`: `utf8`, `UTF-16LE`,
+    /// `utf_32be`, `latin1`, `cp1252`, `sjis`, `cp936`, ... Plain `UTF-16`/`UTF-32` mean
+    /// little-endian, as Windows writes them.
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        let normalized: String = label
+            .chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match normalized.as_str() {
+            "utf8" => Ok(Encoding::Utf8),
+            "utf16" | "utf16le" | "ucs2" => Ok(Encoding::Utf16Le),
+            "utf16be" => Ok(Encoding::Utf16Be),
+            "utf32" | "utf32le" => Ok(Encoding::Utf32Le),
+            "utf32be" => Ok(Encoding::Utf32Be),
+            "latin1" | "iso88591" | "l1" => Ok(Encoding::Latin1),
+            "windows1252" | "cp1252" => Ok(Encoding::Windows1252),
+            "windows1251" | "cp1251" => Ok(Encoding::Windows1251),
+            "shiftjis" | "sjis" | "cp932" | "mskanji" => Ok(Encoding::ShiftJis),
+            "gbk" | "cp936" | "gb2312" => Ok(Encoding::Gbk),
+            _ => Err(format!("Unsupported encoding '{}'", label)),
+        }
+    }
+}
+
+/// Recognizes a byte order mark at the start of `bytes`; returns the
+/// encoding and the mark's length.
+pub fn detect_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    // UTF-32LE's mark starts with UTF-16LE's, so it's checked first.
+    [
+        Encoding::Utf32Le,
+        Encoding::Utf32Be,
+        Encoding::Utf8,
+        Encoding::Utf16Le,
+        Encoding::Utf16Be,
+    ]
+    .into_iter()
+    .find(|encoding| bytes.starts_with(encoding.bom()))
+    .map(|encoding| (encoding, encoding.bom().len()))
+}
+
+/// Line ending style to write.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Newline {
+    Lf,
+    Crlf,
+}
+
+impl Newline {
+    /// The convention of the platform we're running on.
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            Newline::Crlf
+        } else {
+            Newline::Lf
+        }
+    }
+}
+
+impl FromStr for Newline {
+    type Err = String;
+
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        match label.to_ascii_lowercase().as_str() {
+            "lf" | "unix" => Ok(Newline::Lf),
+            "crlf" | "windows" => Ok(Newline::Crlf),
+            "native" => Ok(Newline::native()),
+            _ => Err(format!("Unsupported line ending '{}'", label)),
+        }
+    }
+}
+
+/// What to convert from and to.
+#[derive(Clone, Copy, Debug)]
+pub struct ConvertOptions {
+    /// Source encoding; `None` uses the input's BOM, or detects it from
+    /// the content.
+    pub from: Option<Encoding>,
+    pub to: Encoding,
+    /// Start the output with the target's BOM (ignored for encodings without one).
+    pub write_bom: bool,
+    /// Rewrite every LF and CRLF line ending to this; `None` keeps them.
+    /// Lone CRs are left alone.
+    pub newline: Option<Newline>,
+}
+
+/// What a conversion did.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionReport {
+    pub source: Option<Encoding>,
+    /// How sure detection was of `source`; `None` when it was given.
+    pub confidence: Option<f32>,
+    pub target: Option<Encoding>,
+    /// The input started with a BOM, which was dropped.
+    pub bom_found: bool,
+    pub bom_written: bool,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub chars: u64,
+    /// Malformed input sequences, replaced with U+FFFD.
+    pub invalid_sequences: u64,
+    /// Characters the target can't represent, replaced with `?`.
+    pub unmappable_chars: u64,
+    /// Line endings rewritten to [`ConvertOptions::newline`].
+    pub newlines_converted: u64,
+}
+
+/// Incremental decoder: bytes go in chunk by chunk, characters come out.
+/// Sequences split across chunks are held back until the next one.
+struct Decoder {
+    encoding: Encoding,
+    pending: Vec<u8>,
+    invalid: u64,
+    /// Keeps its own state between chunks, so `pending` stays empty.
+    legacy: Option<encoding_rs::Decoder>,
+}
+
+impl Decoder {
+    fn new(encoding: Encoding) -> Self {
+        Decoder {
+            encoding,
+            pending: Vec::new(),
+            invalid: 0,
+            legacy: encoding.legacy().map(encoding_rs::Encoding::new_decoder_without_bom_handling),
+        }
+    }
+
+    /// Decodes `input` onto `out`. With `last`, incomplete trailing bytes are
+    /// reported as invalid instead of held back.
+    fn decode(&mut self, input: &[u8], out: &mut String, last: bool) {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(input);
+        let consumed = match self.encoding {
+            Encoding::Utf8 => self.decode_utf8(&bytes, out, last),
+            Encoding::Utf16Le => self.decode_utf16(&bytes, out, last, u16::from_le_bytes),
+            Encoding::Utf16Be => self.decode_utf16(&bytes, out, last, u16::from_be_bytes),
+            Encoding::Utf32Le => self.decode_utf32(&bytes, out, last, u32::from_le_bytes),
+            Encoding::Utf32Be => self.decode_utf32(&bytes, out, last, u32::from_be_bytes),
+            Encoding::Latin1 => {
+                out.extend(bytes.iter().map(|&b| char::from(b)));
+                bytes.len()
+            }
+            Encoding::Windows1252 => {
+                out.extend(bytes.iter().map(|&b| match b {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[usize::from(b - 0x80)],
+                    _ => char::from(b),
+                }));
+                bytes.len()
+            }
+            Encoding::Windows1251 | Encoding::ShiftJis | Encoding::Gbk => self.decode_legacy(&bytes, out, last),
+        };
+        self.pending = bytes[consumed..].to_vec();
+    }
+
+    fn decode_legacy(&mut self, bytes: &[u8], out: &mut String, last: bool) -> usize {
+        let Some(decoder) = self.legacy.as_mut() else {
+            return 0;
+        };
+        let mut rest = bytes;
+        loop {
+            // The decoder only writes into spare capacity.
+            out.reserve(
+                decoder
+                    .max_utf8_buffer_length_without_replacement(rest.len())
+                    .unwrap_or(rest.len() * 3 + 4),
+            );
+            let (result, read) = decoder.decode_to_string_without_replacement(rest, out, last);
+            rest = &rest[read..];
+            match result {
+                encoding_rs::DecoderResult::InputEmpty => return bytes.len(),
+                encoding_rs::DecoderResult::OutputFull => {}
+                encoding_rs::DecoderResult::Malformed(..) => {
+                    out.push(char::REPLACEMENT_CHARACTER);
+                    self.invalid += 1;
+                }
+            }
+        }
+    }
+
+    fn decode_utf8(&mut self, bytes: &[u8], out: &mut String, last: bool) -> usize {
+        let mut rest = bytes;
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    out.push_str(valid);
+                    return bytes.len();
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    // SAFETY: from_utf8 just validated this prefix.
+                    out.push_str(unsafe { std::str::from_utf8_unchecked(valid) });
+                    match e.error_len() {
+                        Some(len) => {
+                            out.push(char::REPLACEMENT_CHARACTER);
+                            self.invalid += 1;
+                            rest = &after[len..];
+                        }
+                        // The chunk ends mid-character.
+                        None if !last => return bytes.len() - after.len(),
+                        None => {
+                            out.push(char::REPLACEMENT_CHARACTER);
+                            self.invalid += 1;
+                            return bytes.len();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn decode_utf16(&mut self, bytes: &[u8], out: &mut String, last: bool, unit: fn([u8; 2]) -> u16) -> usize {
+        let mut units: Vec<u16> = bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]])).collect();
+        let mut consumed = units.len() * 2;
+        // A high surrogate at the end may pair with the next chunk's first unit.
+        if !last && units.last().is_some_and(|u| (0xD800..0xDC00).contains(u)) {
+            units.pop();
+            consumed -= 2;
+        }
+        for c in char::decode_utf16(units) {
+            out.push(c.unwrap_or_else(|_| {
+                self.invalid += 1;
+                char::REPLACEMENT_CHARACTER
+            }));
+        }
+        if last && consumed < bytes.len() {
+            self.invalid += 1;
+            out.push(char::REPLACEMENT_CHARACTER);
+            consumed = bytes.len();
+        }
+        consumed
+    }
+
+    fn decode_utf32(&mut self, bytes: &[u8], out: &mut String, last: bool, unit: fn([u8; 4]) -> u32) -> usize {
+        let mut consumed = 0;
+        for quad in bytes.chunks_exact(4) {
+            out.push(char::from_u32(unit([quad[0], quad[1], quad[2], quad[3]])).unwrap_or_else(|| {
+                self.invalid += 1;
+                char::REPLACEMENT_CHARACTER
+            }));
+            consumed += 4;
+        }
+        if last && consumed < bytes.len() {
+            self.invalid += 1;
+            out.push(char::REPLACEMENT_CHARACTER);
+            consumed = bytes.len();
+        }
+        consumed
+    }
+}
+
+/// Appends `c` in `encoding`; returns `false` (writing nothing) if it can't
+/// be represented.
+fn encode_char(c: char, encoding: Encoding, out: &mut Vec<u8>) -> bool {
+    match encoding {
+        Encoding::Utf8 => {
+            let mut buf = [0u8; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let mut buf = [0u16; 2];
+            for unit in c.encode_utf16(&mut buf) {
+                out.extend_from_slice(&match encoding {
+                    Encoding::Utf16Le => unit.to_le_bytes(),
+                    _ => unit.to_be_bytes(),
+                });
+            }
+        }
+        Encoding::Utf32Le => out.extend_from_slice(&u32::from(c).to_le_bytes()),
+        Encoding::Utf32Be => out.extend_from_slice(&u32::from(c).to_be_bytes()),
+        Encoding::Latin1 => match u8::try_from(u32::from(c)) {
+            Ok(byte) => out.push(byte),
+            Err(_) => return false,
+        },
+        Encoding::Windows1252 => {
+            let byte = match u32::from(c) {
+                code @ (0x00..=0x7F | 0xA0..=0xFF) => Some(code as u8),
+                _ => WINDOWS_1252_HIGH
+                    .iter()
+                    .position(|&mapped| mapped == c)
+                    .map(|i| 0x80 + i as u8),
+            };
+            match byte {
+                Some(byte) => out.push(byte),
+                None => return false,
+            }
+        }
+        Encoding::Windows1251 | Encoding::ShiftJis | Encoding::Gbk => {
+            let Some(legacy) = encoding.legacy() else {
+                return false;
+            };
+            let mut buf = [0u8; 4];
+            let (bytes, _, unmappable) = legacy.encode(c.encode_utf8(&mut buf));
+            if unmappable {
+                return false;
+            }
+            out.extend_from_slice(&bytes);
+        }
+    }
+    true
+}
+
+/// Encodes `c`, substituting [`UNMAPPABLE`] if the target can't represent it.
+fn push_char(c: char, encoding: Encoding, out: &mut Vec<u8>, report: &mut ConversionReport) {
+    if !encode_char(c, encoding, out) {
+        report.unmappable_chars += 1;
+        encode_char(UNMAPPABLE, encoding, out);
+    }
+}
+
+/// Streams `reader` to `writer`, converting between encodings without
+/// holding the whole input in memory.
+pub fn convert<R: Read, W: Write>(mut reader: R, mut writer: W, options: ConvertOptions) -> io::Result<ConversionReport> {
+    let mut report = ConversionReport {
+        target: Some(options.to),
+        ..Default::default()
+    };
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+
+    // Read enough to recognize any BOM, or to detect the encoding when
+    // none was given, before choosing the decoder.
+    let wanted = if options.from.is_some() { 4 } else { SAMPLE_SIZE };
+    let mut head = Vec::new();
+    reader.by_ref().take(wanted as u64).read_to_end(&mut head)?;
+    report.bytes_read = head.len() as u64;
+    let bom = detect_bom(&head);
+    let source = match (options.from, bom) {
+        (Some(from), _) => from,
+        (None, Some((encoding, _))) => {
+            report.confidence = Some(1.0);
+            encoding
+        }
+        (None, None) => {
+            let detection = detect(&head);
+            report.confidence = Some(detection.confidence);
+            detection.encoding.unwrap_or(Encoding::Utf8)
+        }
+    };
+    // Only a BOM belonging to the source encoding is stripped.
+    let skip = match bom {
+        Some((encoding, len)) if encoding == source => len,
+        _ => 0,
+    };
+    report.source = Some(source);
+    report.bom_found = skip > 0;
+
+    let mut decoder = Decoder::new(source);
+    let mut text = String::new();
+    let mut encoded = Vec::with_capacity(BUFFER_SIZE * 2);
+    if options.write_bom && !options.to.bom().is_empty() {
+        encoded.extend_from_slice(options.to.bom());
+        report.bom_written = true;
+    }
+
+    let mut chunk = head[skip..].to_vec();
+    // A short head means the input already ended.
+    let mut last = head.len() < wanted;
+    // A CR whose LF may be in the next chunk.
+    let mut pending_cr = false;
+    loop {
+        decoder.decode(&chunk, &mut text, last);
+        for c in text.chars() {
+            report.chars += 1;
+            let Some(newline) = options.newline else {
+                push_char(c, options.to, &mut encoded, &mut report);
+                continue;
+            };
+            match c {
+                '\r' => {
+                    if pending_cr {
+                        push_char('\r', options.to, &mut encoded, &mut report);
+                    }
+                    pending_cr = true;
+                }
+                '\n' => {
+                    let was = if pending_cr { Newline::Crlf } else { Newline::Lf };
+                    pending_cr = false;
+                    if was != newline {
+                        report.newlines_converted += 1;
+                    }
+                    if newline == Newline::Crlf {
+                        push_char('\r', options.to, &mut encoded, &mut report);
+                    }
+                    push_char('\n', options.to, &mut encoded, &mut report);
+                }
+                c => {
+                    if std::mem::take(&mut pending_cr) {
+                        push_char('\r', options.to, &mut encoded, &mut report);
+                    }
+                    push_char(c, options.to, &mut encoded, &mut report);
+                }
+            }
+        }
+        if last && pending_cr {
+            push_char('\r', options.to, &mut encoded, &mut report);
+        }
+        text.clear();
+        writer.write_all(&encoded)?;
+        report.bytes_written += encoded.len() as u64;
+        encoded.clear();
+        if last {
+            break;
+        }
+
+        let n = reader.read(&mut buffer)?;
+        report.bytes_read += n as u64;
+        chunk = buffer[..n].to_vec();
+        last = n == 0;
+    }
+    writer.flush()?;
+    report.invalid_sequences = decoder.invalid;
+    Ok(report)
+}
+
+/// Converts `input` into `output`. The result is written next to `output`
+/// first and renamed into place, so converting a file onto itself works and
+/// a failure never leaves a half-written file behind.
+pub fn convert_file(input: &Path, output: &Path, options: ConvertOptions) -> io::Result<ConversionReport> {
+    let reader = BufReader::new(File::open(input)?);
+    let mut temp = PathBuf::from(output);
+    temp.set_file_name(format!(
+        ".{}.converting",
+        output.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()
+    ));
+
+    let result = File::create(&temp).and_then(|file| convert(reader, BufWriter::new(file), options));
+    match result {
+        Ok(report) => {
+            fs::rename(&temp, output)?;
+            println!(
+                "Converted {} ({}) to {} ({}): {} chars",
+                input.display(),
+                report.source.map(Encoding::name).unwrap_or("?"),
+                output.display(),
+                options.to,
+                report.chars
+            );
+            Ok(report)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out at most `step` bytes per read, so every multi-byte
+    /// sequence ends up split across reads somewhere.
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.step.min(buf.len()).min(self.bytes.len());
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Ok(n)
+        }
+    }
+
+    fn options(from: Option<Encoding>, to: Encoding) -> ConvertOptions {
+        ConvertOptions {
+            from,
+            to,
+            write_bom: false,
+            newline: None,
+        }
+    }
+
+    fn run(input: &[u8], step: usize, options: ConvertOptions) -> (Vec<u8>, ConversionReport) {
+        let mut output = Vec::new();
+        let report = convert(Trickle { bytes: input, step }, &mut output, options).unwrap();
+        (output, report)
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn strips_and_writes_boms() {
+        let (output, report) = run(b"\xEF\xBB\xBFhello", 1, options(None, Encoding::Utf8));
+        assert_eq!(output, b"hello");
+        assert!(report.bom_found);
+        assert!(!report.bom_written);
+
+        let write_bom = ConvertOptions {
+            write_bom: true,
+            ..options(Some(Encoding::Utf8), Encoding::Utf16Le)
+        };
+        let (output, report) = run(b"hi", 1, write_bom);
+        assert_eq!(output, [&[0xFF, 0xFE][..], &utf16le("hi")].concat());
+        assert!(report.bom_written);
+
+        // Only the source encoding's own BOM is stripped.
+        let (output, report) = run(b"\xEF\xBB\xBFA", 1, options(Some(Encoding::Latin1), Encoding::Utf8));
+        assert_eq!(output, "ï»¿A".as_bytes());
+        assert!(!report.bom_found);
+    }
+
+    #[test]
+    fn surrogate_pairs_survive_utf16_and_utf32() {
+        let text = "emoji 😀 and 𝄞";
+        for step in [1, 3, 7] {
+            let (utf16, _) = run(text.as_bytes(), step, options(Some(Encoding::Utf8), Encoding::Utf16Be));
+            let (utf32, _) = run(&utf16, step, options(Some(Encoding::Utf16Be), Encoding::Utf32Le));
+            assert_eq!(utf32.len(), text.chars().count() * 4);
+            let (utf8, report) = run(&utf32, step, options(Some(Encoding::Utf32Le), Encoding::Utf8));
+            assert_eq!(String::from_utf8(utf8).unwrap(), text);
+            assert_eq!(report.invalid_sequences, 0);
+        }
+
+        // A lone high surrogate is invalid, wherever the read ends.
+        let mut lone = utf16le("a");
+        lone.extend_from_slice(&0xD83Du16.to_le_bytes());
+        lone.extend(utf16le("b"));
+        let (output, report) = run(&lone, 1, options(Some(Encoding::Utf16Le), Encoding::Utf8));
+        assert_eq!(String::from_utf8(output).unwrap(), "a\u{FFFD}b");
+        assert_eq!(report.invalid_sequences, 1);
+    }
+
+    #[test]
+    fn sequences_split_across_reads_decode_whole() {
+        let text = "día € 😀 日本語";
+        for step in 1..=5 {
+            let (output, report) = run(text.as_bytes(), step, options(Some(Encoding::Utf8), Encoding::Utf8));
+            assert_eq!(String::from_utf8(output).unwrap(), text, "step {}", step);
+            assert_eq!(report.invalid_sequences, 0);
+        }
+
+        let (sjis, _, _) = encoding_rs::SHIFT_JIS.encode("日本語のテキスト");
+        let (output, report) = run(&sjis, 1, options(Some(Encoding::ShiftJis), Encoding::Utf8));
+        assert_eq!(String::from_utf8(output).unwrap(), "日本語のテキスト");
+        assert_eq!(report.invalid_sequences, 0);
+
+        // Cut off for good at the end of the input.
+        let (output, report) = run(b"caf\xC3", 1, options(Some(Encoding::Utf8), Encoding::Utf8));
+        assert_eq!(String::from_utf8(output).unwrap(), "caf\u{FFFD}");
+        assert_eq!(report.invalid_sequences, 1);
+    }
+
+    #[test]
+    fn converts_line_endings() {
+        let input = b"a\r\nb\nc\rd\r";
+        let with = |newline| ConvertOptions {
+            newline: Some(newline),
+            ..options(Some(Encoding::Utf8), Encoding::Utf8)
+        };
+
+        for step in [1, 2, 64] {
+            let (output, report) = run(input, step, with(Newline::Crlf));
+            assert_eq!(output, b"a\r\nb\r\nc\rd\r");
+            assert_eq!(report.newlines_converted, 1);
+
+            let (output, report) = run(input, step, with(Newline::Lf));
+            assert_eq!(output, b"a\nb\nc\rd\r");
+            assert_eq!(report.newlines_converted, 1);
+        }
+
+        let (output, report) = run(input, 1, options(Some(Encoding::Utf8), Encoding::Utf8));
+        assert_eq!(output, input);
+        assert_eq!(report.newlines_converted, 0);
+    }
+
+    #[test]
+    fn replaces_unmappable_characters() {
+        let (output, report) = run("café € 漢".as_bytes(), 1, options(Some(Encoding::Utf8), Encoding::Latin1));
+        assert_eq!(output, b"caf\xE9 ? ?");
+        assert_eq!(report.unmappable_chars, 2);
+
+        let (output, report) = run("€ Ж".as_bytes(), 1, options(Some(Encoding::Utf8), Encoding::Windows1252));
+        assert_eq!(output, b"\x80 ?");
+        assert_eq!(report.unmappable_chars, 1);
+
+        let (output, report) = run("Жизнь é".as_bytes(), 1, options(Some(Encoding::Utf8), Encoding::Windows1251));
+        assert_eq!(output, b"\xC6\xE8\xE7\xED\xFC ?");
+        assert_eq!(report.unmappable_chars, 1);
+    }
+}
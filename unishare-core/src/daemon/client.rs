@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::UnixStream;
+
+use super::rpc::{self, Event, Message, Request, RpcError, SendParams};
+use super::{current_uid, socket_path};
+use super::transfers::{Direction, Transfer, TransferState};
+use crate::device_discovery::KnownDevice;
+
+/// A connection to a running daemon. Calls wait for their response; events
+/// that arrive meanwhile are kept for [`Client::next_event`].
+pub struct Client {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+    next_id: u64,
+    events: VecDeque<Event>,
+}
+
+impl Client {
+    pub async fn connect(path: &Path) -> io::Result<Self> {
+        let stream = UnixStream::connect(path).await?;
+        // Whoever listens there gets our file paths and accept/reject calls,
+        // so it has to be our own daemon.
+        let peer = stream.peer_cred()?.uid();
+        if peer != current_uid() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is served by another user (uid {})", path.display(), peer),
+            ));
+        }
+        let (reader, writer) = stream.into_split();
+        Ok(Client {
+            reader: BufReader::new(reader),
+            writer,
+            next_id: 1,
+            events: VecDeque::new(),
+        })
+    }
+
+    /// Connects to the daemon at [`socket_path`], or `None` if none is running.
+    pub async fn connect_running() -> Option<Self> {
+        Client::connect(&socket_path()).await.ok()
+    }
+
+    /// Calls `method` and decodes its result.
+    pub async fn call<T: DeserializeOwned>(&mut self, method: &str, params: Value) -> Result<T, RpcError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = Request {
+            jsonrpc: rpc::VERSION.to_string(),
+            id: Some(json!(id)),
+            method: method.to_string(),
+            params,
+        };
+        let mut line = serde_json::to_string(&request).map_err(|e| RpcError::failed(e.to_string()))?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await.map_err(lost)?;
+
+        loop {
+            let message = self.read().await?;
+            if message.id != Some(json!(id)) {
+                continue;
+            }
+            if let Some(error) = message.error {
+                return Err(error);
+            }
+            let result = message.result.unwrap_or(Value::Null);
+            return serde_json::from_value(result).map_err(|e| RpcError::failed(format!("Unexpected reply to {}: {}", method, e)));
+        }
+    }
+
+    /// The next event, once subscribed.
+    pub async fn next_event(&mut self) -> Result<Event, RpcError> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            self.read().await?;
+        }
+    }
+
+    /// Reads one message, queueing it if it's an event.
+    async fn read(&mut self) -> Result<Message, RpcError> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await.map_err(lost)? == 0 {
+            return Err(RpcError::failed("The daemon closed the connection."));
+        }
+        let message: Message = serde_json::from_str(&line).map_err(|e| RpcError::failed(format!("Unreadable message from the daemon: {}", e)))?;
+        // Newer daemons may send events we don't know yet; those are skipped.
+        if message.method.as_deref() == Some(rpc::EVENT) {
+            if let Some(Ok(event)) = message.params.clone().map(serde_json::from_value::<Event>) {
+                self.events.push_back(event);
+            }
+        }
+        Ok(message)
+    }
+
+    pub async fn send(&mut self, file: &str, destination: &str, via: Option<&str>) -> Result<Transfer, RpcError> {
+        let params = SendParams {
+            file: file.to_string(),
+            destination: destination.to_string(),
+            via: via.map(String::from),
+        };
+        self.call("send", json!(params)).await
+    }
+
+    pub async fn transfers(&mut self) -> Result<Vec<Transfer>, RpcError> {
+        self.call("transfers", Value::Null).await
+    }
+
+    pub async fn accept(&mut self, id: &str) -> Result<Transfer, RpcError> {
+        self.call("accept", json!({ "id": id })).await
+    }
+
+    pub async fn reject(&mut self, id: &str) -> Result<Transfer, RpcError> {
+        self.call("reject", json!({ "id": id })).await
+    }
+
+    pub async fn cancel(&mut self, id: &str) -> Result<Transfer, RpcError> {
+        self.call("cancel", json!({ "id": id })).await
+    }
+
+    /// Answers a metered-connection confirmation.
+    pub async fn confirm(&mut self, id: &str, allow: bool) -> Result<(), RpcError> {
+        self.call("confirm", json!({ "id": id, "allow": allow })).await
+    }
+
+    pub async fn devices(&mut self) -> Result<Vec<KnownDevice>, RpcError> {
+        self.call("devices", Value::Null).await
+    }
+
+    /// Starts receiving events on this connection.
+    pub async fn subscribe(&mut self) -> Result<(), RpcError> {
+        self.call("subscribe", Value::Null).await
+    }
+
+    /// Accepts the next file the daemon is offered and waits for it to arrive.
+    pub async fn receive_next(&mut self) -> Result<Transfer, RpcError> {
+        self.subscribe().await?;
+        let offered = loop {
+            if let Event::Transfer { transfer } = self.next_event().await? {
+                if transfer.direction == Direction::Incoming && transfer.state == TransferState::Pending {
+                    break transfer;
+                }
+            }
+        };
+        self.accept(&offered.id).await?;
+        self.wait(&offered.id).await
+    }
+
+    /// Waits for transfer `id` to finish. Subscribe first.
+    pub async fn wait(&mut self, id: &str) -> Result<Transfer, RpcError> {
+        // It may have finished before we subscribed.
+        if let Some(transfer) = self.transfers().await?.into_iter().find(|t| t.id == id) {
+            if transfer.state.is_finished() {
+                return Ok(transfer);
+            }
+        }
+        loop {
+            if let Event::Transfer { transfer } = self.next_event().await? {
+                if transfer.id == id && transfer.state.is_finished() {
+                    return Ok(transfer);
+                }
+            }
+        }
+    }
+}
+
+fn lost(e: io::Error) -> RpcError {
+    RpcError::failed(format!("Lost the connection to the daemon: {}", e))
+}
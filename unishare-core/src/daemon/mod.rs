@@ -0,0 +1,686 @@
+//! Unishare as a background service: receivers and discovery keep running
+//! without a window open, and a JSON-RPC API on a Unix domain socket lets the
+//! apps and the CLI share them.
+//!
+//! - [`rpc`]: the methods and events on the socket.
+//! - [`Client`]: calling them from Rust.
+//! - [`Transfers`]: what the daemon is sending and receiving.
+//!
+//! Incoming files wait for a client to `accept` them unless
+//! `UNISHARE_AUTO_ACCEPT` is set.
+
+pub mod client;
+pub mod rpc;
+pub mod transfers;
+
+pub use client::Client;
+pub use transfers::{Direction, Transfer, TransferState, Transfers};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::AbortHandle;
+
+use crate::device_discovery::beacon::{self, BeaconConfig};
+use crate::device_discovery::{mdns, DeviceRegistry, DiscoveryEvent};
+use crate::protocols::approval::{self, ApprovalGate, Incoming};
+use crate::protocols::metered::{self, MeteredGuard};
+use crate::protocols::protocol_manager::{
+    resolve_route, send_file_to_device, send_file_via_best, send_via, start_receiver, stop_receivers,
+};
+use crate::protocols::capabilities;
+use crate::tools::internet::InternetConfig;
+use crate::tools::watcher::watcher;
+use rpc::{ConfirmParams, Event, IdParams, Message, Request, RpcError, SendParams};
+
+/// How long to wait before restarting receivers that stopped with an error.
+const RECEIVER_RETRY: Duration = Duration::from_secs(2);
+
+/// Transports `send` accepts in `via`.
+const TRANSPORTS: [&str; 3] = ["webrtc", "wifi-direct", "bluetooth"];
+
+#[derive(Clone, Debug)]
+pub struct DaemonConfig {
+    pub socket: PathBuf,
+    /// Accept incoming files without asking.
+    pub auto_accept: bool,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            socket: socket_path(),
+            auto_accept: false,
+        }
+    }
+}
+
+impl DaemonConfig {
+    /// Reads `UNISHARE_DAEMON_SOCKET` (see [`socket_path`]) and
+    /// `UNISHARE_AUTO_ACCEPT`.
+    pub fn from_env() -> Self {
+        let auto_accept = std::env::var("UNISHARE_AUTO_ACCEPT")
+            .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+        DaemonConfig {
+            auto_accept,
+            ..DaemonConfig::default()
+        }
+    }
+}
+
+/// Where the daemon listens: `UNISHARE_DAEMON_SOCKET`, else `unishare.sock`
+/// in `$XDG_RUNTIME_DIR`, else in a per-user directory in the temp directory
+/// that [`bind`] creates private to this user.
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("UNISHARE_DAEMON_SOCKET") {
+        return PathBuf::from(path);
+    }
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return PathBuf::from(dir).join("unishare.sock");
+    }
+    std::env::temp_dir()
+        .join(format!("unishare-{}", current_uid()))
+        .join("unishare.sock")
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: getuid has no preconditions and can't fail.
+    unsafe { libc::getuid() }
+}
+
+/// Fails unless `metadata` belongs to this user, or to root for shared
+/// directories such as `/tmp`.
+fn check_owner(path: &Path, metadata: &std::fs::Metadata, allow_root: bool) -> io::Result<()> {
+    let owner = metadata.uid();
+    if owner == current_uid() || (allow_root && owner == 0) {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("{} belongs to another user (uid {})", path.display(), owner),
+    ))
+}
+
+/// Binds the daemon's socket, replacing one left behind by a daemon that
+/// died. The socket is created private to this user, and a missing directory
+/// readable only by this user; one or a socket that another user already
+/// created there is refused.
+pub async fn bind(path: &Path) -> io::Result<UnixListener> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        if !parent.exists() {
+            std::fs::DirBuilder::new().recursive(true).mode(0o700).create(parent)?;
+        }
+        check_owner(parent, &std::fs::metadata(parent)?, true)?;
+    }
+    if let Ok(existing) = std::fs::symlink_metadata(path) {
+        check_owner(path, &existing, false)?;
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("A daemon is already running on {}", path.display()),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    // Created private rather than chmodded afterwards, so there's no moment
+    // in which another user could connect.
+    // SAFETY: umask has no preconditions; the previous mask is restored
+    // straight away.
+    let previous = unsafe { libc::umask(0o077) };
+    let listener = UnixListener::bind(path);
+    unsafe { libc::umask(previous) };
+    listener
+}
+
+/// Runs the daemon until SIGINT or SIGTERM: advertises this device, keeps
+/// discovery and receivers running, and serves [`rpc`] on `config.socket`.
+pub async fn run(config: DaemonConfig) -> Result<(), Box<dyn Error>> {
+    let listener = bind(&config.socket).await?;
+    println!("🛰️ Unishare daemon listening on {}", config.socket.display());
+
+    let registry = DeviceRegistry::new();
+    registry.spawn_expiry();
+    if let Err(e) = mdns::advertise() {
+        println!("mDNS advertising failed: {}", e);
+    }
+    if let Err(e) = mdns::browse(registry.clone()) {
+        println!("mDNS browsing failed: {}", e);
+    }
+    let beacon_registry = registry.clone();
+    tokio::spawn(async move {
+        if let Err(e) = beacon::start(BeaconConfig::from_env(), beacon_registry).await {
+            println!("Beacon discovery unavailable: {}", e);
+        }
+    });
+    tokio::spawn(async {
+        if let Err(e) = capabilities::serve_hello().await {
+            println!("Capability queries unavailable: {}", e);
+        }
+    });
+    watcher().start(InternetConfig::from_env());
+
+    approval::gate().set_required(true);
+    let daemon = Daemon::new(registry, approval::gate(), metered::guard(), config.auto_accept);
+    daemon.watch();
+    daemon.spawn_receivers();
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let served = tokio::select! {
+        served = daemon.serve(listener) => served.map_err(|e| e.to_string()),
+        _ = tokio::signal::ctrl_c() => Ok(()),
+        _ = terminate.recv() => Ok(()),
+    };
+    let _ = std::fs::remove_file(&config.socket);
+    println!("Unishare daemon stopped.");
+    Ok(served?)
+}
+
+/// The daemon's state, shared by every connection.
+pub struct Daemon {
+    transfers: Transfers,
+    registry: DeviceRegistry,
+    gate: &'static ApprovalGate,
+    guard: &'static MeteredGuard,
+    auto_accept: bool,
+    events: broadcast::Sender<Event>,
+    /// Running sends, by transfer ID.
+    sends: Mutex<HashMap<String, AbortHandle>>,
+    /// The receiver task; it's restarted whenever it ends.
+    receiver: Mutex<Option<AbortHandle>>,
+}
+
+impl Daemon {
+    pub fn new(
+        registry: DeviceRegistry,
+        gate: &'static ApprovalGate,
+        guard: &'static MeteredGuard,
+        auto_accept: bool,
+    ) -> Arc<Self> {
+        Arc::new(Daemon {
+            transfers: Transfers::new(),
+            registry,
+            gate,
+            guard,
+            auto_accept,
+            events: broadcast::channel(64).0,
+            sends: Mutex::new(HashMap::new()),
+            receiver: Mutex::new(None),
+        })
+    }
+
+    pub fn transfers(&self) -> &Transfers {
+        &self.transfers
+    }
+
+    /// Receives every [`Event`] from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Turns transfer changes, discovery, metered confirmations and incoming
+    /// files into events.
+    pub fn watch(self: &Arc<Self>) {
+        let events = self.events.clone();
+        let mut transfers = self.transfers.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match transfers.recv().await {
+                    Ok(transfer) => {
+                        let _ = events.send(Event::Transfer { transfer });
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+
+        let events = self.events.clone();
+        let mut discovery = self.registry.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let event = match discovery.recv().await {
+                    Ok(DiscoveryEvent::Discovered(device)) => Event::DeviceDiscovered { device },
+                    Ok(DiscoveryEvent::Lost { id }) => Event::DeviceLost { id },
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                };
+                let _ = events.send(event);
+            }
+        });
+
+        let events = self.events.clone();
+        let mut confirmations = self.guard.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match confirmations.recv().await {
+                    Ok(request) => {
+                        let _ = events.send(Event::MeteredConfirmation { request });
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+
+        let daemon = self.clone();
+        let mut incoming = self.gate.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match incoming.recv().await {
+                    Ok(event) => daemon.incoming(event),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Tracks an incoming file from offer to outcome.
+    fn incoming(self: &Arc<Self>, event: Incoming) {
+        match event {
+            Incoming::Offer(offer) => {
+                let id = offer.id.clone();
+                let mut transfer = Transfer::new(offer.id, Direction::Incoming, offer.name, offer.size, offer.from);
+                transfer.via = Some(offer.via);
+                transfer.state = TransferState::Pending;
+                self.transfers.insert(transfer);
+                if self.auto_accept {
+                    let _ = self.accept(&id);
+                    return;
+                }
+                // The gate rejects unanswered offers on its own.
+                let daemon = self.clone();
+                let timeout = self.gate.timeout();
+                tokio::spawn(async move {
+                    tokio::time::sleep(timeout).await;
+                    daemon.transfers.update(&id, |transfer| {
+                        let pending = transfer.state == TransferState::Pending;
+                        if pending {
+                            transfer.state = TransferState::Rejected;
+                            transfer.message = Some("Nobody accepted the file in time.".to_string());
+                        }
+                        pending
+                    });
+                });
+            }
+            Incoming::Done { id, file } => {
+                self.transfers.update(&id, |transfer| {
+                    transfer.state = TransferState::Done;
+                    transfer.message = Some(format!("Saved {}", file.path.display()));
+                    transfer.path = Some(file.path);
+                    true
+                });
+            }
+            Incoming::Failed { id, error } => {
+                self.transfers.finish(&id, TransferState::Failed, error);
+            }
+        }
+    }
+
+    /// Keeps the receivers running, restarting them after every file.
+    pub fn spawn_receivers(self: &Arc<Self>) {
+        let daemon = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let task = tokio::spawn(async { start_receiver().await.map_err(|e| e.to_string()) });
+                *daemon.receiver.lock().unwrap() = Some(task.abort_handle());
+                match task.await {
+                    Ok(Ok(received)) => println!("📥 {}: {}", received.message, received.file.path.display()),
+                    Ok(Err(e)) => {
+                        println!("Receiver stopped: {}", e);
+                        tokio::time::sleep(RECEIVER_RETRY).await;
+                    }
+                    // Cancelled; start over straight away.
+                    Err(_) => {}
+                }
+            }
+        });
+    }
+
+    pub fn accept(&self, id: &str) -> Result<Transfer, String> {
+        self.gate.answer(id, true)?;
+        self.transfers
+            .update(id, |transfer| {
+                transfer.state = TransferState::Running;
+                true
+            })
+            .ok_or_else(|| format!("No transfer {}", id))
+    }
+
+    pub fn reject(&self, id: &str) -> Result<Transfer, String> {
+        self.gate.answer(id, false)?;
+        self.transfers
+            .finish(id, TransferState::Rejected, "Rejected")
+            .ok_or_else(|| format!("No transfer {}", id))
+    }
+
+    /// Stops a transfer. A running incoming file is stopped by restarting
+    /// the receivers.
+    pub fn cancel(&self, id: &str) -> Result<Transfer, String> {
+        let transfer = self.transfers.get(id).ok_or_else(|| format!("No transfer {}", id))?;
+        if transfer.state.is_finished() {
+            return Err(format!("Transfer {} has already finished", id));
+        }
+        // Marked first, so the failure the abort causes doesn't overwrite it.
+        let cancelled = self
+            .transfers
+            .finish(id, TransferState::Cancelled, "Cancelled")
+            .ok_or_else(|| format!("No transfer {}", id))?;
+        match (transfer.direction, transfer.state) {
+            (Direction::Incoming, TransferState::Pending) => {
+                let _ = self.gate.answer(id, false);
+            }
+            (Direction::Incoming, _) => {
+                stop_receivers();
+                if let Some(receiver) = self.receiver.lock().unwrap().take() {
+                    receiver.abort();
+                }
+            }
+            (Direction::Outgoing, _) => {
+                if let Some(send) = self.sends.lock().unwrap().remove(id) {
+                    send.abort();
+                }
+            }
+        }
+        Ok(cancelled)
+    }
+
+    /// Starts sending a file and returns its transfer straight away.
+    pub fn send(self: &Arc<Self>, params: SendParams) -> Result<Transfer, String> {
+        let path = Path::new(&params.file);
+        let size = std::fs::metadata(path)
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .ok_or_else(|| format!("{} is not a file", params.file))?
+            .len();
+        if let Some(via) = params.via.as_deref().filter(|via| !TRANSPORTS.contains(via)) {
+            return Err(format!("Unknown transport '{}'; use one of {}", via, TRANSPORTS.join(", ")));
+        }
+
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        let id = format!("outgoing-{}", NEXT.fetch_add(1, Ordering::Relaxed));
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut transfer = Transfer::new(id.clone(), Direction::Outgoing, name, size, params.destination.clone());
+        transfer.via = params.via.clone();
+        self.transfers.insert(transfer.clone());
+
+        // Held while spawning so the task can't finish before it's recorded.
+        let mut sends = self.sends.lock().unwrap();
+        let daemon = self.clone();
+        let task = tokio::spawn(async move {
+            let result = daemon.deliver(&params).await;
+            daemon.sends.lock().unwrap().remove(&id);
+            match result {
+                Ok(message) => daemon.transfers.finish(&id, TransferState::Done, message),
+                Err(e) => daemon.transfers.finish(&id, TransferState::Failed, e),
+            };
+        });
+        sends.insert(transfer.id.clone(), task.abort_handle());
+        Ok(transfer)
+    }
+
+    /// Sends like the CLI does: discovered devices by ID or name, anything
+    /// else as an address or ticket.
+    async fn deliver(&self, params: &SendParams) -> Result<String, String> {
+        let SendParams { file, destination, via } = params;
+        match (self.registry.resolve(destination)?, via.as_deref()) {
+            (Some(known), Some(via)) => {
+                let route = resolve_route(&known.device).await?;
+                let via = send_via(via, file, &route.address).await.map_err(|e| e.to_string())?;
+                Ok(format!("File sent to {} via {}", known.device.name, via))
+            }
+            (Some(known), None) => send_file_to_device(file, &known.device).await.map_err(|e| e.to_string()),
+            (None, Some(via)) => {
+                let via = send_via(via, file, destination).await.map_err(|e| e.to_string())?;
+                Ok(format!("File sent via {}", via))
+            }
+            (None, None) => send_file_via_best(file, destination).await.map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Accepts clients until the listener fails. Connections from other
+    /// users are dropped.
+    pub async fn serve(self: &Arc<Self>, listener: UnixListener) -> io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            match stream.peer_cred().map(|cred| cred.uid()) {
+                Ok(uid) if uid == current_uid() => {}
+                Ok(uid) => {
+                    println!("Dropping a daemon connection from another user (uid {})", uid);
+                    continue;
+                }
+                Err(e) => {
+                    println!("Dropping a daemon connection: {}", e);
+                    continue;
+                }
+            }
+            let daemon = self.clone();
+            tokio::spawn(async move { daemon.connection(stream).await });
+        }
+    }
+
+    /// Answers one client's requests, one per line, and forwards events once
+    /// it subscribes.
+    async fn connection(self: Arc<Self>, stream: UnixStream) {
+        let (reader, mut writer) = stream.into_split();
+        let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+        let writing = tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                let Ok(mut line) = serde_json::to_string(&message) else {
+                    continue;
+                };
+                line.push('\n');
+                if writer.write_all(line.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut forwarding = None;
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let request = match serde_json::from_str::<Request>(&line) {
+                Ok(request) if request.jsonrpc == rpc::VERSION => request,
+                Ok(request) => {
+                    let error = RpcError::new(rpc::INVALID_REQUEST, "Only JSON-RPC 2.0 is supported");
+                    let _ = tx.send(Message::response(request.id.unwrap_or(Value::Null), Err(error)));
+                    continue;
+                }
+                Err(e) => {
+                    let error = RpcError::new(rpc::PARSE_ERROR, e.to_string());
+                    let _ = tx.send(Message::response(Value::Null, Err(error)));
+                    continue;
+                }
+            };
+
+            if request.method == "subscribe" && forwarding.is_none() {
+                let mut events = self.subscribe();
+                let tx = tx.clone();
+                forwarding = Some(tokio::spawn(async move {
+                    loop {
+                        match events.recv().await {
+                            Ok(event) => {
+                                if tx.send(Message::event(&event)).is_err() {
+                                    break;
+                                }
+                            }
+                            Err(RecvError::Lagged(_)) => continue,
+                            Err(RecvError::Closed) => break,
+                        }
+                    }
+                }));
+            }
+            let result = self.handle(&request.method, request.params);
+            if let Some(id) = request.id {
+                let _ = tx.send(Message::response(id, result));
+            }
+        }
+
+        if let Some(forwarding) = forwarding {
+            forwarding.abort();
+        }
+        drop(tx);
+        let _ = writing.await;
+    }
+
+    fn handle(self: &Arc<Self>, method: &str, params: Value) -> Result<Value, RpcError> {
+        let transfer = |result: Result<Transfer, String>| result.map(|transfer| json!(transfer)).map_err(RpcError::failed);
+        match method {
+            "send" => transfer(self.send(parse(params)?)),
+            "transfers" => Ok(json!(self.transfers.list())),
+            "accept" => transfer(self.accept(&parse::<IdParams>(params)?.id)),
+            "reject" => transfer(self.reject(&parse::<IdParams>(params)?.id)),
+            "cancel" => transfer(self.cancel(&parse::<IdParams>(params)?.id)),
+            "confirm" => {
+                let params: ConfirmParams = parse(params)?;
+                self.guard.answer(&params.id, params.allow).map_err(RpcError::failed)?;
+                Ok(Value::Null)
+            }
+            "devices" => Ok(json!(self.registry.devices())),
+            "subscribe" => Ok(Value::Null),
+            other => Err(RpcError::new(rpc::METHOD_NOT_FOUND, format!("Unknown method '{}'", other))),
+        }
+    }
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(rpc::INVALID_PARAMS, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use crate::protocols::filename::ReceivedFile;
+
+    /// A daemon without receivers or discovery, on a socket of its own.
+    async fn daemon(name: &str) -> (Arc<Daemon>, &'static ApprovalGate, PathBuf) {
+        let gate: &'static ApprovalGate = Box::leak(Box::new(ApprovalGate::new()));
+        gate.set_required(true);
+        let daemon = Daemon::new(DeviceRegistry::new(), gate, metered::guard(), false);
+        daemon.watch();
+        let path = std::env::temp_dir().join(format!("unishare-test-{}-{}.sock", name, std::process::id()));
+        let listener = bind(&path).await.unwrap();
+        let serving = daemon.clone();
+        tokio::spawn(async move { serving.serve(listener).await });
+        (daemon, gate, path)
+    }
+
+    /// Skips events until the next incoming file.
+    async fn next_offer(client: &mut Client) -> Transfer {
+        loop {
+            if let Event::Transfer { transfer } = client.next_event().await.unwrap() {
+                if transfer.state == TransferState::Pending {
+                    return transfer;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn binds_in_a_directory_only_this_user_can_read() {
+        let dir = std::env::temp_dir().join(format!("unishare-test-private-{}", std::process::id()));
+        let path = dir.join("unishare.sock");
+        let _listener = bind(&path).await.unwrap();
+        assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o077, 0);
+
+        let client = Client::connect(&path).await;
+        assert!(client.is_ok());
+        assert_eq!(bind(&path).await.unwrap_err().kind(), io::ErrorKind::AddrInUse);
+    }
+
+    #[tokio::test]
+    async fn incoming_files_wait_for_accept_or_reject() {
+        let (_daemon, gate, path) = daemon("incoming").await;
+        let mut client = Client::connect(&path).await.unwrap();
+        client.subscribe().await.unwrap();
+
+        let sender = tokio::spawn(async move {
+            let admission = gate.admit("photo.jpg", 5, "10.0.0.9", "webrtc").await?;
+            admission.done(&ReceivedFile {
+                original_name: "photo.jpg".to_string(),
+                path: PathBuf::from("/tmp/photo.jpg"),
+                renamed: false,
+            });
+            Ok::<(), String>(())
+        });
+        let offered = next_offer(&mut client).await;
+        assert_eq!(offered.direction, Direction::Incoming);
+        assert_eq!((offered.name.as_str(), offered.peer.as_str()), ("photo.jpg", "10.0.0.9"));
+        assert_eq!(client.accept(&offered.id).await.unwrap().state, TransferState::Running);
+        let done = client.wait(&offered.id).await.unwrap();
+        assert_eq!(done.state, TransferState::Done);
+        assert_eq!(done.path, Some(PathBuf::from("/tmp/photo.jpg")));
+        sender.await.unwrap().unwrap();
+
+        let sender = tokio::spawn(async move { gate.admit("virus.exe", 5, "10.0.0.9", "webrtc").await.map(drop) });
+        let offered = next_offer(&mut client).await;
+        assert_eq!(client.reject(&offered.id).await.unwrap().state, TransferState::Rejected);
+        assert!(sender.await.unwrap().unwrap_err().contains("Rejected"));
+        assert!(client.accept(&offered.id).await.is_err());
+
+        let listed = client.transfers().await.unwrap();
+        assert_eq!(listed.len(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn pending_files_can_be_cancelled() {
+        let (_daemon, gate, path) = daemon("cancel").await;
+        let mut client = Client::connect(&path).await.unwrap();
+        client.subscribe().await.unwrap();
+
+        let sender = tokio::spawn(async move { gate.admit("photo.jpg", 5, "10.0.0.9", "webrtc").await.map(drop) });
+        let offered = next_offer(&mut client).await;
+        assert_eq!(client.cancel(&offered.id).await.unwrap().state, TransferState::Cancelled);
+        assert!(sender.await.unwrap().is_err());
+        assert!(client.cancel(&offered.id).await.unwrap_err().message.contains("already finished"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn bad_requests_get_errors() {
+        let (_daemon, _gate, path) = daemon("errors").await;
+        let mut client = Client::connect(&path).await.unwrap();
+
+        let err = client.call::<Value>("frobnicate", Value::Null).await.unwrap_err();
+        assert_eq!(err.code, rpc::METHOD_NOT_FOUND);
+        let err = client.call::<Value>("accept", json!({})).await.unwrap_err();
+        assert_eq!(err.code, rpc::INVALID_PARAMS);
+        let err = client.send("/nonexistent/file", "10.0.0.9", None).await.unwrap_err();
+        assert!(err.message.contains("not a file"), "{}", err);
+        let err = client.cancel("outgoing-999").await.unwrap_err();
+        assert_eq!(err.code, rpc::FAILED);
+
+        // A second daemon can't take over the socket.
+        assert_eq!(bind(&path).await.unwrap_err().kind(), io::ErrorKind::AddrInUse);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn stale_sockets_are_replaced() {
+        let path = std::env::temp_dir().join(format!("unishare-test-stale-{}.sock", std::process::id()));
+        drop(bind(&path).await.unwrap());
+        assert!(path.exists());
+        drop(bind(&path).await.unwrap());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,176 @@
+//! The daemon's wire format: JSON-RPC 2.0, one message per line.
+//!
+//! | Method       | Params                             | Result            |
+//! |--------------|------------------------------------|-------------------|
+//! | `send`       | [`SendParams`]                     | [`Transfer`]      |
+//! | `transfers`  | none                               | `[Transfer]`      |
+//! | `accept`     | [`IdParams`]                       | [`Transfer`]      |
+//! | `reject`     | [`IdParams`]                       | [`Transfer`]      |
+//! | `cancel`     | [`IdParams`]                       | [`Transfer`]      |
+//! | `confirm`    | [`ConfirmParams`]                  | `null`            |
+//! | `devices`    | none                               | `[KnownDevice]`   |
+//! | `subscribe`  | none                               | `null`            |
+//!
+//! After `subscribe`, the connection also receives an `event` notification
+//! for every [`Event`].
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::transfers::Transfer;
+use crate::device_discovery::DiscoveredDevice;
+use crate::protocols::metered::ConfirmationRequest;
+
+pub const VERSION: &str = "2.0";
+
+/// Method name of event notifications.
+pub const EVENT: &str = "event";
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The method ran but couldn't do what was asked.
+pub const FAILED: i64 = -32000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Request {
+    pub jsonrpc: String,
+    /// Absent for notifications, which get no response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub params: Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn failed(message: impl Into<String>) -> Self {
+        RpcError::new(FAILED, message)
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// Everything the daemon writes: responses and event notifications.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Message {
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl Message {
+    pub fn response(id: Value, result: Result<Value, RpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Message {
+            jsonrpc: VERSION.to_string(),
+            id: Some(id),
+            method: None,
+            params: None,
+            result,
+            error,
+        }
+    }
+
+    pub fn event(event: &Event) -> Self {
+        Message {
+            jsonrpc: VERSION.to_string(),
+            id: None,
+            method: Some(EVENT.to_string()),
+            params: serde_json::to_value(event).ok(),
+            result: None,
+            error: None,
+        }
+    }
+}
+
+/// What subscribers hear about.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Event {
+    /// A transfer started or changed state; incoming files arrive `pending`
+    /// and wait for `accept` or `reject`.
+    Transfer { transfer: Transfer },
+    DeviceDiscovered { device: DiscoveredDevice },
+    DeviceLost { id: String },
+    /// A send over a metered connection needs a `confirm`.
+    MeteredConfirmation { request: ConfirmationRequest },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SendParams {
+    pub file: String,
+    /// A device ID or name, an address, or a relay ticket.
+    pub destination: String,
+    /// `webrtc`, `wifi-direct` or `bluetooth`; picked automatically if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IdParams {
+    pub id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ConfirmParams {
+    pub id: String,
+    pub allow: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn messages_match_json_rpc() {
+        let response = Message::response(json!(7), Err(RpcError::new(METHOD_NOT_FOUND, "Unknown method")));
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({ "jsonrpc": "2.0", "id": 7, "error": { "code": -32601, "message": "Unknown method" } })
+        );
+
+        let event = Message::event(&Event::DeviceLost { id: "abc".to_string() });
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({ "jsonrpc": "2.0", "method": "event", "params": { "type": "deviceLost", "id": "abc" } })
+        );
+
+        let request: Request = serde_json::from_str(r#"{"jsonrpc":"2.0","id":"a","method":"transfers"}"#).unwrap();
+        assert_eq!(request.id, Some(json!("a")));
+        assert_eq!(request.params, Value::Null);
+    }
+}
@@ -0,0 +1,197 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// How many finished transfers are kept for `transfers` to list.
+const MAX_FINISHED: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum TransferState {
+    /// An incoming file waiting to be accepted or rejected.
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Rejected,
+    Cancelled,
+}
+
+impl TransferState {
+    /// Finished transfers don't change any more.
+    pub fn is_finished(self) -> bool {
+        !matches!(self, TransferState::Pending | TransferState::Running)
+    }
+}
+
+/// One file going to or coming from another device.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Transfer {
+    pub id: String,
+    pub direction: Direction,
+    /// The file name, as the sender has it.
+    pub name: String,
+    pub size: u64,
+    /// The destination of a send, or the sender's address.
+    pub peer: String,
+    /// The transport, once one has been picked.
+    pub via: Option<String>,
+    pub state: TransferState,
+    /// How the transfer went, or why it failed.
+    pub message: Option<String>,
+    /// Where an incoming file was saved.
+    pub path: Option<PathBuf>,
+    /// Milliseconds since the epoch when the transfer was created.
+    pub started: u64,
+}
+
+impl Transfer {
+    pub fn new(id: String, direction: Direction, name: String, size: u64, peer: String) -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Transfer {
+            id,
+            direction,
+            name,
+            size,
+            peer,
+            via: None,
+            state: TransferState::Running,
+            message: None,
+            path: None,
+            started,
+        }
+    }
+}
+
+/// Every transfer the daemon knows about, oldest first. Each change is
+/// announced to subscribers. Cloning gives another handle to the same table.
+#[derive(Clone)]
+pub struct Transfers {
+    transfers: Arc<Mutex<Vec<Transfer>>>,
+    updates: broadcast::Sender<Transfer>,
+}
+
+impl Transfers {
+    pub fn new() -> Self {
+        Transfers {
+            transfers: Arc::new(Mutex::new(Vec::new())),
+            updates: broadcast::channel(64).0,
+        }
+    }
+
+    /// Receives every new or changed transfer from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Transfer> {
+        self.updates.subscribe()
+    }
+
+    pub fn list(&self) -> Vec<Transfer> {
+        self.transfers.lock().unwrap().clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Transfer> {
+        self.transfers.lock().unwrap().iter().find(|t| t.id == id).cloned()
+    }
+
+    /// Adds a transfer, dropping the oldest finished ones past [`MAX_FINISHED`].
+    pub fn insert(&self, transfer: Transfer) {
+        {
+            let mut transfers = self.transfers.lock().unwrap();
+            let finished = transfers.iter().filter(|t| t.state.is_finished()).count();
+            let mut excess = finished.saturating_sub(MAX_FINISHED - 1);
+            transfers.retain(|t| {
+                let drop = excess > 0 && t.state.is_finished();
+                if drop {
+                    excess -= 1;
+                }
+                !drop
+            });
+            transfers.push(transfer.clone());
+        }
+        let _ = self.updates.send(transfer);
+    }
+
+    /// Changes an unfinished transfer. `change` returns whether it changed
+    /// anything; finished transfers are left alone. Returns the transfer as
+    /// it is afterwards, or `None` if there's no such transfer.
+    pub fn update(&self, id: &str, change: impl FnOnce(&mut Transfer) -> bool) -> Option<Transfer> {
+        let (transfer, changed) = {
+            let mut transfers = self.transfers.lock().unwrap();
+            let transfer = transfers.iter_mut().find(|t| t.id == id)?;
+            let changed = !transfer.state.is_finished() && change(transfer);
+            (transfer.clone(), changed)
+        };
+        if changed {
+            let _ = self.updates.send(transfer.clone());
+        }
+        Some(transfer)
+    }
+
+    /// Moves an unfinished transfer to `state`.
+    pub fn finish(&self, id: &str, state: TransferState, message: impl Into<String>) -> Option<Transfer> {
+        let message = message.into();
+        self.update(id, |transfer| {
+            transfer.state = state;
+            transfer.message = Some(message);
+            true
+        })
+    }
+}
+
+impl Default for Transfers {
+    fn default() -> Self {
+        Transfers::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(id: &str) -> Transfer {
+        Transfer::new(id.to_string(), Direction::Outgoing, "a.txt".to_string(), 3, "laptop".to_string())
+    }
+
+    #[test]
+    fn finished_transfers_stay_finished() {
+        let transfers = Transfers::new();
+        let mut updates = transfers.subscribe();
+        transfers.insert(transfer("outgoing-1"));
+        assert_eq!(updates.try_recv().unwrap().state, TransferState::Running);
+
+        transfers.finish("outgoing-1", TransferState::Cancelled, "Cancelled");
+        assert_eq!(updates.try_recv().unwrap().state, TransferState::Cancelled);
+        let after = transfers.finish("outgoing-1", TransferState::Done, "Sent").unwrap();
+        assert_eq!(after.state, TransferState::Cancelled);
+        assert!(updates.try_recv().is_err());
+        assert!(transfers.finish("outgoing-2", TransferState::Done, "Sent").is_none());
+    }
+
+    #[test]
+    fn old_finished_transfers_are_dropped() {
+        let transfers = Transfers::new();
+        transfers.insert(transfer("running"));
+        for n in 0..MAX_FINISHED + 5 {
+            let id = format!("outgoing-{}", n);
+            transfers.insert(transfer(&id));
+            transfers.finish(&id, TransferState::Done, "Sent");
+        }
+        let list = transfers.list();
+        assert_eq!(list.len(), MAX_FINISHED + 1);
+        assert_eq!(list[0].id, "running");
+        assert_eq!(list[1].id, "outgoing-5");
+    }
+}
@@ -0,0 +1,415 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::converter::{detect_bom, Encoding};
+
+/// How much of a file detection looks at.
+pub const SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Legacy encodings scored when the sample is neither ASCII nor UTF-8.
+const LEGACY: [Encoding; 4] = [
+    Encoding::ShiftJis,
+    Encoding::Gbk,
+    Encoding::Windows1251,
+    Encoding::Windows1252,
+];
+
+/// One encoding the sample could be in.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate {
+    pub encoding: Encoding,
+    /// From 0.0 to 1.0.
+    pub confidence: f32,
+}
+
+/// What [`detect`] concluded about a sample.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Detection {
+    /// The best guess; `None` for binary data.
+    pub encoding: Option<Encoding>,
+    /// From 0.0 to 1.0; a BOM or plain ASCII gives 1.0.
+    pub confidence: f32,
+    pub bom: bool,
+    pub binary: bool,
+    /// Every plausible encoding, best first.
+    pub candidates: Vec<Candidate>,
+}
+
+impl Detection {
+    fn certain(encoding: Encoding, confidence: f32, bom: bool) -> Self {
+        Detection {
+            encoding: Some(encoding),
+            confidence,
+            bom,
+            binary: false,
+            candidates: vec![Candidate { encoding, confidence }],
+        }
+    }
+
+    fn binary() -> Self {
+        Detection {
+            encoding: None,
+            confidence: 1.0,
+            bom: false,
+            binary: true,
+            candidates: Vec::new(),
+        }
+    }
+}
+
+/// Guesses the encoding of `sample`, usually the start of a file.
+///
+/// - A BOM decides outright.
+/// - Alternating zero bytes mean BOM-less UTF-16, as some Windows tools write.
+/// - Other NUL bytes, or control bytes text doesn't use, mean binary.
+/// - Plain ASCII and valid UTF-8 are UTF-8.
+/// - A sample of [`SAMPLE_SIZE`] bytes or more is taken to be the start of
+///   something longer, so a character cut off at its end isn't an error.
+/// - Anything else is scored as Shift_JIS, GBK, Windows-1251 and
+///   Windows-1252 by how typical its non-ASCII characters are of each.
+pub fn detect(sample: &[u8]) -> Detection {
+    if let Some((encoding, _)) = detect_bom(sample) {
+        return Detection::certain(encoding, 1.0, true);
+    }
+    if let Some(encoding) = sniff_utf16(sample) {
+        return Detection::certain(encoding, 0.9, false);
+    }
+    let control = |b: &u8| *b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1a | 0x1b);
+    if sample.iter().any(control) {
+        return Detection::binary();
+    }
+
+    let truncated = sample.len() >= SAMPLE_SIZE;
+    let multibyte = sample.iter().filter(|&&b| b >= 0xC0).count();
+    let utf8 = match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(e) => truncated && e.error_len().is_none(),
+    };
+    if utf8 {
+        // Legacy text rarely happens to be valid UTF-8, less so the more
+        // multi-byte sequences it has.
+        let confidence = if multibyte == 0 {
+            1.0
+        } else {
+            (1.0 - 0.25f32.powi(multibyte.min(16) as i32)).min(0.99)
+        };
+        return Detection::certain(Encoding::Utf8, confidence, false);
+    }
+
+    let scores: Vec<(Encoding, Score)> = LEGACY
+        .iter()
+        .map(|&encoding| (encoding, score(encoding, sample, truncated)))
+        .collect();
+    let total: f32 = scores.iter().map(|(_, score)| score.value()).sum();
+    let mut candidates: Vec<Candidate> = scores
+        .iter()
+        .filter(|(_, score)| score.value() > 0.0)
+        .map(|(encoding, score)| {
+            // Weigh how typical the text is for this encoding by how clearly
+            // it beats the others and by how much evidence there is.
+            let value = score.value();
+            let evidence = 1.0 - 0.5f32.powi(score.units.min(16) as i32);
+            Candidate {
+                encoding: *encoding,
+                confidence: value * value / total * evidence,
+            }
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    let (encoding, confidence) = candidates
+        .first()
+        .map(|best| (best.encoding, best.confidence))
+        // Windows-1252 decodes nearly anything, so it's the last resort.
+        .unwrap_or((Encoding::Windows1252, 0.0));
+    Detection {
+        encoding: Some(encoding),
+        confidence,
+        bom: false,
+        binary: false,
+        candidates,
+    }
+}
+
+/// Detects the encoding of the file at `path` from its first [`SAMPLE_SIZE`] bytes.
+pub fn detect_file(path: &Path) -> io::Result<Detection> {
+    let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+    File::open(path)?.take(SAMPLE_SIZE as u64).read_to_end(&mut sample)?;
+    Ok(detect(&sample))
+}
+
+/// Mostly-ASCII UTF-16 has a zero in every other byte.
+fn sniff_utf16(sample: &[u8]) -> Option<Encoding> {
+    let units = sample.len() / 2;
+    if units < 2 {
+        return None;
+    }
+    let zeros_at = |offset: usize| sample.chunks_exact(2).filter(|unit| unit[offset] == 0).count();
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+    // Most units must be ASCII, and the other half of each must never be zero.
+    if odd * 10 >= units * 9 && even == 0 {
+        Some(Encoding::Utf16Le)
+    } else if even * 10 >= units * 9 && odd == 0 {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// How well a sample fits one encoding.
+#[derive(Default)]
+struct Score {
+    /// Non-ASCII characters.
+    units: usize,
+    /// Weighted count of those that are typical of the encoding's language.
+    typical: f32,
+    /// Byte sequences the encoding doesn't define.
+    invalid: usize,
+}
+
+impl Score {
+    /// The share of typical characters, with each invalid sequence
+    /// cancelling out several good ones.
+    fn value(&self) -> f32 {
+        if self.units == 0 {
+            return 0.0;
+        }
+        ((self.typical - 4.0 * self.invalid as f32) / self.units as f32).max(0.0)
+    }
+}
+
+fn score(encoding: Encoding, sample: &[u8], truncated: bool) -> Score {
+    match encoding {
+        Encoding::ShiftJis => score_shift_jis(sample, truncated),
+        Encoding::Gbk => score_gbk(sample, truncated),
+        Encoding::Windows1251 => score_single_byte(sample, cyrillic_weight),
+        Encoding::Windows1252 => score_single_byte(sample, western_weight),
+        _ => Score::default(),
+    }
+}
+
+/// Japanese text leans on kana, punctuation and the common (level 1) kanji.
+fn score_shift_jis(sample: &[u8], truncated: bool) -> Score {
+    let mut score = Score::default();
+    let mut i = 0;
+    while i < sample.len() {
+        let lead = sample[i];
+        match lead {
+            0x00..=0x7F => {
+                i += 1;
+                continue;
+            }
+            // Half-width katakana: valid, but rare in modern text.
+            0xA1..=0xDF => {}
+            0x81..=0x9F | 0xE0..=0xFC => {
+                let Some(&trail) = sample.get(i + 1) else {
+                    // Cut off at the end of the sample.
+                    score.units += 1;
+                    score.invalid += usize::from(!truncated);
+                    break;
+                };
+                if matches!(trail, 0x40..=0x7E | 0x80..=0xFC) {
+                    score.units += 1;
+                    let typical = match lead {
+                        0x81 => true,
+                        0x82 => trail >= 0x4F,
+                        0x83 => trail <= 0x96,
+                        0x88..=0x98 => true,
+                        _ => false,
+                    };
+                    if typical {
+                        score.typical += 1.0;
+                    }
+                    i += 2;
+                    continue;
+                }
+                score.invalid += 1;
+            }
+            _ => score.invalid += 1,
+        }
+        score.units += 1;
+        i += 1;
+    }
+    score
+}
+
+/// Simplified Chinese text is mostly GB2312 hanzi and punctuation; the
+/// GBK extensions hold rarer characters.
+fn score_gbk(sample: &[u8], truncated: bool) -> Score {
+    let mut score = Score::default();
+    let mut i = 0;
+    while i < sample.len() {
+        let lead = sample[i];
+        match lead {
+            0x00..=0x7F => {
+                i += 1;
+                continue;
+            }
+            0x81..=0xFE => {
+                let Some(&trail) = sample.get(i + 1) else {
+                    score.units += 1;
+                    score.invalid += usize::from(!truncated);
+                    break;
+                };
+                if matches!(trail, 0x40..=0x7E | 0x80..=0xFE) {
+                    score.units += 1;
+                    score.typical += match (lead, trail) {
+                        // Punctuation, full-width forms and level 1 hanzi.
+                        (0xA1..=0xA3 | 0xB0..=0xD7, 0xA1..) => 1.0,
+                        // Level 2 hanzi.
+                        (0xD8..=0xF7, 0xA1..) => 0.5,
+                        _ => 0.0,
+                    };
+                    i += 2;
+                    continue;
+                }
+                score.invalid += 1;
+            }
+            _ => score.invalid += 1,
+        }
+        score.units += 1;
+        i += 1;
+    }
+    score
+}
+
+/// Scores each non-ASCII byte with `weight`, which sees the byte and
+/// whether its neighbours are ASCII letters or other non-ASCII bytes.
+fn score_single_byte(sample: &[u8], weight: fn(u8, Neighbours) -> Option<f32>) -> Score {
+    let mut score = Score::default();
+    for (i, &byte) in sample.iter().enumerate() {
+        if byte < 0x80 {
+            continue;
+        }
+        let neighbours = Neighbours {
+            ascii_letter: [i.checked_sub(1), Some(i + 1)]
+                .into_iter()
+                .flatten()
+                .any(|j| sample.get(j).is_some_and(u8::is_ascii_alphabetic)),
+            high: [i.checked_sub(1), Some(i + 1)]
+                .into_iter()
+                .flatten()
+                .any(|j| sample.get(j).is_some_and(|&b| b >= 0xC0)),
+        };
+        score.units += 1;
+        match weight(byte, neighbours) {
+            Some(weight) => score.typical += weight,
+            None => score.invalid += 1,
+        }
+    }
+    score
+}
+
+#[derive(Clone, Copy)]
+struct Neighbours {
+    ascii_letter: bool,
+    high: bool,
+}
+
+/// Cyrillic words are runs of letters from 0xC0 up, mostly lower case,
+/// with no ASCII letters mixed in.
+fn cyrillic_weight(byte: u8, neighbours: Neighbours) -> Option<f32> {
+    let in_word = neighbours.high && !neighbours.ascii_letter;
+    match byte {
+        0x98 => None,
+        0xE0..=0xFF | 0xB8 if in_word => Some(1.0),
+        0xC0..=0xDF | 0xA8 if in_word => Some(0.5),
+        // Typographic quotes, dashes and the like.
+        0x84 | 0x85 | 0x8B | 0x91..=0x97 | 0x9B | 0xAB | 0xB9 | 0xBB => Some(0.5),
+        _ => Some(0.0),
+    }
+}
+
+/// Western European accented letters sit inside otherwise ASCII words.
+fn western_weight(byte: u8, neighbours: Neighbours) -> Option<f32> {
+    match byte {
+        0x81 | 0x8D | 0x8F | 0x90 | 0x9D => None,
+        0xC0..=0xFF if byte != 0xD7 && byte != 0xF7 && neighbours.ascii_letter => Some(1.0),
+        0x8A | 0x8C | 0x8E | 0x9A | 0x9C | 0x9E | 0x9F if neighbours.ascii_letter => Some(1.0),
+        // The euro sign, typographic quotes, dashes and common symbols.
+        0x80 | 0x85 | 0x91..=0x97 | 0xA0..=0xBF => Some(0.5),
+        _ => Some(0.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(text: &str, encoding: &'static encoding_rs::Encoding) -> Vec<u8> {
+        let (bytes, _, unmappable) = encoding.encode(text);
+        assert!(!unmappable, "{} can't encode the sample", encoding.name());
+        bytes.into_owned()
+    }
+
+    fn detected(sample: &[u8]) -> (Option<Encoding>, f32) {
+        let detection = detect(sample);
+        (detection.encoding, detection.confidence)
+    }
+
+    #[test]
+    fn detects_legacy_encodings() {
+        let japanese = encode("今日は良い天気ですね。明日も晴れるでしょう。ファイルを送ります。", encoding_rs::SHIFT_JIS);
+        let chinese = encode("今天天气很好。我们明天去公园散步，然后一起吃饭。文件已经发送。", encoding_rs::GBK);
+        let russian = encode("Привет, как дела? Сегодня хорошая погода, и мы пойдём гулять.", encoding_rs::WINDOWS_1251);
+        let french = encode("Le café était déjà fermé, alors nous sommes allés à la crêperie.", encoding_rs::WINDOWS_1252);
+
+        for (sample, expected) in [
+            (japanese, Encoding::ShiftJis),
+            (chinese, Encoding::Gbk),
+            (russian, Encoding::Windows1251),
+            (french, Encoding::Windows1252),
+        ] {
+            let (encoding, confidence) = detected(&sample);
+            assert_eq!(encoding, Some(expected));
+            assert!(confidence >= 0.5, "{} at {}", expected, confidence);
+        }
+    }
+
+    #[test]
+    fn boms_decide_outright() {
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend("héllo".encode_utf16().flat_map(u16::to_le_bytes));
+        let detection = detect(&utf16);
+        assert_eq!(detection.encoding, Some(Encoding::Utf16Le));
+        assert!(detection.bom);
+        assert_eq!(detection.confidence, 1.0);
+
+        let bomless: Vec<u8> = "plain text".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let detection = detect(&bomless);
+        assert_eq!(detection.encoding, Some(Encoding::Utf16Be));
+        assert!(!detection.bom);
+    }
+
+    #[test]
+    fn recognizes_binary_and_utf8() {
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00\x00\x01";
+        let detection = detect(png);
+        assert!(detection.binary);
+        assert_eq!(detection.encoding, None);
+
+        assert_eq!(detected(b"just ascii"), (Some(Encoding::Utf8), 1.0));
+        let (encoding, confidence) = detected("naïve café façade".as_bytes());
+        assert_eq!(encoding, Some(Encoding::Utf8));
+        assert!(confidence > 0.9);
+    }
+
+    #[test]
+    fn a_character_cut_off_by_the_sample_is_not_an_error() {
+        let mut sample = "é".repeat(SAMPLE_SIZE / 2).into_bytes();
+        sample.push(0xC3);
+        assert_eq!(detected(&sample).0, Some(Encoding::Utf8));
+        // A short file ending the same way is just broken.
+        assert_ne!(detected(b"caf\xC3").0, Some(Encoding::Utf8));
+    }
+
+    #[test]
+    fn little_evidence_means_low_confidence() {
+        let (_, confidence) = detected(b"price: 10\xA4");
+        assert!(confidence < 0.5, "{}", confidence);
+    }
+}
@@ -0,0 +1,455 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::io::{self, Write};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+
+use super::{config_dir, local_device, DeviceRegistry, DiscoveredDevice};
+
+/// UDP port beacons are broadcast to when `UNISHARE_BEACON_PORT` isn't set.
+pub const DEFAULT_BEACON_PORT: u16 = 9003;
+const MAX_DATAGRAM: usize = 2048;
+/// Most devices whose keys and sequence numbers are remembered at once; the
+/// longest silent one is forgotten to make room for a new one.
+const MAX_PEERS: usize = 1024;
+/// Devices silent for this long are forgotten, key pin included.
+const PEER_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Settings for the UDP beacon fallback.
+#[derive(Clone, Debug)]
+pub struct BeaconConfig {
+    /// Port announcements are sent to and received on.
+    pub port: u16,
+    /// How often this instance announces itself.
+    pub interval: Duration,
+    /// How long a device stays listed after its last announcement.
+    pub ttl: Duration,
+}
+
+impl Default for BeaconConfig {
+    fn default() -> Self {
+        BeaconConfig {
+            port: DEFAULT_BEACON_PORT,
+            interval: Duration::from_secs(5),
+            ttl: Duration::from_secs(20),
+        }
+    }
+}
+
+impl BeaconConfig {
+    /// Reads `UNISHARE_BEACON_PORT` and `UNISHARE_BEACON_INTERVAL_SECS`. The TTL
+    /// covers several missed announcements, so a dropped datagram or two
+    /// doesn't make a device flicker.
+    pub fn from_env() -> Self {
+        let mut config = BeaconConfig::default();
+        if let Some(port) = std::env::var("UNISHARE_BEACON_PORT").ok().and_then(|p| p.parse().ok()) {
+            config.port = port;
+        }
+        if let Some(secs) = std::env::var("UNISHARE_BEACON_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+        {
+            config.interval = Duration::from_secs(secs);
+            config.ttl = Duration::from_secs(secs * 4);
+        }
+        config
+    }
+}
+
+/// What a beacon says about the device sending it. Addresses aren't included:
+/// the receiver takes them from the datagram's source, which is the address
+/// that actually reached it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Announcement {
+    pub id: String,
+    pub name: String,
+    pub ports: BTreeMap<String, u16>,
+    pub transports: Vec<String>,
+    pub ttl_secs: u64,
+    /// Milliseconds since the epoch at sending time; older announcements from
+    /// the same device are ignored, which stops replays.
+    pub seq: u64,
+}
+
+/// The datagram on the wire: the announcement's JSON text plus an Ed25519
+/// signature over exactly those bytes.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    payload: String,
+    public_key: String,
+    signature: String,
+}
+
+/// The device key signing our beacons, generated and saved on first use.
+fn signing_key() -> SigningKey {
+    let _ = std::fs::create_dir_all(config_dir());
+    load_or_create_key(&config_dir().join("beacon-key"))
+}
+
+/// Reads the key saved at `path`, or generates one and saves it readable by
+/// this user only. A saved key others could read gets its permissions
+/// repaired; one that can't be repaired, or isn't a key, is replaced.
+fn load_or_create_key(path: &Path) -> SigningKey {
+    match read_key(path) {
+        Ok(Some(key)) => return key,
+        Ok(None) => {}
+        Err(e) => {
+            println!("Replacing beacon key {}: {}", path.display(), e);
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    let key = SigningKey::generate(&mut rand::rngs::OsRng);
+    match save_key(path, &key) {
+        Ok(()) => key,
+        // Another instance saved one first; sign with the same key.
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => read_key(path).ok().flatten().unwrap_or(key),
+        Err(e) => {
+            println!("Could not save beacon key to {}: {}", path.display(), e);
+            key
+        }
+    }
+}
+
+/// Returns the key at `path`, or `None` if there's no file yet.
+fn read_key(path: &Path) -> Result<Option<SigningKey>, Box<dyn Error>> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    keep_private(path)?;
+    let secret = <[u8; 32]>::try_from(bytes.as_slice()).map_err(|_| "not a 32-byte key")?;
+    Ok(Some(SigningKey::from_bytes(&secret)))
+}
+
+/// Restricts the key at `path` to this user if group or others can read it.
+#[cfg(unix)]
+fn keep_private(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(path)?.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        println!("Beacon key {} had mode {:o}; restricting it to this user.", path.display(), mode);
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+/// Windows keeps files in the profile private to their user already.
+#[cfg(not(unix))]
+fn keep_private(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Writes `key` to a new file at `path` that only this user can read.
+fn save_key(path: &Path, key: &SigningKey) -> io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(&key.to_bytes())
+}
+
+pub fn encode(announcement: &Announcement, key: &SigningKey) -> Vec<u8> {
+    let payload = serde_json::to_string(announcement).expect("announcement serializes");
+    let envelope = Envelope {
+        signature: BASE64.encode(key.sign(payload.as_bytes()).to_bytes()),
+        public_key: BASE64.encode(key.verifying_key().to_bytes()),
+        payload,
+    };
+    serde_json::to_vec(&envelope).expect("envelope serializes")
+}
+
+/// Parses a datagram and checks its signature, returning the announcement and
+/// the key that signed it.
+pub fn decode(datagram: &[u8]) -> Result<(Announcement, VerifyingKey), Box<dyn Error>> {
+    let envelope: Envelope = serde_json::from_slice(datagram)?;
+    let public_key: [u8; 32] = BASE64
+        .decode(&envelope.public_key)?
+        .try_into()
+        .map_err(|_| "public key must be 32 bytes")?;
+    let public_key = VerifyingKey::from_bytes(&public_key)?;
+    let signature = Signature::from_slice(&BASE64.decode(&envelope.signature)?)?;
+    public_key.verify(envelope.payload.as_bytes(), &signature)?;
+
+    Ok((serde_json::from_str(&envelope.payload)?, public_key))
+}
+
+/// What the listener remembers per device ID to reject forgeries and replays.
+struct Peer {
+    key: VerifyingKey,
+    seq: u64,
+    last_seen: Instant,
+}
+
+/// Every device heard from recently, bounded by [`MAX_PEERS`] and [`PEER_TTL`]
+/// so a flood of made-up IDs can't grow it without limit.
+#[derive(Default)]
+struct Peers {
+    peers: HashMap<String, Peer>,
+}
+
+impl Peers {
+    /// Pins the device's key on first sight and insists on increasing `seq`.
+    fn accept(&mut self, announcement: &Announcement, key: VerifyingKey, now: Instant) -> bool {
+        self.peers.retain(|_, peer| now.duration_since(peer.last_seen) < PEER_TTL);
+        match self.peers.get_mut(&announcement.id) {
+            Some(peer) if peer.key != key || announcement.seq <= peer.seq => false,
+            Some(peer) => {
+                peer.seq = announcement.seq;
+                peer.last_seen = now;
+                true
+            }
+            None => {
+                if self.peers.len() >= MAX_PEERS {
+                    let oldest = self
+                        .peers
+                        .iter()
+                        .min_by_key(|(_, peer)| peer.last_seen)
+                        .map(|(id, _)| id.clone());
+                    if let Some(oldest) = oldest {
+                        self.peers.remove(&oldest);
+                    }
+                }
+                self.peers.insert(
+                    announcement.id.clone(),
+                    Peer {
+                        key,
+                        seq: announcement.seq,
+                        last_seen: now,
+                    },
+                );
+                true
+            }
+        }
+    }
+}
+
+/// Starts broadcasting our beacon and listening for others', feeding `registry`.
+///
+/// - Announcements go to the IPv4 broadcast address every `interval`.
+/// - A device ID is pinned to the first key seen signing for it; announcements
+///   for that ID under another key are dropped.
+/// - Devices that stop announcing expire after their advertised TTL.
+pub async fn start(config: BeaconConfig, registry: DeviceRegistry) -> Result<(), Box<dyn Error>> {
+    let socket = Arc::new(bind(config.port)?);
+    println!("📢 Beacon listening on UDP port {}", config.port);
+
+    let key = signing_key();
+    let sender = socket.clone();
+    let interval = config.interval;
+    let ttl_secs = config.ttl.as_secs();
+    let target = SocketAddr::from((Ipv4Addr::BROADCAST, config.port));
+    tokio::spawn(async move {
+        let local = local_device();
+        let mut tick = tokio::time::interval(interval);
+        loop {
+            tick.tick().await;
+            let announcement = Announcement {
+                id: local.id.clone(),
+                name: local.name.clone(),
+                ports: local.ports.clone(),
+                transports: local.transports.clone(),
+                ttl_secs,
+                seq: now_millis(),
+            };
+            if let Err(e) = sender.send_to(&encode(&announcement, &key), target).await {
+                println!("Beacon broadcast failed: {}", e);
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut peers = Peers::default();
+        let mut buffer = [0u8; MAX_DATAGRAM];
+        loop {
+            let (len, from) = match socket.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(e) => {
+                    println!("Beacon receive failed: {}", e);
+                    continue;
+                }
+            };
+            let (announcement, key) = match decode(&buffer[..len]) {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    println!("Ignoring invalid beacon from {}: {}", from, e);
+                    continue;
+                }
+            };
+            if announcement.id == local_device().id {
+                continue;
+            }
+            if !peers.accept(&announcement, key, Instant::now()) {
+                println!("Ignoring beacon for {} from {}: wrong key or replayed", announcement.id, from);
+                continue;
+            }
+
+            let ttl = Duration::from_secs(announcement.ttl_secs.clamp(1, 600));
+            let device = DiscoveredDevice {
+                id: announcement.id,
+                name: announcement.name,
+                addresses: vec![from.ip().to_string()],
+                ports: announcement.ports,
+                transports: announcement.transports,
+                sources: vec!["beacon".to_string()],
+            };
+            registry.report(device, "beacon", Some(ttl));
+        }
+    });
+
+    Ok(())
+}
+
+/// Binds the beacon port with address reuse, so several instances on one host
+/// (say, the app and a CLI) can all hear broadcasts.
+fn bind(port: u16) -> Result<UdpSocket, Box<dyn Error>> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    socket.set_reuse_port(true)?;
+    socket.set_broadcast(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)).into())?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn announcement(id: &str, seq: u64) -> Announcement {
+        Announcement {
+            id: id.to_string(),
+            name: "Laptop".to_string(),
+            ports: BTreeMap::from([("webrtc".to_string(), 9002)]),
+            transports: vec!["webrtc".to_string()],
+            ttl_secs: 20,
+            seq,
+        }
+    }
+
+    fn key() -> SigningKey {
+        SigningKey::generate(&mut rand::rngs::OsRng)
+    }
+
+    #[test]
+    fn signed_beacons_round_trip() {
+        let key = key();
+        let (decoded, signer) = decode(&encode(&announcement("laptop", 1), &key)).unwrap();
+        assert_eq!(decoded.id, "laptop");
+        assert_eq!(decoded.ports["webrtc"], 9002);
+        assert_eq!(signer, key.verifying_key());
+    }
+
+    #[test]
+    fn tampered_beacons_are_rejected() {
+        let key = key();
+        let datagram = encode(&announcement("laptop", 1), &key);
+        let mut envelope: Envelope = serde_json::from_slice(&datagram).unwrap();
+        envelope.payload = envelope.payload.replace("Laptop", "Evil");
+        assert!(decode(&serde_json::to_vec(&envelope).unwrap()).is_err());
+
+        // Re-signed by someone else, but claiming the original key.
+        let mut envelope: Envelope = serde_json::from_slice(&datagram).unwrap();
+        let forged = self::key().sign(envelope.payload.as_bytes());
+        envelope.signature = BASE64.encode(forged.to_bytes());
+        assert!(decode(&serde_json::to_vec(&envelope).unwrap()).is_err());
+        assert!(decode(b"not json").is_err());
+    }
+
+    #[test]
+    fn pinned_ids_refuse_other_keys_and_old_sequence_numbers() {
+        let (key, other) = (key(), key());
+        let mut peers = Peers::default();
+        let now = Instant::now();
+        assert!(peers.accept(&announcement("laptop", 10), key.verifying_key(), now));
+        assert!(!peers.accept(&announcement("laptop", 11), other.verifying_key(), now));
+        // A replay, and an older announcement.
+        assert!(!peers.accept(&announcement("laptop", 10), key.verifying_key(), now));
+        assert!(!peers.accept(&announcement("laptop", 9), key.verifying_key(), now));
+        assert!(peers.accept(&announcement("laptop", 11), key.verifying_key(), now));
+        assert!(peers.accept(&announcement("phone", 1), other.verifying_key(), now));
+    }
+
+    #[test]
+    fn peers_are_bounded() {
+        let key = key().verifying_key();
+        let mut peers = Peers::default();
+        let start = Instant::now();
+        for n in 0..MAX_PEERS + 10 {
+            let seen = start + Duration::from_millis(n as u64);
+            assert!(peers.accept(&announcement(&format!("device-{}", n), 1), key, seen));
+        }
+        assert_eq!(peers.peers.len(), MAX_PEERS);
+        assert!(!peers.peers.contains_key("device-0"));
+        assert!(peers.peers.contains_key(&format!("device-{}", MAX_PEERS + 9)));
+
+        // A day later, every pin has lapsed.
+        let later = start + PEER_TTL + Duration::from_secs(60);
+        assert!(peers.accept(&announcement("device-x", 1), key, later));
+        assert_eq!(peers.peers.len(), 1);
+    }
+
+    fn key_path(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("unishare-beacon-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("beacon-key")
+    }
+
+    #[cfg(unix)]
+    fn mode(path: &Path) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saves_a_new_key_for_this_user_only() {
+        let path = key_path("new");
+        let key = load_or_create_key(&path);
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(load_or_create_key(&path).to_bytes(), key.to_bytes());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restricts_a_key_others_can_read() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = key_path("readable");
+        let key = key();
+        std::fs::write(&path, key.to_bytes()).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        assert_eq!(load_or_create_key(&path).to_bytes(), key.to_bytes());
+        assert_eq!(mode(&path), 0o600);
+    }
+
+    #[test]
+    fn replaces_a_file_that_is_not_a_key() {
+        let path = key_path("malformed");
+        std::fs::write(&path, b"not a key").unwrap();
+        let key = load_or_create_key(&path);
+        assert_eq!(std::fs::read(&path).unwrap(), key.to_bytes());
+    }
+}
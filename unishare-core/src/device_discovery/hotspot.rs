@@ -0,0 +1,380 @@
+use std::error::Error;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::local_device;
+
+/// NetworkManager connection profile used for our access point.
+pub const CONNECTION_NAME: &str = "unishare-hotspot";
+const GATEWAY_ATTEMPTS: u32 = 20;
+const GATEWAY_POLL: Duration = Duration::from_millis(250);
+
+/// What a peer needs to join the hotspot and reach our receivers.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HotspotInfo {
+    pub ssid: String,
+    pub passphrase: String,
+    /// Our address on the hotspot network; peers send files here.
+    pub gateway: String,
+    pub interface: String,
+}
+
+/// The NetworkManager operations hotspots need, so tests can swap in a fake.
+pub trait NetworkManager: Send + Sync {
+    /// Name of a Wi‑Fi device that can host an access point.
+    fn wifi_device(&self) -> Result<String, Box<dyn Error>>;
+    /// Creates and activates an access-point connection called `name`.
+    fn create_hotspot(&self, name: &str, interface: &str, ssid: &str, passphrase: &str) -> Result<(), Box<dyn Error>>;
+    /// The device's IPv4 address, if it has one yet.
+    fn ipv4_address(&self, interface: &str) -> Result<Option<String>, Box<dyn Error>>;
+    /// Deactivates and removes the connection called `name`.
+    fn delete_connection(&self, name: &str) -> Result<(), Box<dyn Error>>;
+}
+
+/// [`NetworkManager`] backed by the `nmcli` command-line client.
+pub struct Nmcli;
+
+impl Nmcli {
+    fn run(&self, args: &[&str]) -> Result<String, Box<dyn Error>> {
+        self.run_with_input(args, "")
+    }
+
+    /// Runs nmcli with `input` on its stdin, which is how secrets get to it
+    /// without showing up on its command line.
+    fn run_with_input(&self, args: &[&str], input: &str) -> Result<String, Box<dyn Error>> {
+        let not_found = |e: io::Error| -> Box<dyn Error> {
+            if e.kind() == io::ErrorKind::NotFound {
+                "NetworkManager (nmcli) isn't available on this system".into()
+            } else {
+                e.into()
+            }
+        };
+        let mut child = Command::new("nmcli")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(not_found)?;
+        // nmcli may exit without reading it all; its exit status says more.
+        let _ = child.stdin.take().expect("stdin is piped").write_all(input.as_bytes());
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("nmcli {} failed: {}", args.join(" "), stderr.trim()).into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl NetworkManager for Nmcli {
+    fn wifi_device(&self) -> Result<String, Box<dyn Error>> {
+        // Terse output is `DEVICE:TYPE` per line.
+        let devices = self.run(&["-t", "-f", "DEVICE,TYPE", "device"])?;
+        devices
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(_, kind)| *kind == "wifi")
+            .map(|(device, _)| device.to_string())
+            .ok_or_else(|| "No Wi‑Fi device found".into())
+    }
+
+    fn create_hotspot(&self, name: &str, interface: &str, ssid: &str, passphrase: &str) -> Result<(), Box<dyn Error>> {
+        self.run(&hotspot_profile_args(name, interface, ssid))?;
+        // The profile doesn't keep the passphrase; it's handed over on stdin
+        // for this activation only, so it never appears in a process listing.
+        let up = self.run_with_input(&["connection", "up", name, "passwd-file", "/dev/stdin"], &passwd_file(passphrase));
+        if let Err(e) = up {
+            let _ = self.delete_connection(name);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn ipv4_address(&self, interface: &str) -> Result<Option<String>, Box<dyn Error>> {
+        // Prints e.g. `10.42.0.1/24`, or nothing before the address is assigned.
+        let addresses = self.run(&["-g", "IP4.ADDRESS", "device", "show", interface])?;
+        Ok(addresses
+            .split(['\n', '|'])
+            .map(str::trim)
+            .find(|address| !address.is_empty())
+            .map(|address| address.split('/').next().unwrap_or(address).to_string()))
+    }
+
+    fn delete_connection(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.run(&["connection", "delete", name])?;
+        Ok(())
+    }
+}
+
+/// `nmcli` arguments adding an access-point profile like the one
+/// `nmcli device wifi hotspot` creates, except that NetworkManager asks for
+/// the passphrase on activation rather than storing it.
+fn hotspot_profile_args<'a>(name: &'a str, interface: &'a str, ssid: &'a str) -> Vec<&'a str> {
+    vec![
+        "connection", "add",
+        "type", "wifi",
+        "ifname", interface,
+        "con-name", name,
+        "autoconnect", "no",
+        "ssid", ssid,
+        "802-11-wireless.mode", "ap",
+        "ipv4.method", "shared",
+        "ipv6.method", "ignore",
+        "802-11-wireless-security.key-mgmt", "wpa-psk",
+        "802-11-wireless-security.proto", "rsn",
+        "802-11-wireless-security.pairwise", "ccmp",
+        "802-11-wireless-security.group", "ccmp",
+        // 2: not saved, asked for on each activation.
+        "802-11-wireless-security.psk-flags", "2",
+    ]
+}
+
+/// The `passwd-file` `nmcli connection up` reads the passphrase from.
+fn passwd_file(passphrase: &str) -> String {
+    format!("802-11-wireless-security.psk:{}\n", passphrase)
+}
+
+/// Starts and stops our access point. Kept in Tauri state so `stop_hotspot`
+/// tears down what `start_hotspot` created.
+pub struct HotspotController {
+    nm: Box<dyn NetworkManager>,
+    ssid: String,
+    active: Mutex<Option<HotspotInfo>>,
+}
+
+impl HotspotController {
+    pub fn new(nm: Box<dyn NetworkManager>, ssid: String) -> Self {
+        HotspotController {
+            nm,
+            ssid,
+            active: Mutex::new(None),
+        }
+    }
+
+    /// Uses `nmcli` and names the network after this device, e.g. `Unishare-3f2a`.
+    pub fn with_nmcli() -> Self {
+        let id = &local_device().id;
+        Self::new(Box::new(Nmcli), format!("Unishare-{}", &id[..id.len().min(4)]))
+    }
+
+    /// Creates the access point and waits for its gateway address. Calling it
+    /// while a hotspot is up returns the running one.
+    pub fn start(&self) -> Result<HotspotInfo, Box<dyn Error>> {
+        let mut active = self.active.lock().unwrap();
+        if let Some(info) = active.as_ref() {
+            return Ok(info.clone());
+        }
+
+        let interface = self.nm.wifi_device()?;
+        let ssid = self.ssid.clone();
+        let passphrase = generate_passphrase();
+        // A profile left behind by a crash would clash with the new one.
+        let _ = self.nm.delete_connection(CONNECTION_NAME);
+        self.nm.create_hotspot(CONNECTION_NAME, &interface, &ssid, &passphrase)?;
+
+        let gateway = match self.wait_for_gateway(&interface) {
+            Ok(gateway) => gateway,
+            Err(e) => {
+                let _ = self.nm.delete_connection(CONNECTION_NAME);
+                return Err(e);
+            }
+        };
+
+        let info = HotspotInfo {
+            ssid,
+            passphrase,
+            gateway,
+            interface,
+        };
+        println!("📡 Hotspot '{}' up on {} at {}", info.ssid, info.interface, info.gateway);
+        *active = Some(info.clone());
+        Ok(info)
+    }
+
+    /// Tears the access point down. Does nothing if none is running.
+    pub fn stop(&self) -> Result<(), Box<dyn Error>> {
+        let mut active = self.active.lock().unwrap();
+        if let Some(info) = active.as_ref() {
+            self.nm.delete_connection(CONNECTION_NAME)?;
+            println!("📡 Hotspot '{}' stopped", info.ssid);
+            *active = None;
+        }
+        Ok(())
+    }
+
+    pub fn active(&self) -> Option<HotspotInfo> {
+        self.active.lock().unwrap().clone()
+    }
+
+    fn wait_for_gateway(&self, interface: &str) -> Result<String, Box<dyn Error>> {
+        for _ in 0..GATEWAY_ATTEMPTS {
+            if let Some(address) = self.nm.ipv4_address(interface)? {
+                return Ok(address);
+            }
+            sleep(GATEWAY_POLL);
+        }
+        Err(format!("Hotspot on {} never got an IPv4 address", interface).into())
+    }
+}
+
+/// A WPA2 passphrase without look-alike characters, so it can be typed from
+/// the screen.
+fn generate_passphrase() -> String {
+    const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyzABCDEFGHJKMNPQRSTUVWXYZ23456789";
+    (0..12)
+        .map(|_| ALPHABET[rand::random::<usize>() % ALPHABET.len()] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Pretends to be NetworkManager with one Wi‑Fi device, recording the
+    /// connections that exist.
+    #[derive(Default)]
+    struct FakeNetworkManager {
+        connections: Mutex<Vec<String>>,
+        /// How many address lookups return nothing before the gateway appears.
+        address_delay: Mutex<u32>,
+        no_wifi: bool,
+        fail_create: bool,
+    }
+
+    impl NetworkManager for Arc<FakeNetworkManager> {
+        fn wifi_device(&self) -> Result<String, Box<dyn Error>> {
+            if self.no_wifi {
+                return Err("No Wi‑Fi device found".into());
+            }
+            Ok("wlan0".to_string())
+        }
+
+        fn create_hotspot(&self, name: &str, interface: &str, ssid: &str, passphrase: &str) -> Result<(), Box<dyn Error>> {
+            assert_eq!(interface, "wlan0");
+            assert!(ssid.starts_with("Unishare-"));
+            assert!(passphrase.len() >= 8);
+            if self.fail_create {
+                return Err("nmcli device wifi hotspot failed: No suitable device found".into());
+            }
+            self.connections.lock().unwrap().push(name.to_string());
+            Ok(())
+        }
+
+        fn ipv4_address(&self, _interface: &str) -> Result<Option<String>, Box<dyn Error>> {
+            let mut delay = self.address_delay.lock().unwrap();
+            if *delay > 0 {
+                *delay -= 1;
+                return Ok(None);
+            }
+            Ok(Some("10.42.0.1".to_string()))
+        }
+
+        fn delete_connection(&self, name: &str) -> Result<(), Box<dyn Error>> {
+            let mut connections = self.connections.lock().unwrap();
+            let before = connections.len();
+            connections.retain(|c| c != name);
+            if connections.len() == before {
+                return Err(format!("Error: unknown connection '{}'", name).into());
+            }
+            Ok(())
+        }
+    }
+
+    fn controller(fake: &Arc<FakeNetworkManager>) -> HotspotController {
+        HotspotController::new(Box::new(fake.clone()), "Unishare-test".to_string())
+    }
+
+    #[test]
+    fn start_returns_join_details() {
+        let fake = Arc::new(FakeNetworkManager::default());
+        *fake.address_delay.lock().unwrap() = 2;
+        let hotspot = controller(&fake);
+
+        let info = hotspot.start().unwrap();
+        assert_eq!(info.gateway, "10.42.0.1");
+        assert_eq!(info.interface, "wlan0");
+        assert_eq!(info.passphrase.len(), 12);
+        assert_eq!(*fake.connections.lock().unwrap(), vec![CONNECTION_NAME.to_string()]);
+        assert_eq!(hotspot.active(), Some(info));
+    }
+
+    #[test]
+    fn start_twice_reuses_running_hotspot() {
+        let fake = Arc::new(FakeNetworkManager::default());
+        let hotspot = controller(&fake);
+
+        let first = hotspot.start().unwrap();
+        let second = hotspot.start().unwrap();
+        assert_eq!(first, second);
+        assert_eq!(fake.connections.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn stop_removes_connection() {
+        let fake = Arc::new(FakeNetworkManager::default());
+        let hotspot = controller(&fake);
+
+        hotspot.start().unwrap();
+        hotspot.stop().unwrap();
+        assert!(fake.connections.lock().unwrap().is_empty());
+        assert_eq!(hotspot.active(), None);
+
+        // Stopping again is a no-op rather than an nmcli error.
+        hotspot.stop().unwrap();
+    }
+
+    #[test]
+    fn stale_profile_is_replaced() {
+        let fake = Arc::new(FakeNetworkManager::default());
+        fake.connections.lock().unwrap().push(CONNECTION_NAME.to_string());
+        let hotspot = controller(&fake);
+
+        hotspot.start().unwrap();
+        assert_eq!(*fake.connections.lock().unwrap(), vec![CONNECTION_NAME.to_string()]);
+    }
+
+    #[test]
+    fn missing_gateway_tears_down() {
+        let fake = Arc::new(FakeNetworkManager::default());
+        *fake.address_delay.lock().unwrap() = GATEWAY_ATTEMPTS;
+        let hotspot = controller(&fake);
+
+        assert!(hotspot.start().is_err());
+        assert!(fake.connections.lock().unwrap().is_empty());
+        assert_eq!(hotspot.active(), None);
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let no_wifi = Arc::new(FakeNetworkManager {
+            no_wifi: true,
+            ..Default::default()
+        });
+        assert!(controller(&no_wifi).start().unwrap_err().to_string().contains("No Wi‑Fi device"));
+
+        let failing = Arc::new(FakeNetworkManager {
+            fail_create: true,
+            ..Default::default()
+        });
+        let hotspot = controller(&failing);
+        assert!(hotspot.start().unwrap_err().to_string().contains("No suitable device"));
+        assert_eq!(hotspot.active(), None);
+    }
+
+    #[test]
+    fn passphrases_stay_off_the_command_line() {
+        let args = hotspot_profile_args(CONNECTION_NAME, "wlan0", "Unishare-3f2a");
+        assert!(!args.contains(&"password"));
+        let flags = args.iter().position(|arg| *arg == "802-11-wireless-security.psk-flags").unwrap();
+        assert_eq!(args[flags + 1], "2");
+        assert_eq!(passwd_file("k3yPhrase42"), "802-11-wireless-security.psk:k3yPhrase42\n");
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+use mdns_sd::{ResolvedService, ServiceDaemon, ServiceEvent, ServiceInfo};
+
+use super::{local_device, DeviceRegistry, DiscoveredDevice, LocalDevice, WIFI_DIRECT_PORT};
+
+/// DNS-SD service type every Unishare instance advertises.
+pub const SERVICE_TYPE: &str = "_unishare._tcp.local.";
+
+/// The mDNS responder, started on first use and shared by advertising and browsing.
+static DAEMON: Mutex<Option<ServiceDaemon>> = Mutex::new(None);
+static BROWSING: Mutex<bool> = Mutex::new(false);
+
+fn daemon() -> Result<ServiceDaemon, Box<dyn Error>> {
+    let mut daemon = DAEMON.lock().unwrap();
+    if let Some(daemon) = daemon.as_ref() {
+        return Ok(daemon.clone());
+    }
+    let started = ServiceDaemon::new()?;
+    *daemon = Some(started.clone());
+    Ok(started)
+}
+
+/// Advertises this instance as `<device id>._unishare._tcp.local.`.
+///
+/// - The SRV record points at the Wi‑Fi Direct receiver port.
+/// - TXT records carry the device name, ID, per-transport ports and transports.
+/// - Addresses follow the host's interfaces as they come and go.
+pub fn advertise() -> Result<(), Box<dyn Error>> {
+    let local = local_device();
+    let properties = txt_properties(local);
+
+    let host_name = format!("unishare-{}.local.", local.id);
+    let info = ServiceInfo::new(SERVICE_TYPE, &local.id, &host_name, "", WIFI_DIRECT_PORT, &properties[..])?
+        .enable_addr_auto();
+    daemon()?.register(info)?;
+    println!("📣 Advertising {} as {} over mDNS", local.name, local.id);
+    Ok(())
+}
+
+/// Starts browsing for other instances in the background, feeding `registry`.
+/// Calling it again while a browse is running does nothing.
+pub fn browse(registry: DeviceRegistry) -> Result<(), Box<dyn Error>> {
+    let mut browsing = BROWSING.lock().unwrap();
+    if *browsing {
+        return Ok(());
+    }
+    let events = daemon()?.browse(SERVICE_TYPE)?;
+    *browsing = true;
+
+    tokio::spawn(async move {
+        while let Ok(event) = events.recv_async().await {
+            match event {
+                ServiceEvent::ServiceResolved(service) => {
+                    if let Some(device) = parse_service(&service) {
+                        registry.report(device, "mdns", None);
+                    }
+                }
+                ServiceEvent::ServiceRemoved(_, fullname) => {
+                    if let Some(id) = fullname.strip_suffix(&format!(".{}", SERVICE_TYPE)) {
+                        registry.forget(id, "mdns");
+                    }
+                }
+                _ => {}
+            }
+        }
+        *BROWSING.lock().unwrap() = false;
+    });
+    Ok(())
+}
+
+/// The TXT records advertising `local`.
+fn txt_properties(local: &LocalDevice) -> [(&'static str, String); 4] {
+    let ports = local
+        .ports
+        .iter()
+        .map(|(transport, port)| format!("{}:{}", transport, port))
+        .collect::<Vec<_>>()
+        .join(",");
+    [
+        ("id", local.id.clone()),
+        ("name", local.name.clone()),
+        ("ports", ports),
+        ("transports", local.transports.join(",")),
+    ]
+}
+
+/// Turns a resolved service into a device, or `None` if it lacks the TXT
+/// records every Unishare instance publishes.
+fn parse_service(service: &ResolvedService) -> Option<DiscoveredDevice> {
+    let addresses = service.get_addresses().iter().map(|ip| ip.to_ip_addr()).collect();
+    parse_txt(|key| service.get_property_val_str(key), addresses)
+}
+
+/// Builds a device from its TXT records, looked up with `property`, and the
+/// addresses its service resolved to.
+fn parse_txt<'a>(property: impl Fn(&str) -> Option<&'a str>, mut addresses: Vec<IpAddr>) -> Option<DiscoveredDevice> {
+    let id = property("id")?.to_string();
+    let name = property("name").unwrap_or(&id).to_string();
+
+    let ports: BTreeMap<String, u16> = property("ports")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|entry| {
+            let (transport, port) = entry.split_once(':')?;
+            Some((transport.to_string(), port.parse().ok()?))
+        })
+        .collect();
+    let transports = property("transports")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    // Routable IPv4 first: that's what the TCP-based transports are usually
+    // reached on. Loopback only helps when both instances share a host.
+    addresses.sort_by_key(|ip| (ip.is_loopback(), ip.is_ipv6(), *ip));
+    let addresses = addresses.into_iter().map(|ip| ip.to_string()).collect();
+
+    Some(DiscoveredDevice {
+        id,
+        name,
+        addresses,
+        ports,
+        transports,
+        sources: vec!["mdns".to_string()],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn local() -> LocalDevice {
+        LocalDevice {
+            id: "a1b2".to_string(),
+            name: "Studio Mac".to_string(),
+            ports: BTreeMap::from([("webrtc".to_string(), 9002), ("wifi-direct".to_string(), 9000)]),
+            transports: vec!["webrtc".to_string(), "wifi-direct".to_string()],
+        }
+    }
+
+    #[test]
+    fn txt_records_round_trip() {
+        let local = local();
+        let txt: HashMap<&str, String> = txt_properties(&local).into_iter().collect();
+        assert_eq!(txt["ports"], "webrtc:9002,wifi-direct:9000");
+        assert_eq!(txt["transports"], "webrtc,wifi-direct");
+
+        let addresses = ["fe80::1", "127.0.0.1", "192.168.1.20"].map(|ip| ip.parse().unwrap()).to_vec();
+        let device = parse_txt(|key| txt.get(key).map(String::as_str), addresses).unwrap();
+        assert_eq!(device.id, local.id);
+        assert_eq!(device.name, local.name);
+        assert_eq!(device.ports, local.ports);
+        assert_eq!(device.transports, local.transports);
+        // Routable IPv4 first, loopback last.
+        assert_eq!(device.addresses, ["192.168.1.20", "fe80::1", "127.0.0.1"]);
+        assert_eq!(device.sources, ["mdns"]);
+    }
+
+    #[test]
+    fn tolerates_sparse_or_malformed_txt_records() {
+        let txt = HashMap::from([("id", "c3"), ("ports", "webrtc:9002,bluetooth,wifi-direct:huge")]);
+        let device = parse_txt(|key| txt.get(key).copied(), Vec::new()).unwrap();
+        assert_eq!(device.name, "c3");
+        assert_eq!(device.ports, BTreeMap::from([("webrtc".to_string(), 9002)]));
+        assert!(device.transports.is_empty());
+
+        // Not a Unishare instance.
+        let other = HashMap::from([("name", "Printer")]);
+        assert!(parse_txt(|key| other.get(key).copied(), Vec::new()).is_none());
+    }
+}
@@ -0,0 +1,118 @@
+pub mod beacon;
+pub mod hotspot;
+pub mod mdns;
+pub mod registry;
+
+pub use hotspot::{HotspotController, HotspotInfo};
+pub use registry::{DeviceRegistry, DiscoveryEvent, KnownDevice};
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocols::webrtc::SIGNALING_PORT;
+use crate::tools::connectivity::check_bluetooth;
+
+/// Port the Wi‑Fi Direct receiver listens on (see `protocols::wifi_direct`).
+pub const WIFI_DIRECT_PORT: u16 = 9000;
+/// Port the simulated Bluetooth receiver listens on (see `protocols::bluetooth`).
+pub const BLUETOOTH_PORT: u16 = 9001;
+
+/// A Unishare instance found on the network, merged across every discovery
+/// mechanism that has seen it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveredDevice {
+    pub id: String,
+    pub name: String,
+    /// IP addresses the device can be reached on; any of them works as a
+    /// `send_file` destination.
+    pub addresses: Vec<String>,
+    /// Listening port per transport, e.g. `"wifi-direct" => 9000`.
+    pub ports: BTreeMap<String, u16>,
+    pub transports: Vec<String>,
+    /// How the device was found, e.g. `["beacon", "mdns"]`.
+    pub sources: Vec<String>,
+}
+
+/// What this instance advertises about itself.
+#[derive(Clone, Debug)]
+pub struct LocalDevice {
+    pub id: String,
+    pub name: String,
+    pub ports: BTreeMap<String, u16>,
+    pub transports: Vec<String>,
+}
+
+static LOCAL_DEVICE: LazyLock<LocalDevice> = LazyLock::new(|| {
+    let mut ports = BTreeMap::new();
+    ports.insert("wifi-direct".to_string(), WIFI_DIRECT_PORT);
+    ports.insert("webrtc".to_string(), SIGNALING_PORT);
+
+    let mut transports = vec!["wifi-direct".to_string(), "webrtc".to_string()];
+    if check_bluetooth().unwrap_or(false) {
+        ports.insert("bluetooth".to_string(), BLUETOOTH_PORT);
+        transports.push("bluetooth".to_string());
+    }
+    transports.push("mobile-data".to_string());
+
+    LocalDevice {
+        id: device_id(),
+        name: device_name(),
+        ports,
+        transports,
+    }
+});
+
+pub fn local_device() -> &'static LocalDevice {
+    &LOCAL_DEVICE
+}
+
+/// Directory for Unishare's own settings (`$XDG_CONFIG_HOME/unishare` or the
+/// platform equivalent). Tests get a temp directory of their own, so they
+/// never create or read the user's device ID and keys.
+pub fn config_dir() -> PathBuf {
+    if cfg!(test) {
+        return std::env::temp_dir().join(format!("unishare-config-{}", std::process::id()));
+    }
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("unishare")
+}
+
+/// Returns this installation's device ID, generating and saving one on first use
+/// so other devices recognise us across restarts.
+fn device_id() -> String {
+    let path = config_dir().join("device-id");
+    if let Ok(id) = std::fs::read_to_string(&path) {
+        let id = id.trim();
+        if !id.is_empty() {
+            return id.to_string();
+        }
+    }
+
+    let id: String = (0..16).map(|_| format!("{:02x}", rand::random::<u8>())).collect();
+    let _ = std::fs::create_dir_all(config_dir());
+    if let Err(e) = std::fs::write(&path, &id) {
+        println!("Could not save device ID to {}: {}", path.display(), e);
+    }
+    id
+}
+
+/// The name shown to other devices: `UNISHARE_DEVICE_NAME` if set, otherwise the hostname.
+fn device_name() -> String {
+    if let Ok(name) = std::env::var("UNISHARE_DEVICE_NAME") {
+        return name;
+    }
+    std::process::Command::new("hostname")
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "Unishare device".to_string())
+}
@@ -0,0 +1,306 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use super::{local_device, DiscoveredDevice};
+
+#[derive(Clone, Debug)]
+pub enum DiscoveryEvent {
+    Discovered(DiscoveredDevice),
+    Lost { id: String },
+}
+
+/// A registry entry as shown by `list_devices`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownDevice {
+    #[serde(flatten)]
+    pub device: DiscoveredDevice,
+    /// Milliseconds since the epoch when any source last reported the device.
+    pub last_seen: u64,
+}
+
+/// One discovery mechanism's latest report of a device.
+struct Sighting {
+    device: DiscoveredDevice,
+    seen: SystemTime,
+    /// When the report goes stale; `None` if the mechanism reports removals itself.
+    expires: Option<Instant>,
+}
+
+type Sightings = BTreeMap<&'static str, Sighting>;
+
+/// Devices currently visible on the network, fed by every discovery mechanism
+/// and kept in Tauri state. Cloning gives another handle to the same registry.
+#[derive(Clone)]
+pub struct DeviceRegistry {
+    /// Keyed by device ID and then by discovery source.
+    devices: Arc<Mutex<HashMap<String, Sightings>>>,
+    events: broadcast::Sender<DiscoveryEvent>,
+}
+
+impl Default for DeviceRegistry {
+    fn default() -> Self {
+        DeviceRegistry {
+            devices: Arc::new(Mutex::new(HashMap::new())),
+            events: broadcast::channel(64).0,
+        }
+    }
+}
+
+impl DeviceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Receives every device discovered or lost from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<DiscoveryEvent> {
+        self.events.subscribe()
+    }
+
+    /// Records a device reported by `source` and announces it if that changes
+    /// what we know. Reports with a `ttl` are dropped unless refreshed in time.
+    /// Our own advertisement is ignored.
+    pub fn report(&self, device: DiscoveredDevice, source: &'static str, ttl: Option<Duration>) {
+        if device.id == local_device().id {
+            return;
+        }
+        let id = device.id.clone();
+        let sighting = Sighting {
+            device,
+            seen: SystemTime::now(),
+            expires: ttl.map(|ttl| Instant::now() + ttl),
+        };
+        let (before, after) = {
+            let mut devices = self.devices.lock().unwrap();
+            let sightings = devices.entry(id.clone()).or_default();
+            let before = merge_sightings(sightings);
+            sightings.insert(source, sighting);
+            (before, merge_sightings(sightings))
+        };
+        self.publish_change(&id, before, after);
+    }
+
+    /// Forgets `source`'s report of a device. The device is only lost once no
+    /// source reports it any more.
+    pub fn forget(&self, id: &str, source: &'static str) {
+        let (before, after) = {
+            let mut devices = self.devices.lock().unwrap();
+            let Some(sightings) = devices.get_mut(id) else {
+                return;
+            };
+            let before = merge_sightings(sightings);
+            sightings.remove(source);
+            let after = merge_sightings(sightings);
+            if sightings.is_empty() {
+                devices.remove(id);
+            }
+            (before, after)
+        };
+        self.publish_change(id, before, after);
+    }
+
+    /// Drops reports whose TTL has passed without a refresh.
+    pub fn expire(&self) {
+        let now = Instant::now();
+        let mut changes = Vec::new();
+        {
+            let mut devices = self.devices.lock().unwrap();
+            devices.retain(|id, sightings| {
+                let before = merge_sightings(sightings);
+                sightings.retain(|_, sighting| sighting.expires.is_none_or(|expires| expires > now));
+                let after = merge_sightings(sightings);
+                if before != after {
+                    changes.push((id.clone(), before, after));
+                }
+                !sightings.is_empty()
+            });
+        }
+        for (id, before, after) in changes {
+            self.publish_change(&id, before, after);
+        }
+    }
+
+    /// Runs [`DeviceRegistry::expire`] once a second for as long as the app runs.
+    pub fn spawn_expiry(&self) {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                tick.tick().await;
+                registry.expire();
+            }
+        });
+    }
+
+    /// Every visible device, most recently seen first.
+    pub fn devices(&self) -> Vec<KnownDevice> {
+        let devices = self.devices.lock().unwrap();
+        let mut known: Vec<KnownDevice> = devices.values().filter_map(known_device).collect();
+        known.sort_by(|a, b| b.last_seen.cmp(&a.last_seen).then_with(|| a.device.name.cmp(&b.device.name)));
+        known
+    }
+
+    /// Looks a device up by ID, or failing that by name (ignoring case).
+    ///
+    /// Returns `Ok(None)` when nothing matches, so callers can fall back to
+    /// treating the input as an address, and an error when a name is shared
+    /// by several devices.
+    pub fn resolve(&self, id_or_name: &str) -> Result<Option<KnownDevice>, String> {
+        let devices = self.devices.lock().unwrap();
+        if let Some(known) = devices.get(id_or_name).and_then(known_device) {
+            return Ok(Some(known));
+        }
+
+        let mut matches = devices
+            .values()
+            .filter_map(known_device)
+            .filter(|known| known.device.name.eq_ignore_ascii_case(id_or_name));
+        match (matches.next(), matches.next()) {
+            (Some(known), None) => Ok(Some(known)),
+            (Some(_), Some(_)) => Err(format!(
+                "Several devices are named '{}'; use a device ID instead.",
+                id_or_name
+            )),
+            _ => Ok(None),
+        }
+    }
+
+    fn publish_change(&self, id: &str, before: Option<DiscoveredDevice>, after: Option<DiscoveredDevice>) {
+        if before == after {
+            return;
+        }
+        match after {
+            Some(device) => {
+                println!(
+                    "🔎 Discovered {} ({}) at {:?} via {:?}",
+                    device.name, device.id, device.addresses, device.sources
+                );
+                let _ = self.events.send(DiscoveryEvent::Discovered(device));
+            }
+            None => {
+                println!("👋 Lost device {}", id);
+                let _ = self.events.send(DiscoveryEvent::Lost { id: id.to_string() });
+            }
+        }
+    }
+}
+
+fn known_device(sightings: &Sightings) -> Option<KnownDevice> {
+    let device = merge_sightings(sightings)?;
+    let last_seen = sightings.values().map(|sighting| sighting.seen).max()?;
+    Some(KnownDevice {
+        device,
+        last_seen: last_seen
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+    })
+}
+
+/// Combines every source's view of one device. Addresses are pooled; the
+/// other fields come from the first source that reported them.
+fn merge_sightings(sightings: &Sightings) -> Option<DiscoveredDevice> {
+    let mut merged: Option<DiscoveredDevice> = None;
+    for (source, sighting) in sightings {
+        let merged = merged.get_or_insert_with(|| DiscoveredDevice {
+            addresses: Vec::new(),
+            sources: Vec::new(),
+            ..sighting.device.clone()
+        });
+        for address in &sighting.device.addresses {
+            if !merged.addresses.contains(address) {
+                merged.addresses.push(address.clone());
+            }
+        }
+        merged.sources.push(source.to_string());
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, name: &str, address: &str) -> DiscoveredDevice {
+        DiscoveredDevice {
+            id: id.to_string(),
+            name: name.to_string(),
+            addresses: vec![address.to_string()],
+            ports: BTreeMap::from([("webrtc".to_string(), 9002)]),
+            transports: vec!["webrtc".to_string()],
+            sources: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn merges_sightings_from_every_source() {
+        let registry = DeviceRegistry::new();
+        let mut events = registry.subscribe();
+        registry.report(device("a1", "Laptop", "192.168.1.5"), "mdns", None);
+        registry.report(device("a1", "Laptop (beacon)", "10.0.0.5"), "beacon", None);
+        registry.report(device("a1", "Laptop", "192.168.1.5"), "mdns", None);
+
+        let known = registry.devices();
+        assert_eq!(known.len(), 1);
+        let merged = &known[0].device;
+        assert_eq!(merged.addresses, ["10.0.0.5", "192.168.1.5"]);
+        assert_eq!(merged.sources, ["beacon", "mdns"]);
+        assert_eq!(merged.name, "Laptop (beacon)");
+        // The repeated mDNS report changed nothing, so it wasn't announced.
+        assert!(matches!(events.try_recv(), Ok(DiscoveryEvent::Discovered(_))));
+        assert!(matches!(events.try_recv(), Ok(DiscoveryEvent::Discovered(_))));
+        assert!(events.try_recv().is_err());
+
+        // Lost only once the last source forgets it.
+        registry.forget("a1", "mdns");
+        assert_eq!(registry.devices()[0].device.addresses, ["10.0.0.5"]);
+        registry.forget("a1", "beacon");
+        assert!(registry.devices().is_empty());
+        assert!(matches!(events.try_recv(), Ok(DiscoveryEvent::Discovered(_))));
+        assert!(matches!(events.try_recv(), Ok(DiscoveryEvent::Lost { id }) if id == "a1"));
+    }
+
+    #[test]
+    fn sightings_expire_after_their_ttl() {
+        let registry = DeviceRegistry::new();
+        registry.report(device("b1", "Phone", "10.0.0.7"), "beacon", Some(Duration::from_millis(20)));
+        registry.report(device("b1", "Phone", "10.0.0.8"), "mdns", None);
+        registry.report(device("b2", "Tablet", "10.0.0.9"), "beacon", Some(Duration::from_millis(20)));
+        registry.expire();
+        assert_eq!(registry.devices().len(), 2);
+
+        std::thread::sleep(Duration::from_millis(30));
+        let mut events = registry.subscribe();
+        registry.expire();
+        let known = registry.devices();
+        assert_eq!(known.len(), 1);
+        // The mDNS sighting has no TTL and keeps the phone listed.
+        assert_eq!(known[0].device.addresses, ["10.0.0.8"]);
+        assert_eq!(known[0].device.sources, ["mdns"]);
+        let mut lost = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let DiscoveryEvent::Lost { id } = event {
+                lost.push(id);
+            }
+        }
+        assert_eq!(lost, ["b2"]);
+    }
+
+    #[test]
+    fn resolves_by_id_or_unambiguous_name() {
+        let registry = DeviceRegistry::new();
+        registry.report(device("c1", "Office PC", "10.0.0.1"), "mdns", None);
+        registry.report(device("c2", "Office PC", "10.0.0.2"), "mdns", None);
+        registry.report(device("c3", "Kitchen", "10.0.0.3"), "mdns", None);
+
+        assert_eq!(registry.resolve("c2").unwrap().unwrap().device.addresses, ["10.0.0.2"]);
+        assert_eq!(registry.resolve("kitchen").unwrap().unwrap().device.id, "c3");
+        let err = registry.resolve("office pc").unwrap_err();
+        assert!(err.contains("Several devices"), "{}", err);
+        assert!(registry.resolve("10.0.0.9").unwrap().is_none());
+    }
+}
@@ -0,0 +1,532 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::gif::GifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::tiff::TiffEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, GenericImageView, ImageDecoder, ImageEncoder, ImageReader, Rgb, RgbImage};
+use serde::Serialize;
+
+use crate::protocols::filename::FilenamePolicy;
+use crate::protocols::metadata::carry_over;
+
+/// JPEG quality used when none is given.
+const DEFAULT_QUALITY: u8 = 85;
+
+/// Image formats the converter reads and writes.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Bmp,
+    Tiff,
+    Gif,
+}
+
+impl ImageFormat {
+    /// The usual file extension.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Tiff => "tiff",
+            ImageFormat::Gif => "gif",
+        }
+    }
+
+    /// Guesses the format from a file's first bytes.
+    pub fn sniff(head: &[u8]) -> Option<Self> {
+        image::guess_format(head).ok().and_then(Self::from_image)
+    }
+
+    /// Guesses the format from a path's extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+
+    fn from_image(format: image::ImageFormat) -> Option<Self> {
+        match format {
+            image::ImageFormat::Png => Some(ImageFormat::Png),
+            image::ImageFormat::Jpeg => Some(ImageFormat::Jpeg),
+            image::ImageFormat::WebP => Some(ImageFormat::Webp),
+            image::ImageFormat::Bmp => Some(ImageFormat::Bmp),
+            image::ImageFormat::Tiff => Some(ImageFormat::Tiff),
+            image::ImageFormat::Gif => Some(ImageFormat::Gif),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    /// Accepts format names and extensions, ignoring case.
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        match label.to_ascii_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "jpeg" | "jpg" | "jpe" => Ok(ImageFormat::Jpeg),
+            "webp" => Ok(ImageFormat::Webp),
+            "bmp" => Ok(ImageFormat::Bmp),
+            "tiff" | "tif" => Ok(ImageFormat::Tiff),
+            "gif" => Ok(ImageFormat::Gif),
+            _ => Err(format!("Unsupported image format '{}'", label)),
+        }
+    }
+}
+
+/// What to convert an image to.
+#[derive(Clone, Copy, Debug)]
+pub struct ImageOptions {
+    pub format: ImageFormat,
+    /// Larger images are scaled down to fit, keeping their aspect ratio.
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    /// JPEG quality, 1 to 100. WebP is always written lossless.
+    pub quality: u8,
+    /// Drop EXIF (camera, location, timestamps) and ICC metadata. Kept
+    /// metadata is only written where the target format supports it.
+    pub strip_metadata: bool,
+}
+
+impl ImageOptions {
+    pub fn new(format: ImageFormat) -> Self {
+        ImageOptions {
+            format,
+            max_width: None,
+            max_height: None,
+            quality: DEFAULT_QUALITY,
+            strip_metadata: true,
+        }
+    }
+}
+
+/// What an image conversion did.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageReport {
+    pub input: String,
+    pub output: String,
+    pub source: ImageFormat,
+    pub target: ImageFormat,
+    pub original_width: u32,
+    pub original_height: u32,
+    pub width: u32,
+    pub height: u32,
+    /// The EXIF orientation was baked into the pixels.
+    pub orientation_applied: bool,
+    pub metadata_stripped: bool,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+/// Converts the image at `input` and writes it to `output`.
+///
+/// - The EXIF orientation is applied to the pixels, so viewers that ignore
+///   it still show the image the right way up.
+/// - Only the first frame of an animated GIF or WebP is kept.
+/// - `output` may equal `input`; it's only replaced once the new image is
+///   fully written.
+pub fn convert_image(input: &Path, output: &Path, options: ImageOptions) -> Result<ImageReport, Box<dyn Error>> {
+    let bytes_read = fs::metadata(input)?.len();
+    let reader = ImageReader::open(input)?.with_guessed_format()?;
+    let source = reader
+        .format()
+        .and_then(ImageFormat::from_image)
+        .ok_or_else(|| format!("{} isn't a supported image", input.display()))?;
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut exif = decoder.exif_metadata()?;
+    let icc = decoder.icc_profile()?;
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    let (original_width, original_height) = image.dimensions();
+    image.apply_orientation(orientation);
+    if let Some(exif) = exif.as_mut() {
+        // The pixels are upright now; a kept orientation tag would turn them again.
+        let _ = Orientation::remove_from_exif_chunk(exif);
+    }
+
+    let (width, height) = image.dimensions();
+    let max_width = options.max_width.unwrap_or(width);
+    let max_height = options.max_height.unwrap_or(height);
+    if width > max_width || height > max_height {
+        image = image.resize(max_width, max_height, FilterType::Lanczos3);
+    }
+    let image = prepare_pixels(image, options.format);
+    let metadata = if options.strip_metadata {
+        Metadata::default()
+    } else {
+        Metadata { exif, icc }
+    };
+
+    let mut temp = PathBuf::from(output);
+    temp.set_file_name(format!(
+        ".{}.converting",
+        output.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()
+    ));
+    let result = File::create(&temp)
+        .map_err(Into::into)
+        .and_then(|file| write_image(&image, BufWriter::new(file), &options, metadata));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    // Keeps the permissions and times restored from the sender.
+    if let Err(e) = carry_over(input, &temp) {
+        println!("Couldn't keep the permissions and times of {}: {}", input.display(), e);
+    }
+    fs::rename(&temp, output)?;
+
+    let (width, height) = image.dimensions();
+    let report = ImageReport {
+        input: input.display().to_string(),
+        output: output.display().to_string(),
+        source,
+        target: options.format,
+        original_width,
+        original_height,
+        width,
+        height,
+        orientation_applied: orientation != Orientation::NoTransforms,
+        metadata_stripped: options.strip_metadata,
+        bytes_read,
+        bytes_written: fs::metadata(output)?.len(),
+    };
+    println!(
+        "🖼️ Converted {} ({}, {}x{}) to {} ({}, {}x{})",
+        input.display(),
+        source,
+        original_width,
+        original_height,
+        output.display(),
+        options.format,
+        width,
+        height
+    );
+    Ok(report)
+}
+
+#[derive(Default)]
+struct Metadata {
+    exif: Option<Vec<u8>>,
+    icc: Option<Vec<u8>>,
+}
+
+/// Converts pixels to something `format` can store: JPEG has no alpha, and
+/// only PNG and TIFF keep 16-bit samples.
+fn prepare_pixels(image: DynamicImage, format: ImageFormat) -> DynamicImage {
+    match format {
+        ImageFormat::Png => image,
+        ImageFormat::Jpeg if image.color().has_alpha() => DynamicImage::ImageRgb8(flatten(&image)),
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()),
+        ImageFormat::Gif => DynamicImage::ImageRgba8(image.to_rgba8()),
+        ImageFormat::Webp | ImageFormat::Bmp | ImageFormat::Tiff if image.color().has_alpha() => {
+            DynamicImage::ImageRgba8(image.to_rgba8())
+        }
+        ImageFormat::Webp | ImageFormat::Bmp | ImageFormat::Tiff => DynamicImage::ImageRgb8(image.to_rgb8()),
+    }
+}
+
+/// Composites transparent pixels onto white instead of letting them turn black.
+fn flatten(image: &DynamicImage) -> RgbImage {
+    let rgba = image.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: u8| ((u16::from(c) * u16::from(a) + 255 * (255 - u16::from(a))) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+fn write_image(
+    image: &DynamicImage,
+    mut writer: BufWriter<File>,
+    options: &ImageOptions,
+    metadata: Metadata,
+) -> Result<(), Box<dyn Error>> {
+    match options.format {
+        ImageFormat::Png => encode(image, PngEncoder::new(&mut writer), metadata)?,
+        ImageFormat::Jpeg => encode(
+            image,
+            JpegEncoder::new_with_quality(&mut writer, options.quality.clamp(1, 100)),
+            metadata,
+        )?,
+        ImageFormat::Webp => encode(image, WebPEncoder::new_lossless(&mut writer), metadata)?,
+        ImageFormat::Bmp => encode(image, BmpEncoder::new(&mut writer), metadata)?,
+        ImageFormat::Tiff => encode(image, TiffEncoder::new(&mut writer), metadata)?,
+        ImageFormat::Gif => encode(image, GifEncoder::new(&mut writer), metadata)?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes `image` with whatever of `metadata` the encoder can store.
+fn encode(image: &DynamicImage, mut encoder: impl ImageEncoder, metadata: Metadata) -> image::ImageResult<()> {
+    if let Some(icc) = metadata.icc {
+        let _ = encoder.set_icc_profile(icc);
+    }
+    if let Some(exif) = metadata.exif {
+        let _ = encoder.set_exif_metadata(exif);
+    }
+    image.write_with_encoder(encoder)
+}
+
+/// Which received images to convert automatically, and how.
+#[derive(Clone, Debug)]
+pub struct ImageRules {
+    /// Source format and the format to convert it to.
+    pub conversions: Vec<(ImageFormat, ImageFormat)>,
+    /// Larger images are scaled down to fit this many pixels on each side.
+    pub max_dimension: Option<u32>,
+    pub quality: u8,
+    pub strip_metadata: bool,
+}
+
+impl Default for ImageRules {
+    /// WebP, TIFF and BMP become PNG, which every viewer opens; metadata is
+    /// stripped.
+    fn default() -> Self {
+        ImageRules {
+            conversions: vec![
+                (ImageFormat::Webp, ImageFormat::Png),
+                (ImageFormat::Tiff, ImageFormat::Png),
+                (ImageFormat::Bmp, ImageFormat::Png),
+            ],
+            max_dimension: None,
+            quality: DEFAULT_QUALITY,
+            strip_metadata: true,
+        }
+    }
+}
+
+impl ImageRules {
+    /// Reads `UNISHARE_IMAGE_RULES` (e.g. `webp=png,tiff=jpeg`),
+    /// `UNISHARE_IMAGE_MAX_DIMENSION`, `UNISHARE_IMAGE_QUALITY` and
+    /// `UNISHARE_IMAGE_KEEP_METADATA`.
+    pub fn from_env() -> Self {
+        let mut rules = ImageRules::default();
+        if let Ok(value) = std::env::var("UNISHARE_IMAGE_RULES") {
+            match parse_conversions(&value) {
+                Ok(conversions) => rules.conversions = conversions,
+                Err(e) => println!("Ignoring UNISHARE_IMAGE_RULES: {}", e),
+            }
+        }
+        if let Some(max) = std::env::var("UNISHARE_IMAGE_MAX_DIMENSION").ok().and_then(|v| v.parse().ok()) {
+            rules.max_dimension = Some(max);
+        }
+        if let Some(quality) = std::env::var("UNISHARE_IMAGE_QUALITY").ok().and_then(|v| v.parse().ok()) {
+            rules.quality = quality;
+        }
+        if let Ok(value) = std::env::var("UNISHARE_IMAGE_KEEP_METADATA") {
+            rules.strip_metadata = !matches!(value.as_str(), "1" | "true" | "yes");
+        }
+        rules
+    }
+
+    /// Converts the received file at `path` if a rule matches its content,
+    /// replacing it with a file carrying the target's extension. Returns
+    /// `None` when no rule applies.
+    ///
+    /// The new name is claimed through `policy` like a received file's, so a
+    /// file already there gets a ` (1)` suffix instead of being overwritten.
+    pub fn apply(&self, policy: &FilenamePolicy, path: &Path) -> Result<Option<ImageReport>, Box<dyn Error>> {
+        let mut head = Vec::with_capacity(32);
+        File::open(path)?.take(32).read_to_end(&mut head)?;
+        let Some(source) = ImageFormat::sniff(&head) else {
+            return Ok(None);
+        };
+        let Some(&(_, target)) = self.conversions.iter().find(|(from, _)| *from == source) else {
+            return Ok(None);
+        };
+
+        let options = ImageOptions {
+            format: target,
+            max_width: self.max_dimension,
+            max_height: self.max_dimension,
+            quality: self.quality,
+            strip_metadata: self.strip_metadata,
+        };
+        let download_dir = policy.download_dir.canonicalize()?;
+        let relative = path
+            .canonicalize()?
+            .strip_prefix(&download_dir)
+            .map(|relative| relative.with_extension(target.extension()))
+            .map_err(|_| format!("{} isn't in the download directory", path.display()))?;
+        let output = policy.reserve(&relative.to_string_lossy())?.path;
+        let report = match convert_image(path, &output, options) {
+            Ok(report) => report,
+            Err(e) => {
+                let _ = fs::remove_file(&output);
+                return Err(e);
+            }
+        };
+        fs::remove_file(path)?;
+        Ok(Some(report))
+    }
+}
+
+/// Parses `from=to` pairs separated by commas.
+fn parse_conversions(value: &str) -> Result<Vec<(ImageFormat, ImageFormat)>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (from, to) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected from=to, got '{}'", pair))?;
+            Ok((from.trim().parse()?, to.trim().parse()?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("unishare-images-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn policy(dir: &Path) -> FilenamePolicy {
+        FilenamePolicy {
+            download_dir: dir.to_path_buf(),
+            ..FilenamePolicy::default()
+        }
+    }
+
+    fn write_image(path: &Path, width: u32, height: u32, format: image::ImageFormat) {
+        let image = RgbImage::from_fn(width, height, |x, y| Rgb([(x * 10) as u8, (y * 10) as u8, 128]));
+        image.save_with_format(path, format).unwrap();
+    }
+
+    /// A big-endian EXIF chunk holding only an orientation tag.
+    fn exif_with_orientation(orientation: u16) -> Vec<u8> {
+        let mut exif = b"MM\x00\x2a\x00\x00\x00\x08\x00\x01\x01\x12\x00\x03\x00\x00\x00\x01".to_vec();
+        exif.extend_from_slice(&orientation.to_be_bytes());
+        exif.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        exif
+    }
+
+    #[test]
+    fn recognizes_formats_by_content_and_name() {
+        let dir = temp_dir("formats");
+        for (format, expected) in [
+            (image::ImageFormat::Png, ImageFormat::Png),
+            (image::ImageFormat::Jpeg, ImageFormat::Jpeg),
+            (image::ImageFormat::WebP, ImageFormat::Webp),
+            (image::ImageFormat::Bmp, ImageFormat::Bmp),
+            (image::ImageFormat::Tiff, ImageFormat::Tiff),
+            (image::ImageFormat::Gif, ImageFormat::Gif),
+        ] {
+            // The extension lies; the content decides.
+            let path = dir.join(format!("image-{}.bin", expected));
+            write_image(&path, 2, 2, format);
+            assert_eq!(ImageFormat::sniff(&fs::read(&path).unwrap()), Some(expected));
+        }
+        assert_eq!(ImageFormat::sniff(b"just some text"), None);
+
+        assert_eq!(ImageFormat::from_path(Path::new("a/photo.JPG")), Some(ImageFormat::Jpeg));
+        assert_eq!(ImageFormat::from_path(Path::new("scan.tif")), Some(ImageFormat::Tiff));
+        assert_eq!(ImageFormat::from_path(Path::new("notes.txt")), None);
+        assert!("heic".parse::<ImageFormat>().is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn applies_exif_orientation_and_resizes() {
+        let dir = temp_dir("orientation");
+        let input = dir.join("rotated.png");
+        // 4x2 with a red top-left corner, tagged "rotate 90° clockwise".
+        let image = RgbImage::from_fn(4, 2, |x, y| if (x, y) == (0, 0) { Rgb([255, 0, 0]) } else { Rgb([0, 0, 255]) });
+        let mut encoder = PngEncoder::new(BufWriter::new(File::create(&input).unwrap()));
+        encoder.set_exif_metadata(exif_with_orientation(6)).unwrap();
+        DynamicImage::ImageRgb8(image).write_with_encoder(encoder).unwrap();
+
+        let output = dir.join("upright.png");
+        let report = convert_image(&input, &output, ImageOptions::new(ImageFormat::Png)).unwrap();
+        assert!(report.orientation_applied);
+        assert_eq!((report.original_width, report.original_height), (4, 2));
+        assert_eq!((report.width, report.height), (2, 4));
+        let upright = image::open(&output).unwrap().to_rgb8();
+        assert_eq!(upright.get_pixel(1, 0).0, [255, 0, 0]);
+
+        let large = dir.join("large.bmp");
+        write_image(&large, 40, 20, image::ImageFormat::Bmp);
+        let options = ImageOptions {
+            max_width: Some(10),
+            max_height: Some(10),
+            ..ImageOptions::new(ImageFormat::Jpeg)
+        };
+        let report = convert_image(&large, &dir.join("small.jpg"), options).unwrap();
+        assert!(!report.orientation_applied);
+        assert_eq!((report.width, report.height), (10, 5));
+        assert_eq!(image::open(dir.join("small.jpg")).unwrap().dimensions(), (10, 5));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rules_only_convert_matching_images() {
+        let dir = temp_dir("rules");
+        let rules = ImageRules {
+            conversions: parse_conversions(" webp=png, tiff = jpg ,").unwrap(),
+            ..ImageRules::default()
+        };
+        assert_eq!(
+            rules.conversions,
+            [(ImageFormat::Webp, ImageFormat::Png), (ImageFormat::Tiff, ImageFormat::Jpeg)]
+        );
+        assert!(parse_conversions("webp").is_err());
+        assert!(parse_conversions("webp=heic").is_err());
+
+        // A PNG has no rule, and text isn't an image at all.
+        write_image(&dir.join("keep.png"), 2, 2, image::ImageFormat::Png);
+        fs::write(dir.join("notes.tiff"), b"not really an image").unwrap();
+        assert_eq!(rules.apply(&policy(&dir), &dir.join("keep.png")).unwrap(), None);
+        assert_eq!(rules.apply(&policy(&dir), &dir.join("notes.tiff")).unwrap(), None);
+        assert!(dir.join("keep.png").exists());
+
+        // Matched by content, whatever the extension says.
+        write_image(&dir.join("scan.png"), 3, 2, image::ImageFormat::Tiff);
+        let report = rules.apply(&policy(&dir), &dir.join("scan.png")).unwrap().unwrap();
+        assert_eq!((report.source, report.target), (ImageFormat::Tiff, ImageFormat::Jpeg));
+        assert_eq!(PathBuf::from(&report.output).file_name().unwrap(), "scan.jpg");
+        assert!(!dir.join("scan.png").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn conversions_never_overwrite_existing_files() {
+        let dir = temp_dir("collision");
+        fs::write(dir.join("photo.png"), b"keep me").unwrap();
+        write_image(&dir.join("photo.webp"), 4, 3, image::ImageFormat::WebP);
+
+        let report = ImageRules::default().apply(&policy(&dir), &dir.join("photo.webp")).unwrap().unwrap();
+        assert_eq!(fs::read(dir.join("photo.png")).unwrap(), b"keep me");
+        assert_eq!(PathBuf::from(&report.output).file_name().unwrap(), "photo (1).png");
+        assert_eq!(ImageFormat::sniff(&fs::read(&report.output).unwrap()), Some(ImageFormat::Png));
+        assert!(!dir.join("photo.webp").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,31 @@
+//! Everything the Unishare apps share: transports, device discovery,
+//! connectivity checks and file conversion. The Tauri apps wrap this in
+//! commands; the `unishare` binary drives it from the command line.
+//!
+//! - [`protocols`]: sending and receiving files over WebRTC, Wi‑Fi Direct,
+//!   Bluetooth and the Mobile Data relay, picked by
+//!   [`protocols::protocol_manager`].
+//! - [`device_discovery`]: finding other instances over mDNS and UDP beacons,
+//!   and opening a hotspot for them to join.
+//! - [`tools`]: adapter, interface and internet checks, kept current by
+//!   [`tools::watcher`].
+//! - [`converter`], [`detect`], [`normalize`] and [`image_converter`]: text
+//!   encoding and image conversion, run on received files by [`post_receive`].
+//! - `daemon` (Unix only): all of the above as a background service with a
+//!   JSON-RPC API on a Unix domain socket, and a client for it. Elsewhere the
+//!   apps and the CLI run everything in-process.
+//!
+//! Background work is spawned with `tokio::spawn`, so callers need to be
+//! inside a Tokio runtime.
+
+pub mod converter;
+#[cfg(unix)]
+pub mod daemon;
+pub mod detect;
+pub mod device_discovery;
+pub mod image_converter;
+pub mod normalize;
+pub mod post_receive;
+pub mod protocols;
+pub mod tools;
+pub mod webrtc_transfer;
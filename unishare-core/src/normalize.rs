@@ -0,0 +1,230 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::converter::{convert, ConvertOptions, Encoding, Newline};
+use crate::detect::{detect_file, Detection};
+use crate::protocols::metadata::carry_over;
+
+/// Below this, a guessed encoding isn't trusted enough to rewrite a file.
+const MIN_CONFIDENCE: f32 = 0.5;
+
+/// What received text files are normalized to.
+#[derive(Clone, Copy, Debug)]
+pub struct NormalizeOptions {
+    pub encoding: Encoding,
+    pub newline: Newline,
+}
+
+impl Default for NormalizeOptions {
+    /// UTF-8 with the platform's line endings.
+    fn default() -> Self {
+        NormalizeOptions {
+            encoding: Encoding::Utf8,
+            newline: Newline::native(),
+        }
+    }
+}
+
+/// What normalizing one received file did; attached to the transfer result.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizationRecord {
+    pub file: String,
+    /// `false` means the file looked binary and was left alone.
+    pub text: bool,
+    pub source_encoding: Option<Encoding>,
+    /// How sure detection was of `source_encoding`.
+    pub confidence: f32,
+    pub target_encoding: Option<Encoding>,
+    pub line_endings_converted: u64,
+    /// Human-readable list of changes; empty when the file was already normal.
+    pub changes: Vec<String>,
+}
+
+impl NormalizationRecord {
+    fn untouched(file: &Path, detection: &Detection) -> Self {
+        NormalizationRecord {
+            file: file.display().to_string(),
+            text: !detection.binary,
+            source_encoding: detection.encoding,
+            confidence: detection.confidence,
+            target_encoding: None,
+            line_endings_converted: 0,
+            changes: Vec::new(),
+        }
+    }
+}
+
+/// Rewrites a received file to `options` if it's text.
+///
+/// - Binary files, text whose encoding is uncertain and text that doesn't
+///   decode cleanly are left untouched.
+/// - The file is only replaced when something actually changed.
+pub fn normalize_file(path: &Path, options: NormalizeOptions) -> io::Result<NormalizationRecord> {
+    let detection = detect_file(path)?;
+    let Some(source) = detection.encoding else {
+        println!("Leaving {} as is: not a text file", path.display());
+        return Ok(NormalizationRecord::untouched(path, &detection));
+    };
+    if detection.confidence < MIN_CONFIDENCE {
+        println!(
+            "Leaving {} as is: unsure of its encoding ({} at {:.0}%)",
+            path.display(),
+            source,
+            detection.confidence * 100.0
+        );
+        return Ok(NormalizationRecord::untouched(path, &detection));
+    }
+
+    let mut temp = PathBuf::from(path);
+    temp.set_file_name(format!(
+        ".{}.normalizing",
+        path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()
+    ));
+    let convert_options = ConvertOptions {
+        from: Some(source),
+        to: options.encoding,
+        write_bom: false,
+        newline: Some(options.newline),
+    };
+    let reader = BufReader::new(File::open(path)?);
+    let report = match File::create(&temp).and_then(|file| convert(reader, BufWriter::new(file), convert_options)) {
+        Ok(report) => report,
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+    };
+
+    let mut changes = Vec::new();
+    if report.invalid_sequences > 0 {
+        // The detected encoding was wrong somewhere past the sample; rewriting
+        // would lose data.
+        let _ = fs::remove_file(&temp);
+        println!(
+            "Leaving {} as is: {} invalid {} sequences",
+            path.display(),
+            report.invalid_sequences,
+            source
+        );
+        return Ok(NormalizationRecord::untouched(path, &detection));
+    }
+    if source != options.encoding {
+        changes.push(format!("Converted from {} to {}", source, options.encoding));
+    }
+    if report.bom_found {
+        changes.push("Removed byte order mark".to_string());
+    }
+    if report.newlines_converted > 0 {
+        let newline = match options.newline {
+            Newline::Lf => "LF",
+            Newline::Crlf => "CRLF",
+        };
+        changes.push(format!("Converted line endings to {} ({} lines)", newline, report.newlines_converted));
+    }
+    if report.unmappable_chars > 0 {
+        changes.push(format!(
+            "Replaced {} characters {} can't represent",
+            report.unmappable_chars, options.encoding
+        ));
+    }
+
+    if changes.is_empty() {
+        fs::remove_file(&temp)?;
+    } else {
+        // Keeps the permissions and times restored from the sender.
+        if let Err(e) = carry_over(path, &temp) {
+            println!("Couldn't keep the permissions and times of {}: {}", path.display(), e);
+        }
+        fs::rename(&temp, path)?;
+        println!("Normalized {}: {}", path.display(), changes.join("; "));
+    }
+    Ok(NormalizationRecord {
+        file: path.display().to_string(),
+        text: true,
+        source_encoding: Some(source),
+        confidence: detection.confidence,
+        target_encoding: Some(options.encoding),
+        line_endings_converted: report.newlines_converted,
+        changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::SAMPLE_SIZE;
+
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("unishare-normalize-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn lf() -> NormalizeOptions {
+        NormalizeOptions {
+            encoding: Encoding::Utf8,
+            newline: Newline::Lf,
+        }
+    }
+
+    #[test]
+    fn rewrites_legacy_text_as_utf8() {
+        let text = "Привет, как дела?\r\nСегодня хорошая погода, и мы пойдём гулять.\r\n";
+        let (bytes, _, _) = encoding_rs::WINDOWS_1251.encode(text);
+        let path = temp_file("cp1251.txt", &bytes);
+
+        let record = normalize_file(&path, lf()).unwrap();
+        assert_eq!(record.source_encoding, Some(Encoding::Windows1251));
+        assert_eq!(record.target_encoding, Some(Encoding::Utf8));
+        assert_eq!(record.line_endings_converted, 2);
+        assert_eq!(fs::read_to_string(&path).unwrap(), text.replace("\r\n", "\n"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn drops_the_bom_of_utf16_text() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("hello\nwörld\n".encode_utf16().flat_map(u16::to_le_bytes));
+        let path = temp_file("utf16.txt", &bytes);
+
+        let record = normalize_file(&path, lf()).unwrap();
+        assert_eq!(record.source_encoding, Some(Encoding::Utf16Le));
+        assert!(record.changes.iter().any(|change| change.contains("byte order mark")));
+        assert_eq!(fs::read(&path).unwrap(), "hello\nwörld\n".as_bytes());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn leaves_binary_and_uncertain_files_alone() {
+        for (name, contents) in [
+            ("binary.png", &b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR"[..]),
+            ("uncertain.txt", &b"price: 10\xA4\r\n"[..]),
+        ] {
+            let path = temp_file(name, contents);
+            let record = normalize_file(&path, lf()).unwrap();
+            assert_eq!(record.target_encoding, None, "{}", name);
+            assert!(record.changes.is_empty());
+            assert_eq!(fs::read(&path).unwrap(), contents);
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn leaves_files_with_invalid_sequences_past_the_sample_alone() {
+        let (line, _, _) = encoding_rs::SHIFT_JIS.encode("今日は良い天気ですね。ファイルを送ります。\r\n");
+        let mut bytes = line.repeat(SAMPLE_SIZE / line.len() + 1);
+        bytes.extend_from_slice(b"\xFF\xFF\r\n");
+        let path = temp_file("sjis.txt", &bytes);
+
+        let record = normalize_file(&path, lf()).unwrap();
+        assert_eq!(record.source_encoding, Some(Encoding::ShiftJis));
+        assert!(record.confidence >= MIN_CONFIDENCE);
+        assert_eq!(record.target_encoding, None);
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use serde::Serialize;
+
+use crate::image_converter::{ImageReport, ImageRules};
+use crate::normalize::{normalize_file, NormalizationRecord, NormalizeOptions};
+use crate::protocols::filename::{self, FilenamePolicy};
+use crate::protocols::protocol_manager::Received;
+
+/// Opt-in steps run on a file once it's been received.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PostReceive {
+    /// Convert text files to UTF-8 with this platform's line endings.
+    pub normalize_text: bool,
+    /// Convert images per [`ImageRules::from_env`].
+    pub convert_images: bool,
+}
+
+static STEPS: LazyLock<PostReceive> = LazyLock::new(PostReceive::from_env);
+
+/// The steps [`protocol_manager::start_receiver`](crate::protocols::protocol_manager::start_receiver)
+/// runs on every received file.
+pub fn steps() -> PostReceive {
+    *STEPS
+}
+
+impl PostReceive {
+    /// Reads `UNISHARE_NORMALIZE_TEXT` and `UNISHARE_CONVERT_IMAGES`
+    /// (`1`/`0`, `true`/`false`, `yes`/`no`); both are off by default.
+    pub fn from_env() -> Self {
+        let flag = |name| {
+            matches!(
+                std::env::var(name).unwrap_or_default().trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        };
+        PostReceive {
+            normalize_text: flag("UNISHARE_NORMALIZE_TEXT"),
+            convert_images: flag("UNISHARE_CONVERT_IMAGES"),
+        }
+    }
+}
+
+/// What a receive command did, including any post-receive steps.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferResult {
+    pub message: String,
+    /// Where the received file ended up, after any conversion.
+    pub file: Option<String>,
+    pub normalization: Option<NormalizationRecord>,
+    pub image_conversion: Option<ImageReport>,
+}
+
+impl From<Received> for TransferResult {
+    fn from(received: Received) -> Self {
+        TransferResult {
+            message: received.message,
+            file: Some(received.file.path.display().to_string()),
+            normalization: received.normalization,
+            image_conversion: received.image_conversion,
+        }
+    }
+}
+
+impl TransferResult {
+    pub fn new(message: String, file: Option<String>) -> Self {
+        TransferResult {
+            message,
+            file,
+            normalization: None,
+            image_conversion: None,
+        }
+    }
+
+    /// Runs `steps` on the received file. A failing step is logged and
+    /// doesn't fail the transfer.
+    pub async fn finish(self, steps: PostReceive) -> Self {
+        if self.file.is_none() || !(steps.normalize_text || steps.convert_images) {
+            return self;
+        }
+        let unchanged = self.clone();
+        tokio::task::spawn_blocking(move || self.run(steps, filename::policy()))
+            .await
+            .unwrap_or_else(|e| {
+                println!("Post-receive steps failed: {}", e);
+                unchanged
+            })
+    }
+
+    fn run(mut self, steps: PostReceive, policy: &FilenamePolicy) -> Self {
+        let Some(mut path) = self.file.as_ref().map(PathBuf::from) else {
+            return self;
+        };
+        if steps.convert_images {
+            match ImageRules::from_env().apply(policy, &path) {
+                Ok(Some(report)) => {
+                    path = PathBuf::from(&report.output);
+                    self.file = Some(report.output.clone());
+                    self.image_conversion = Some(report);
+                }
+                Ok(None) => {}
+                Err(e) => println!("Converting image {} failed: {}", path.display(), e),
+            }
+        }
+        // A converted image is binary; there's no text to normalize.
+        if steps.normalize_text && self.image_conversion.is_none() {
+            match normalize_file(&path, NormalizeOptions::default()) {
+                Ok(record) => self.normalization = Some(record),
+                Err(e) => println!("Normalizing {} failed: {}", path.display(), e),
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("unishare-post-receive-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn received(path: &Path) -> TransferResult {
+        TransferResult::new("Received".to_string(), Some(path.display().to_string()))
+    }
+
+    #[test]
+    fn runs_only_the_steps_asked_for() {
+        let dir = temp_dir("steps");
+        let policy = FilenamePolicy {
+            download_dir: dir.clone(),
+            ..FilenamePolicy::default()
+        };
+        let all = PostReceive {
+            normalize_text: true,
+            convert_images: true,
+        };
+
+        let image = dir.join("photo.webp");
+        image::RgbImage::new(2, 2).save_with_format(&image, image::ImageFormat::WebP).unwrap();
+        let result = received(&image).run(all, &policy);
+        let report = result.image_conversion.expect("converted");
+        assert_eq!(result.file, Some(report.output.clone()));
+        assert!(report.output.ends_with("photo.png"));
+        assert_eq!(result.normalization, None);
+
+        let text = dir.join("notes.txt");
+        fs::write(&text, b"caf\xE9 cr\xE8me br\xFBl\xE9e, d\xE9j\xE0 vu").unwrap();
+        let untouched = received(&text).run(PostReceive::default(), &policy);
+        assert_eq!(untouched.normalization, None);
+        assert_eq!(fs::read(&text).unwrap(), b"caf\xE9 cr\xE8me br\xFBl\xE9e, d\xE9j\xE0 vu");
+
+        let result = received(&text).run(all, &policy);
+        assert_eq!(result.image_conversion, None);
+        assert!(result.normalization.is_some_and(|record| !record.changes.is_empty()));
+        assert_eq!(fs::read_to_string(&text).unwrap(), "café crème brûlée, déjà vu");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rewritten_files_keep_the_senders_metadata() {
+        use crate::protocols::metadata::{FileMetadata, MetadataPolicy};
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("metadata");
+        let policy = FilenamePolicy {
+            download_dir: dir.clone(),
+            ..FilenamePolicy::default()
+        };
+        let all = PostReceive {
+            normalize_text: true,
+            convert_images: true,
+        };
+        let sent = FileMetadata {
+            modified: Some(1_600_000_000_000_000_000),
+            mode: Some(0o700),
+            ..FileMetadata::default()
+        };
+        let kept = |path: &str| {
+            let metadata = fs::metadata(path).unwrap();
+            let modified = metadata.modified().unwrap().duration_since(std::time::UNIX_EPOCH).unwrap();
+            (modified.as_secs(), metadata.permissions().mode() & 0o777)
+        };
+
+        let text = dir.join("notes.txt");
+        fs::write(&text, b"caf\xE9 cr\xE8me\r\n").unwrap();
+        MetadataPolicy::default().apply(&sent, &text);
+        let result = received(&text).run(all, &policy);
+        assert!(result.normalization.is_some_and(|record| !record.changes.is_empty()));
+        assert_eq!(kept(result.file.as_deref().unwrap()), (1_600_000_000, 0o700));
+
+        let image = dir.join("photo.webp");
+        image::RgbImage::new(2, 2).save_with_format(&image, image::ImageFormat::WebP).unwrap();
+        MetadataPolicy::default().apply(&sent, &image);
+        let result = received(&image).run(all, &policy);
+        assert!(result.image_conversion.is_some());
+        assert_eq!(kept(result.file.as_deref().unwrap()), (1_600_000_000, 0o700));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, oneshot};
+
+use crate::protocols::filename::ReceivedFile;
+
+/// How long an offer waits for an answer before it's rejected. Kept under the
+/// WebRTC sender's idle timeout, which runs while the receiver decides.
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// An incoming file waiting to be accepted or rejected; answer with
+/// [`ApprovalGate::answer`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Offer {
+    pub id: String,
+    pub name: String,
+    pub size: u64,
+    /// The sender's address.
+    pub from: String,
+    pub via: String,
+}
+
+/// What happens to incoming files while approval is required.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Incoming {
+    /// A sender wants to send a file.
+    Offer(Offer),
+    /// An accepted file was saved.
+    Done { id: String, file: ReceivedFile },
+    /// An accepted file didn't arrive.
+    Failed { id: String, error: String },
+}
+
+/// Holds incoming files until someone accepts them.
+///
+/// Receivers check every file a sender pushes at them. Until
+/// [`ApprovalGate::set_required`] turns approval on, every file is accepted
+/// as before; the daemon turns it on and answers offers for its clients.
+pub struct ApprovalGate {
+    required: AtomicBool,
+    timeout: Duration,
+    pending: Mutex<HashMap<String, oneshot::Sender<bool>>>,
+    events: broadcast::Sender<Incoming>,
+}
+
+static GATE: LazyLock<ApprovalGate> = LazyLock::new(ApprovalGate::new);
+
+/// The gate every receiver checks.
+pub fn gate() -> &'static ApprovalGate {
+    &GATE
+}
+
+/// Permission to write one accepted file. Report the saved file with
+/// [`Admission::done`]; dropping it unused reports the transfer as failed.
+#[must_use]
+pub struct Admission {
+    /// `None` when approval wasn't required, so there's nobody to tell.
+    id: Option<String>,
+    events: broadcast::Sender<Incoming>,
+}
+
+impl Admission {
+    pub fn done(mut self, file: &ReceivedFile) {
+        if let Some(id) = self.id.take() {
+            let _ = self.events.send(Incoming::Done { id, file: file.clone() });
+        }
+    }
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            let error = "The transfer ended before the file was saved.".to_string();
+            let _ = self.events.send(Incoming::Failed { id, error });
+        }
+    }
+}
+
+impl ApprovalGate {
+    pub fn new() -> Self {
+        ApprovalGate {
+            required: AtomicBool::new(false),
+            timeout: APPROVAL_TIMEOUT,
+            pending: Mutex::new(HashMap::new()),
+            events: broadcast::channel(16).0,
+        }
+    }
+
+    /// Whether incoming files wait for an answer.
+    pub fn set_required(&self, required: bool) {
+        self.required.store(required, Ordering::SeqCst);
+    }
+
+    /// How long an offer waits for an answer.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Receives every offer and outcome from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Incoming> {
+        self.events.subscribe()
+    }
+
+    /// Decides whether `size` bytes of `name` from `from` may be written.
+    ///
+    /// - Without approval turned on, everything passes.
+    /// - Otherwise the offer goes to subscribers and waits for an answer; no
+    ///   answer in time, or nobody to ask, counts as a rejection.
+    pub async fn admit(&self, name: &str, size: u64, from: &str, via: &str) -> Result<Admission, String> {
+        if !self.required.load(Ordering::SeqCst) {
+            return Ok(Admission {
+                id: None,
+                events: self.events.clone(),
+            });
+        }
+
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        let id = format!("incoming-{}", NEXT.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id.clone(), tx);
+        let offer = Offer {
+            id: id.clone(),
+            name: name.to_string(),
+            size,
+            from: from.to_string(),
+            via: via.to_string(),
+        };
+        println!("📥 Asking whether to accept {} ({} bytes) from {}", name, size, from);
+        if self.events.send(Incoming::Offer(offer)).is_err() {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(format!("Nobody to ask whether to accept {}.", name));
+        }
+
+        let answer = tokio::time::timeout(self.timeout, rx).await;
+        self.pending.lock().unwrap().remove(&id);
+        match answer {
+            Ok(Ok(true)) => Ok(Admission {
+                id: Some(id),
+                events: self.events.clone(),
+            }),
+            Ok(_) => Err(format!("Rejected {} from {}.", name, from)),
+            Err(_) => Err(format!("No answer whether to accept {} from {}.", name, from)),
+        }
+    }
+
+    /// Answers an [`Offer`].
+    pub fn answer(&self, id: &str, accept: bool) -> Result<(), String> {
+        let tx = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(id)
+            .ok_or_else(|| format!("No pending offer {}", id))?;
+        let _ = tx.send(accept);
+        Ok(())
+    }
+}
+
+impl Default for ApprovalGate {
+    fn default() -> Self {
+        ApprovalGate::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn gate() -> &'static ApprovalGate {
+        let mut gate = ApprovalGate::new();
+        gate.timeout = Duration::from_millis(200);
+        gate.set_required(true);
+        Box::leak(Box::new(gate))
+    }
+
+    /// Answers the next offer with `accept`.
+    fn answer_next(gate: &'static ApprovalGate, accept: bool) -> tokio::task::JoinHandle<Offer> {
+        let mut events = gate.subscribe();
+        tokio::spawn(async move {
+            let Incoming::Offer(offer) = events.recv().await.unwrap() else {
+                panic!("expected an offer");
+            };
+            gate.answer(&offer.id, accept).unwrap();
+            offer
+        })
+    }
+
+    #[tokio::test]
+    async fn everything_passes_until_approval_is_required() {
+        let gate = ApprovalGate::new();
+        let mut events = gate.subscribe();
+        drop(gate.admit("photo.jpg", 10, "10.0.0.2", "webrtc").await.unwrap());
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn offers_wait_for_an_answer() {
+        let gate = gate();
+        let answered = answer_next(gate, false);
+        let err = gate.admit("photo.jpg", 10, "10.0.0.2", "webrtc").await.err().unwrap();
+        assert!(err.contains("Rejected"), "{}", err);
+        let offer = answered.await.unwrap();
+        assert_eq!((offer.name.as_str(), offer.size, offer.via.as_str()), ("photo.jpg", 10, "webrtc"));
+
+        let answered = answer_next(gate, true);
+        let admission = gate.admit("photo.jpg", 10, "10.0.0.2", "webrtc").await.unwrap();
+        let offer = answered.await.unwrap();
+        let mut events = gate.subscribe();
+        let file = ReceivedFile {
+            original_name: "photo.jpg".to_string(),
+            path: PathBuf::from("/tmp/photo.jpg"),
+            renamed: false,
+        };
+        admission.done(&file);
+        assert_eq!(events.recv().await.unwrap(), Incoming::Done { id: offer.id, file });
+        assert!(gate.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn dropped_admissions_report_failure() {
+        let gate = gate();
+        let answered = answer_next(gate, true);
+        let admission = gate.admit("photo.jpg", 10, "10.0.0.2", "webrtc").await.unwrap();
+        let offer = answered.await.unwrap();
+        let mut events = gate.subscribe();
+        drop(admission);
+        assert!(matches!(events.recv().await.unwrap(), Incoming::Failed { id, .. } if id == offer.id));
+    }
+
+    #[tokio::test]
+    async fn unanswered_offers_are_rejected() {
+        let gate = gate();
+        let err = gate.admit("photo.jpg", 10, "10.0.0.2", "webrtc").await.err().unwrap();
+        assert!(err.contains("Nobody to ask"), "{}", err);
+
+        let mut events = gate.subscribe();
+        let err = gate.admit("photo.jpg", 10, "10.0.0.2", "webrtc").await.err().unwrap();
+        assert!(err.contains("No answer"), "{}", err);
+        let Incoming::Offer(offer) = events.recv().await.unwrap() else {
+            panic!("expected an offer");
+        };
+        assert!(gate.answer(&offer.id, true).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use std::error::Error;
+use tokio::net::{TcpListener, TcpStream};
+use std::path::Path;
+use crate::protocols::{approval, capabilities, filename, metadata};
+use crate::protocols::filename::ReceivedFile;
+use crate::protocols::framing::StreamHeader;
+use crate::tools::connectivity;
+
+/// Whether a Bluetooth adapter is on (see [`connectivity::check_bluetooth`]).
+/// The check reads sysfs or runs a platform tool, so it runs on the
+/// blocking pool.
+pub async fn is_available() -> bool {
+    tokio::task::spawn_blocking(|| connectivity::check_bluetooth().unwrap_or(false))
+        .await
+        .unwrap_or(false)
+}
+
+
+pub async fn send_file(file_path: &str, destination: &str) -> Result<(), Box<dyn Error>> {
+    println!("🔵 (BT) Connecting to {} on port 9001...", destination);
+
+
+    let header = StreamHeader::for_file(Path::new(file_path))?;
+    
+  
+    let dest_addr = format!("{}:9001", destination);
+    let mut stream = TcpStream::connect(dest_addr).await?;
+    println!("🔵 (BT) Connected. Sending file data...");
+    
+
+    header.write(&mut stream).await?;
+    
+  
+    header.write_file(&mut stream, Path::new(file_path)).await?;
+    println!("✅ (BT) File sent successfully.");
+    Ok(())
+}
+
+
+/// Receives one file and returns where it was saved.
+pub async fn start_receiver() -> Result<ReceivedFile, Box<dyn Error>> {
+    let listener = TcpListener::bind("0.0.0.0:9001").await?;
+    println!("📡 (BT) Bluetooth Receiver listening on port 9001...");
+    let _receiving = capabilities::mark_receiving("bluetooth");
+    
+
+    let (mut socket, addr) = listener.accept().await?;
+    println!("📡 (BT) Received connection from {}", addr);
+    
+
+    let header = StreamHeader::read(&mut socket).await?;
+    println!("📡 (BT) Expecting '{}', {} bytes.", header.name, header.size);
+    let admission = approval::gate().admit(&header.name, header.size, &addr.ip().to_string(), "bluetooth").await?;
+    
+
+    let received = filename::policy().reserve(&header.name)?;
+    header.read_file(&mut socket, &received.path).await?;
+    metadata::policy().apply(&header.metadata, &received.path);
+    
+    println!("✅ (BT) File received and saved as {}", received.path.display());
+    admission.done(&received);
+    Ok(received)
+}
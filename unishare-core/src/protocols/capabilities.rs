@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::time::{timeout, Duration};
+
+use crate::device_discovery::local_device;
+
+/// Port every instance answers capability queries on.
+pub const HELLO_PORT: u16 = 9004;
+const HELLO_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_HELLO_LEN: u64 = 16 * 1024;
+
+/// Transports whose receivers are listening right now, with a count so
+/// overlapping receivers of one kind are tracked correctly.
+static RUNNING: Mutex<BTreeMap<&'static str, usize>> = Mutex::new(BTreeMap::new());
+
+/// A peer's answer to a capability query.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Hello {
+    pub id: String,
+    pub name: String,
+    /// Transports the peer supports at all.
+    pub transports: Vec<String>,
+    /// Transports the peer has a receiver listening on right now.
+    pub receiving: Vec<String>,
+}
+
+impl Hello {
+    pub fn is_receiving(&self, transport: &str) -> bool {
+        self.receiving.iter().any(|t| t == transport)
+    }
+}
+
+/// Marks a transport's receiver as listening until dropped.
+pub struct ReceiverGuard(&'static str);
+
+impl Drop for ReceiverGuard {
+    fn drop(&mut self) {
+        let mut running = RUNNING.lock().unwrap();
+        if let Some(count) = running.get_mut(self.0) {
+            *count -= 1;
+            if *count == 0 {
+                running.remove(self.0);
+            }
+        }
+    }
+}
+
+/// Call once a receiver is bound; capability queries report the transport as
+/// receiving for as long as the guard lives.
+pub fn mark_receiving(transport: &'static str) -> ReceiverGuard {
+    *RUNNING.lock().unwrap().entry(transport).or_insert(0) += 1;
+    ReceiverGuard(transport)
+}
+
+pub fn local_hello() -> Hello {
+    let local = local_device();
+    Hello {
+        id: local.id.clone(),
+        name: local.name.clone(),
+        transports: local.transports.clone(),
+        receiving: RUNNING.lock().unwrap().keys().map(|t| t.to_string()).collect(),
+    }
+}
+
+/// Answers capability queries on [`HELLO_PORT`]: each connection gets our
+/// [`Hello`] as one line of JSON and is closed.
+pub async fn serve_hello() -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(("0.0.0.0", HELLO_PORT)).await?;
+    println!("Answering capability queries on port {}", HELLO_PORT);
+    answer(listener).await
+}
+
+async fn answer(listener: TcpListener) -> Result<(), Box<dyn Error>> {
+    loop {
+        let (mut socket, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            let mut line = serde_json::to_vec(&local_hello()).expect("hello serializes");
+            line.push(b'\n');
+            let _ = socket.write_all(&line).await;
+            let _ = socket.shutdown().await;
+        });
+    }
+}
+
+/// Asks the instance at `destination` what it can receive on. Returns `None`
+/// if nothing answers in time, e.g. an older version without the hello
+/// service.
+pub async fn query(destination: &str) -> Option<Hello> {
+    ask((destination, HELLO_PORT)).await
+}
+
+async fn ask(address: impl ToSocketAddrs) -> Option<Hello> {
+    let exchange = async {
+        let stream = TcpStream::connect(address).await.ok()?;
+        let mut reply = Vec::new();
+        stream.take(MAX_HELLO_LEN).read_to_end(&mut reply).await.ok()?;
+        serde_json::from_slice::<Hello>(&reply).ok()
+    };
+    timeout(HELLO_TIMEOUT, exchange).await.ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receiving(transport: &str) -> bool {
+        local_hello().is_receiving(transport)
+    }
+
+    #[test]
+    fn hellos_serialize_in_camel_case() {
+        let hello = Hello {
+            id: "a1".to_string(),
+            name: "Laptop".to_string(),
+            transports: vec!["webrtc".to_string(), "bluetooth".to_string()],
+            receiving: vec!["webrtc".to_string()],
+        };
+        let json = serde_json::to_value(&hello).unwrap();
+        assert_eq!(json["receiving"], serde_json::json!(["webrtc"]));
+        assert_eq!(serde_json::from_value::<Hello>(json).unwrap(), hello);
+        assert!(hello.is_receiving("webrtc"));
+        assert!(!hello.is_receiving("bluetooth"));
+    }
+
+    #[test]
+    fn receivers_are_counted() {
+        let first = mark_receiving("test-counted");
+        let second = mark_receiving("test-counted");
+        assert!(receiving("test-counted"));
+        drop(first);
+        assert!(receiving("test-counted"));
+        drop(second);
+        assert!(!receiving("test-counted"));
+    }
+
+    #[test]
+    fn saves_the_device_id_outside_the_users_config() {
+        let id = &local_device().id;
+        let saved = crate::device_discovery::config_dir().join("device-id");
+        assert!(saved.starts_with(std::env::temp_dir()));
+        assert_eq!(std::fs::read_to_string(saved).unwrap().trim(), id);
+    }
+
+    #[tokio::test]
+    async fn answers_hellos_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = answer(listener).await.map_err(|e| e.to_string());
+        });
+
+        let guard = mark_receiving("test-loopback");
+        let hello = ask(address).await.expect("hello");
+        assert_eq!(hello.id, local_device().id);
+        assert!(hello.is_receiving("test-loopback"));
+
+        drop(guard);
+        assert!(!ask(address).await.expect("hello").is_receiving("test-loopback"));
+
+        // Nothing listening: no answer rather than an error.
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        assert_eq!(ask(closed).await, None);
+    }
+}
@@ -0,0 +1,539 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest file or directory name common filesystems accept, in bytes.
+const MAX_COMPONENT_BYTES: usize = 255;
+/// Longest relative path created under the download directory, in bytes.
+const MAX_PATH_BYTES: usize = 1024;
+/// Characters Windows doesn't allow in names.
+const INVALID_CHARS: [char; 7] = ['<', '>', ':', '"', '|', '?', '*'];
+/// Extensions longer than this aren't kept when a name is shortened.
+const MAX_EXTENSION_BYTES: usize = 16;
+/// How many ` (n)` suffixes to try before giving up on a name.
+const MAX_COLLISIONS: u32 = 10_000;
+
+/// Why a sender-supplied name was refused.
+#[derive(Debug, PartialEq)]
+pub enum FilenameError {
+    Empty,
+    /// `/etc/passwd`, `C:\Windows`, `\\server\share`, ...
+    Absolute(String),
+    /// A `..` component, or a directory that resolves outside the download
+    /// directory.
+    Traversal(String),
+    TooLong(String),
+}
+
+impl fmt::Display for FilenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilenameError::Empty => write!(f, "Empty file name"),
+            FilenameError::Absolute(name) => write!(f, "Refusing absolute path '{}'", name),
+            FilenameError::Traversal(name) => write!(f, "Refusing path '{}' outside the download directory", name),
+            FilenameError::TooLong(name) => write!(f, "Path '{}' is longer than {} bytes", name, MAX_PATH_BYTES),
+        }
+    }
+}
+
+impl Error for FilenameError {}
+
+/// The Unicode normalization form received names are stored in.
+///
+/// macOS sends names decomposed (NFD), while Linux and Windows tools expect
+/// them composed (NFC); the two look the same but are different bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnicodeForm {
+    #[default]
+    Nfc,
+    Nfd,
+    /// Also folds compatibility characters such as full-width letters.
+    Nfkc,
+    Nfkd,
+    /// Keep names exactly as sent.
+    Unchanged,
+}
+
+impl UnicodeForm {
+    pub fn apply(self, name: &str) -> String {
+        match self {
+            UnicodeForm::Nfc => name.nfc().collect(),
+            UnicodeForm::Nfd => name.nfd().collect(),
+            UnicodeForm::Nfkc => name.nfkc().collect(),
+            UnicodeForm::Nfkd => name.nfkd().collect(),
+            UnicodeForm::Unchanged => name.to_string(),
+        }
+    }
+}
+
+impl FromStr for UnicodeForm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "nfc" => Ok(UnicodeForm::Nfc),
+            "nfd" => Ok(UnicodeForm::Nfd),
+            "nfkc" => Ok(UnicodeForm::Nfkc),
+            "nfkd" => Ok(UnicodeForm::Nfkd),
+            "none" | "off" | "unchanged" => Ok(UnicodeForm::Unchanged),
+            other => Err(format!("Unknown Unicode normalization form '{}'", other)),
+        }
+    }
+}
+
+/// Names that differ only in how accented characters are encoded are the
+/// same name to a user, whatever form they're stored in.
+fn collision_key(name: &OsStr) -> String {
+    name.to_string_lossy().nfc().collect()
+}
+
+/// A sender-supplied name made safe to create under the download directory
+/// on any platform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SafeName {
+    pub original: String,
+    /// Relative, with no `.` or `..` components.
+    pub relative: PathBuf,
+    /// Whether `relative` differs from what the sender asked for, beyond
+    /// Unicode normalization.
+    pub changed: bool,
+}
+
+/// Checks and cleans a name received from a peer.
+///
+/// - The whole path is first normalized to `form`, so compatibility
+///   characters that fold into `/` or `.` can't slip past the checks below.
+/// - Absolute paths and `..` components are refused outright.
+/// - Both `/` and `\` separate directories, whichever platform sent it.
+/// - Characters Windows forbids and control characters become `_`, and
+///   trailing dots and spaces are dropped.
+/// - Reserved Windows device names (`CON`, `NUL`, `COM1`, ...) get a `_` prefix.
+/// - Over-long names are shortened, keeping the extension; over-long paths
+///   are refused.
+pub fn sanitize(original: &str, form: UnicodeForm) -> Result<SafeName, FilenameError> {
+    let normalized = form.apply(original);
+    let bytes = normalized.as_bytes();
+    let drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    if normalized.starts_with(['/', '\\']) || drive {
+        return Err(FilenameError::Absolute(original.to_string()));
+    }
+
+    let mut components = Vec::new();
+    for component in normalized.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." => return Err(FilenameError::Traversal(original.to_string())),
+            component => components.push(sanitize_component(component)),
+        }
+    }
+    if components.is_empty() {
+        return Err(FilenameError::Empty);
+    }
+    let joined = components.join("/");
+    if joined.len() > MAX_PATH_BYTES {
+        return Err(FilenameError::TooLong(original.to_string()));
+    }
+    Ok(SafeName {
+        original: original.to_string(),
+        changed: joined != normalized,
+        relative: components.iter().collect(),
+    })
+}
+
+fn sanitize_component(component: &str) -> String {
+    let mapped: String = component
+        .chars()
+        .map(|c| if c.is_control() || INVALID_CHARS.contains(&c) { '_' } else { c })
+        .collect();
+    // Windows silently drops these, so `a.` and `a` would collide there.
+    let mut name = mapped.trim_end_matches(['.', ' ']).to_string();
+    if name.is_empty() {
+        name = "_".to_string();
+    }
+    if is_reserved(&name) {
+        name.insert(0, '_');
+    }
+    truncate(name, MAX_COMPONENT_BYTES)
+}
+
+/// Windows device names are reserved with any extension, in any case.
+fn is_reserved(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default().trim_end().to_ascii_uppercase();
+    match stem.as_str() {
+        "CON" | "PRN" | "AUX" | "NUL" => true,
+        _ => matches!(stem.as_bytes(), [b'C', b'O', b'M' | b'L', b'1'..=b'9'] | [b'L', b'P', b'T', b'1'..=b'9']),
+    }
+}
+
+/// Shortens `name` to at most `max` bytes on a character boundary, keeping a
+/// short extension.
+fn truncate(name: String, max: usize) -> String {
+    if name.len() <= max {
+        return name;
+    }
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 && name.len() - dot <= MAX_EXTENSION_BYTES => name.split_at(dot),
+        _ => (name.as_str(), ""),
+    };
+    let mut end = max - extension.len();
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &stem[..end], extension)
+}
+
+/// Where a received file was saved, and what the sender called it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceivedFile {
+    pub original_name: String,
+    pub path: PathBuf,
+    /// Saved under a different name than the sender's, either because it
+    /// wasn't safe or because the name was taken.
+    pub renamed: bool,
+}
+
+/// Decides where received files go.
+#[derive(Clone, Debug)]
+pub struct FilenamePolicy {
+    pub download_dir: PathBuf,
+    pub unicode_form: UnicodeForm,
+}
+
+impl Default for FilenamePolicy {
+    /// Receivers have always saved to the working directory.
+    fn default() -> Self {
+        FilenamePolicy {
+            download_dir: PathBuf::from("."),
+            unicode_form: UnicodeForm::default(),
+        }
+    }
+}
+
+static POLICY: LazyLock<FilenamePolicy> = LazyLock::new(FilenamePolicy::from_env);
+
+/// The policy every receiver uses.
+pub fn policy() -> &'static FilenamePolicy {
+    &POLICY
+}
+
+impl FilenamePolicy {
+    /// Reads `UNISHARE_DOWNLOAD_DIR` and `UNISHARE_FILENAME_FORM` (`nfc`,
+    /// `nfd`, `nfkc`, `nfkd` or `none`).
+    pub fn from_env() -> Self {
+        let mut policy = FilenamePolicy::default();
+        if let Ok(dir) = std::env::var("UNISHARE_DOWNLOAD_DIR") {
+            if !dir.is_empty() {
+                policy.download_dir = PathBuf::from(dir);
+            }
+        }
+        if let Ok(form) = std::env::var("UNISHARE_FILENAME_FORM") {
+            match form.parse() {
+                Ok(form) => policy.unicode_form = form,
+                Err(e) => println!("Ignoring UNISHARE_FILENAME_FORM: {}", e),
+            }
+        }
+        policy
+    }
+
+    /// Claims a path for `original` under the download directory by creating
+    /// an empty file there, so concurrent receivers can't pick the same one.
+    ///
+    /// - The name goes through [`sanitize`] first.
+    /// - An existing directory whose name is canonically equivalent to one in
+    ///   the path is reused rather than created alongside it.
+    /// - Missing directories are created, and must not lead outside the
+    ///   download directory through symlinks.
+    /// - A name that's taken, including by a file whose name only differs in
+    ///   Unicode normalization, gets a ` (1)`, ` (2)`, ... suffix before its
+    ///   extension.
+    pub fn reserve(&self, original: &str) -> Result<ReceivedFile, Box<dyn Error>> {
+        let safe = sanitize(original, self.unicode_form)?;
+        let components: Vec<&OsStr> = safe.relative.iter().collect();
+        let Some((file_name, dirs)) = components.split_last() else {
+            return Err(FilenameError::Empty.into());
+        };
+        fs::create_dir_all(&self.download_dir)?;
+        let root = self.download_dir.canonicalize()?;
+        // One component at a time, each checked before anything is created
+        // under it, so a symlinked directory can't make us create directories
+        // outside the download directory.
+        let mut parent = self.download_dir.clone();
+        for dir in dirs {
+            match existing_dir(&parent, dir) {
+                Some(existing) => parent.push(existing),
+                None => {
+                    parent.push(dir);
+                    match fs::create_dir(&parent) {
+                        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e.into()),
+                        _ => {}
+                    }
+                }
+            }
+            if !parent.canonicalize()?.starts_with(&root) {
+                return Err(FilenameError::Traversal(original.to_string()).into());
+            }
+        }
+        let taken: HashSet<String> = fs::read_dir(&parent)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| collision_key(&entry.file_name()))
+            .collect();
+
+        let file_name = Path::new(file_name);
+        for attempt in 0..MAX_COLLISIONS {
+            let name = if attempt == 0 { file_name.to_path_buf() } else { numbered(file_name, attempt) };
+            if taken.contains(&collision_key(name.as_os_str())) {
+                continue;
+            }
+            let path = parent.join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => {
+                    let renamed = safe.changed || attempt > 0;
+                    if renamed {
+                        println!("Saving '{}' as {}", original, path.display());
+                    }
+                    return Ok(ReceivedFile {
+                        original_name: original.to_string(),
+                        path,
+                        renamed,
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(format!("No free name for '{}' in {}", original, self.download_dir.display()).into())
+    }
+}
+
+/// The entry in `parent` that is the directory `name`, written in any
+/// Unicode normalization form.
+fn existing_dir(parent: &Path, name: &OsStr) -> Option<OsString> {
+    let key = collision_key(name);
+    fs::read_dir(parent)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| collision_key(&entry.file_name()) == key && entry.path().is_dir())
+        .map(|entry| entry.file_name())
+}
+
+/// `report.pdf` becomes `report (2).pdf`. The stem is shortened if the
+/// suffix would make the name longer than [`MAX_COMPONENT_BYTES`].
+fn numbered(path: &Path, n: u32) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let suffix = format!(" ({}){}", n, extension);
+    let mut end = stem.len().min(MAX_COMPONENT_BYTES.saturating_sub(suffix.len()));
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    path.with_file_name(format!("{}{}", &stem[..end], suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(name: &str) -> String {
+        sanitize(name, UnicodeForm::Nfc).unwrap().relative.to_string_lossy().into_owned()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("unishare-filename-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn refuses_absolute_and_traversal_paths() {
+        for name in ["/etc/passwd", "\\Windows\\win.ini", "C:\\boot.ini", "c:evil", "\\\\server\\share\\x"] {
+            assert!(matches!(sanitize(name, UnicodeForm::Nfc), Err(FilenameError::Absolute(_))), "{}", name);
+        }
+        for name in ["../../.bashrc", "docs/../../x", "a\\..\\..\\b", ".."] {
+            assert!(matches!(sanitize(name, UnicodeForm::Nfc), Err(FilenameError::Traversal(_))), "{}", name);
+        }
+        assert_eq!(sanitize("", UnicodeForm::Nfc), Err(FilenameError::Empty));
+        assert_eq!(sanitize("./.", UnicodeForm::Nfc), Err(FilenameError::Empty));
+    }
+
+    #[test]
+    fn keeps_ordinary_names() {
+        let safe = sanitize("report.pdf", UnicodeForm::Nfc).unwrap();
+        assert_eq!(safe.relative, PathBuf::from("report.pdf"));
+        assert!(!safe.changed);
+        assert_eq!(clean(".bashrc"), ".bashrc");
+        assert_eq!(clean("photos/2024/beach.jpg"), "photos/2024/beach.jpg");
+        assert_eq!(clean("Grüße 日本.txt"), "Grüße 日本.txt");
+    }
+
+    #[test]
+    fn maps_windows_hostile_names() {
+        assert_eq!(clean("what?.txt"), "what_.txt");
+        assert_eq!(clean("12:30 <draft>|\"x\"*.md"), "12_30 _draft___x__.md");
+        assert_eq!(clean("tab\there\u{7}.txt"), "tab_here_.txt");
+        assert_eq!(clean("trailing. . "), "trailing");
+        assert_eq!(clean("..."), "_");
+        assert_eq!(clean("docs\\notes.txt"), "docs/notes.txt");
+        assert!(sanitize("docs\\notes.txt", UnicodeForm::Nfc).unwrap().changed);
+    }
+
+    #[test]
+    fn prefixes_reserved_device_names() {
+        assert_eq!(clean("CON.txt"), "_CON.txt");
+        assert_eq!(clean("nul"), "_nul");
+        assert_eq!(clean("com1.tar.gz"), "_com1.tar.gz");
+        assert_eq!(clean("LPT9"), "_LPT9");
+        assert_eq!(clean("dir/aux/file"), "dir/_aux/file");
+        assert_eq!(clean("COM10.txt"), "COM10.txt");
+        assert_eq!(clean("CONSOLE.txt"), "CONSOLE.txt");
+    }
+
+    #[test]
+    fn caps_lengths() {
+        let long = format!("{}.txt", "é".repeat(200));
+        let cleaned = clean(&long);
+        assert!(cleaned.len() <= MAX_COMPONENT_BYTES);
+        assert!(cleaned.ends_with("é.txt"));
+
+        let deep = vec!["directory"; 150].join("/");
+        assert!(matches!(sanitize(&deep, UnicodeForm::Nfc), Err(FilenameError::TooLong(_))));
+    }
+
+    #[test]
+    fn reserves_unique_paths_inside_the_download_dir() {
+        let dir = temp_dir("reserve");
+        let policy = FilenamePolicy {
+            download_dir: dir.clone(),
+            ..FilenamePolicy::default()
+        };
+
+        let first = policy.reserve("report.pdf").unwrap();
+        assert_eq!(first.path, dir.join("report.pdf"));
+        assert!(!first.renamed);
+        let second = policy.reserve("report.pdf").unwrap();
+        assert_eq!(second.path, dir.join("report (1).pdf"));
+        assert!(second.renamed);
+        assert_eq!(second.original_name, "report.pdf");
+
+        let nested = policy.reserve("a/b/CON").unwrap();
+        assert_eq!(nested.path, dir.join("a/b/_CON"));
+        assert!(policy.reserve("../escape").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn numbered_names_stay_within_the_length_limit() {
+        let dir = temp_dir("numbered-length");
+        let policy = FilenamePolicy {
+            download_dir: dir.clone(),
+            ..FilenamePolicy::default()
+        };
+        let long = format!("{}.txt", "é".repeat(200));
+
+        let first = policy.reserve(&long).unwrap();
+        let second = policy.reserve(&long).unwrap();
+        let name = second.path.file_name().unwrap().to_str().unwrap();
+        assert_ne!(first.path, second.path);
+        assert!(name.len() <= MAX_COMPONENT_BYTES, "{} bytes", name.len());
+        assert!(name.ends_with("é (1).txt"), "{}", name);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn normalizes_unicode_forms() {
+        let decomposed = "Cafe\u{301}/re\u{301}sume\u{301}.txt";
+        let safe = sanitize(decomposed, UnicodeForm::Nfc).unwrap();
+        assert_eq!(safe.relative, PathBuf::from("Caf\u{e9}/r\u{e9}sum\u{e9}.txt"));
+        assert!(!safe.changed);
+        assert_eq!(
+            sanitize("Caf\u{e9}", UnicodeForm::Nfd).unwrap().relative,
+            PathBuf::from("Cafe\u{301}")
+        );
+        assert_eq!(
+            sanitize(decomposed, UnicodeForm::Unchanged).unwrap().relative,
+            PathBuf::from(decomposed)
+        );
+        // A full-width solidus and one-dot leaders fold into `/` and `..`.
+        assert_eq!(
+            sanitize("a\u{ff0f}b", UnicodeForm::Nfkc).unwrap().relative,
+            PathBuf::from("a/b")
+        );
+        assert!(matches!(
+            sanitize("\u{2024}\u{2024}/x", UnicodeForm::Nfkc),
+            Err(FilenameError::Traversal(_))
+        ));
+    }
+
+    #[test]
+    fn detects_collisions_across_normalization_forms() {
+        let dir = temp_dir("unicode");
+        fs::create_dir(dir.join("Cafe\u{301}")).unwrap();
+        fs::write(dir.join("Cafe\u{301}/menu.txt"), b"").unwrap();
+        fs::write(dir.join("nai\u{308}ve.txt"), b"").unwrap();
+        let policy = FilenamePolicy {
+            download_dir: dir.clone(),
+            ..FilenamePolicy::default()
+        };
+
+        let file = policy.reserve("na\u{ef}ve.txt").unwrap();
+        assert_eq!(file.path, dir.join("na\u{ef}ve (1).txt"));
+        assert!(file.renamed);
+
+        // The existing decomposed directory is reused, not duplicated.
+        let nested = policy.reserve("Caf\u{e9}/menu.txt").unwrap();
+        assert_eq!(nested.path, dir.join("Cafe\u{301}/menu (1).txt"));
+        assert!(!dir.join("Caf\u{e9}").exists());
+
+        let unchanged = FilenamePolicy {
+            unicode_form: UnicodeForm::Unchanged,
+            ..policy.clone()
+        };
+        let kept = unchanged.reserve("na\u{ef}ve.txt").unwrap();
+        assert_eq!(kept.path, dir.join("na\u{ef}ve (2).txt"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn parses_unicode_forms() {
+        assert_eq!("NFC".parse(), Ok(UnicodeForm::Nfc));
+        assert_eq!("nfkd".parse(), Ok(UnicodeForm::Nfkd));
+        assert_eq!("none".parse(), Ok(UnicodeForm::Unchanged));
+        assert!("nfx".parse::<UnicodeForm>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_directories_symlinked_outside() {
+        let dir = temp_dir("symlink");
+        let outside = temp_dir("symlink-outside");
+        std::os::unix::fs::symlink(&outside, dir.join("link")).unwrap();
+        let policy = FilenamePolicy {
+            download_dir: dir.clone(),
+            ..FilenamePolicy::default()
+        };
+
+        let err = policy.reserve("link/file.txt").unwrap_err();
+        assert!(err.to_string().contains("outside"), "{}", err);
+        assert!(!outside.join("file.txt").exists());
+
+        // Nothing may be created beyond the link either.
+        let err = policy.reserve("link/sub/deeper/file.txt").unwrap_err();
+        assert!(err.to_string().contains("outside"), "{}", err);
+        assert!(!outside.join("sub").exists());
+        fs::remove_dir_all(dir).unwrap();
+        fs::remove_dir_all(outside).unwrap();
+    }
+}
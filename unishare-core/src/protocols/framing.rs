@@ -0,0 +1,205 @@
+//! The header the TCP transports (Wi‑Fi Direct and Bluetooth) send before a
+//! file: its length as 4 big‑endian bytes, then the header as JSON. The
+//! manual WebRTC path sends the same header as a text message.
+
+use std::error::Error;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::protocols::metadata::{self, FileMetadata};
+
+/// Headers longer than this are refused. Extended attributes are capped well
+/// below it, even base64-encoded.
+const MAX_HEADER_BYTES: u32 = 64 * 1024;
+
+/// Files announced as larger than this are refused, unless
+/// `UNISHARE_MAX_FILE_BYTES` says otherwise.
+const DEFAULT_MAX_FILE_BYTES: u64 = 64 * 1024 * 1024 * 1024;
+
+static MAX_FILE_BYTES: LazyLock<u64> = LazyLock::new(|| {
+    std::env::var("UNISHARE_MAX_FILE_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_FILE_BYTES)
+});
+
+/// The largest file receivers accept.
+pub fn max_file_bytes() -> u64 {
+    *MAX_FILE_BYTES
+}
+
+/// What comes before the file data.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamHeader {
+    /// The file name as the sender has it; receivers pass it through
+    /// [`filename::policy`](crate::protocols::filename::policy).
+    pub name: String,
+    pub size: u64,
+    #[serde(default)]
+    pub metadata: FileMetadata,
+}
+
+impl StreamHeader {
+    /// The header for sending `path`, with the metadata
+    /// [`metadata::policy`] keeps.
+    pub fn for_file(path: &Path) -> std::io::Result<Self> {
+        Ok(StreamHeader {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "file".to_string()),
+            size: std::fs::metadata(path)?.len(),
+            metadata: metadata::policy().read(path)?,
+        })
+    }
+
+    pub async fn write<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        let json = serde_json::to_vec(self)?;
+        if json.len() > MAX_HEADER_BYTES as usize {
+            return Err(format!("File header is {} bytes, more than {}", json.len(), MAX_HEADER_BYTES).into());
+        }
+        writer.write_all(&(json.len() as u32).to_be_bytes()).await?;
+        writer.write_all(&json).await?;
+        Ok(())
+    }
+
+    pub async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let mut len_buf = [0u8; 4];
+        reader.read_exact(&mut len_buf).await?;
+        let len = u32::from_be_bytes(len_buf);
+        if len > MAX_HEADER_BYTES {
+            return Err(format!("Refusing a {} byte file header", len).into());
+        }
+        let mut json = vec![0u8; len as usize];
+        reader.read_exact(&mut json).await?;
+        let header: StreamHeader = serde_json::from_slice(&json)?;
+        if header.size > max_file_bytes() {
+            return Err(format!(
+                "Refusing '{}': {} bytes is more than the {} byte limit",
+                header.name, header.size, max_file_bytes()
+            )
+            .into());
+        }
+        Ok(header)
+    }
+
+    /// Sends the `size` bytes of `path` this header announced, a chunk at a time.
+    pub async fn write_file<W: AsyncWrite + Unpin>(&self, writer: &mut W, path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = File::open(path).await?;
+        let sent = tokio::io::copy(&mut file.take(self.size), writer).await?;
+        if sent != self.size {
+            return Err(format!("{} shrank to {} bytes while sending", path.display(), sent).into());
+        }
+        writer.flush().await?;
+        Ok(())
+    }
+
+    /// Writes the `size` bytes following this header into `path`, a chunk at
+    /// a time. If the stream ends early, `path` is removed.
+    pub async fn read_file<R: AsyncRead + Unpin>(&self, reader: &mut R, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut file = File::create(path).await?;
+        let copied = tokio::io::copy(&mut reader.take(self.size), &mut file).await;
+        let flushed = match copied {
+            Ok(n) if n == self.size => file.flush().await,
+            Ok(n) => Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("connection closed after {} of {} bytes", n, self.size),
+            )),
+            Err(e) => Err(e),
+        };
+        drop(file);
+        if let Err(e) = flushed {
+            let _ = tokio::fs::remove_file(path).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn headers_round_trip() {
+        let header = StreamHeader {
+            name: "notes.txt".to_string(),
+            size: 42,
+            metadata: FileMetadata {
+                modified: Some(1_700_000_000_000_000_000),
+                mode: Some(0o644),
+                ..FileMetadata::default()
+            },
+        };
+        let (mut a, mut b) = tokio::io::duplex(1024);
+        header.write(&mut a).await.unwrap();
+        assert_eq!(StreamHeader::read(&mut b).await.unwrap(), header);
+    }
+
+    #[tokio::test]
+    async fn refuses_oversized_headers() {
+        let (mut a, mut b) = tokio::io::duplex(1024);
+        a.write_all(&(MAX_HEADER_BYTES + 1).to_be_bytes()).await.unwrap();
+        assert!(StreamHeader::read(&mut b).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn refuses_files_over_the_limit() {
+        let header = StreamHeader {
+            name: "huge.bin".to_string(),
+            size: u64::MAX,
+            metadata: FileMetadata::default(),
+        };
+        let (mut a, mut b) = tokio::io::duplex(1024);
+        header.write(&mut a).await.unwrap();
+        let err = StreamHeader::read(&mut b).await.unwrap_err();
+        assert!(err.to_string().contains("limit"), "{}", err);
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("unishare-framing-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn files_stream_through_a_small_pipe() {
+        let dir = temp_dir("stream");
+        let data: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        std::fs::write(dir.join("source"), &data).unwrap();
+        let header = StreamHeader::for_file(&dir.join("source")).unwrap();
+
+        let (mut a, mut b) = tokio::io::duplex(256);
+        let target = dir.join("target");
+        let (sent, received) = tokio::join!(
+            async { header.write_file(&mut a, &dir.join("source")).await.map_err(|e| e.to_string()) },
+            async { header.read_file(&mut b, &target).await.map_err(|e| e.to_string()) },
+        );
+        sent.unwrap();
+        received.unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn short_bodies_leave_no_file() {
+        let dir = temp_dir("short");
+        let header = StreamHeader {
+            name: "cut.bin".to_string(),
+            size: 100,
+            metadata: FileMetadata::default(),
+        };
+        let (mut a, mut b) = tokio::io::duplex(1024);
+        a.write_all(&[7u8; 40]).await.unwrap();
+        drop(a);
+
+        let target = dir.join("cut.bin");
+        let err = header.read_file(&mut b, &target).await.unwrap_err();
+        assert!(err.to_string().contains("40 of 100"), "{}", err);
+        assert!(!target.exists());
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, FileTimes};
+use std::io;
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+/// Extended attributes beyond this many bytes in total aren't sent, so the
+/// header stays small enough for a single data channel message.
+const MAX_XATTR_BYTES: usize = 16 * 1024;
+/// Only user attributes are portable; the others hold ACLs, security labels
+/// and the like that mean nothing on the receiver, or need privileges to set.
+const XATTR_NAMESPACE: &str = "user.";
+
+/// What a sender records about a file next to its name and size.
+///
+/// Every field is optional, so headers from peers that don't send metadata,
+/// or from platforms that lack some of it, still parse.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FileMetadata {
+    /// Nanoseconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<i64>,
+    /// Nanoseconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accessed: Option<i64>,
+    /// Unix permission bits, e.g. `0o755`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// Extended attributes by name, values base64-encoded.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, String>,
+}
+
+/// Which metadata is sent and applied.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MetadataPolicy {
+    pub times: bool,
+    pub permissions: bool,
+    pub xattrs: bool,
+}
+
+impl Default for MetadataPolicy {
+    /// Times and permissions are kept; extended attributes are opt-in since
+    /// they can carry things like download quarantine flags.
+    fn default() -> Self {
+        MetadataPolicy {
+            times: true,
+            permissions: true,
+            xattrs: false,
+        }
+    }
+}
+
+static POLICY: LazyLock<MetadataPolicy> = LazyLock::new(MetadataPolicy::from_env);
+
+/// The policy every sender and receiver uses.
+pub fn policy() -> &'static MetadataPolicy {
+    &POLICY
+}
+
+impl MetadataPolicy {
+    /// Reads `UNISHARE_PRESERVE_TIMES`, `UNISHARE_PRESERVE_PERMISSIONS` and
+    /// `UNISHARE_PRESERVE_XATTRS` (`1`/`0`, `true`/`false`, `yes`/`no`).
+    pub fn from_env() -> Self {
+        let mut policy = MetadataPolicy::default();
+        let flag = |name| match std::env::var(name).ok()?.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Some(true),
+            "0" | "false" | "no" | "off" => Some(false),
+            _ => None,
+        };
+        if let Some(times) = flag("UNISHARE_PRESERVE_TIMES") {
+            policy.times = times;
+        }
+        if let Some(permissions) = flag("UNISHARE_PRESERVE_PERMISSIONS") {
+            policy.permissions = permissions;
+        }
+        if let Some(xattrs) = flag("UNISHARE_PRESERVE_XATTRS") {
+            policy.xattrs = xattrs;
+        }
+        policy
+    }
+
+    /// Collects the metadata of `path` this policy sends.
+    pub fn read(&self, path: &Path) -> io::Result<FileMetadata> {
+        let metadata = fs::metadata(path)?;
+        let mut read = FileMetadata::default();
+        if self.times {
+            read.modified = metadata.modified().ok().map(to_nanos);
+            read.accessed = metadata.accessed().ok().map(to_nanos);
+        }
+        if self.permissions {
+            read.mode = Some(mode_of(&metadata.permissions()));
+        }
+        if self.xattrs {
+            read.xattrs = read_xattrs(path);
+        }
+        Ok(read)
+    }
+
+    /// Applies the parts of `metadata` this policy keeps to the received
+    /// file at `path`, returning what was applied.
+    ///
+    /// - Failures are logged and skipped; the file itself arrived fine.
+    /// - Permissions are limited to the read/write/execute bits, so a peer
+    ///   can't hand out setuid files, and masked by this process's umask
+    ///   (group and others never get write access). The owner can always
+    ///   read and write the file.
+    /// - Permissions go last, since a read-only mode would stop the others.
+    pub fn apply(&self, metadata: &FileMetadata, path: &Path) -> Vec<String> {
+        let mut applied = Vec::new();
+        if self.xattrs && !metadata.xattrs.is_empty() {
+            let count = write_xattrs(path, &metadata.xattrs);
+            if count > 0 {
+                applied.push(format!("{} extended attributes", count));
+            }
+        }
+        if self.times && (metadata.modified.is_some() || metadata.accessed.is_some()) {
+            let mut times = FileTimes::new();
+            if let Some(modified) = metadata.modified {
+                times = times.set_modified(from_nanos(modified));
+            }
+            if let Some(accessed) = metadata.accessed {
+                times = times.set_accessed(from_nanos(accessed));
+            }
+            match File::open(path).and_then(|file| file.set_times(times)) {
+                Ok(()) => applied.push("times".to_string()),
+                Err(e) => println!("Couldn't set times on {}: {}", path.display(), e),
+            }
+        }
+        if self.permissions {
+            if let Some(mode) = metadata.mode {
+                let mode = received_mode(mode, umask());
+                match set_mode(path, mode) {
+                    Ok(()) => applied.push(format!("permissions {:o}", mode)),
+                    Err(e) => println!("Couldn't set permissions on {}: {}", path.display(), e),
+                }
+            }
+        }
+        if !applied.is_empty() {
+            println!("Restored {} on {}", applied.join(", "), path.display());
+        }
+        applied
+    }
+}
+
+/// Gives `to` the permissions and times of `from`. For post-receive steps
+/// that rewrite a received file through a temporary copy, so what
+/// [`MetadataPolicy::apply`] restored survives the rewrite.
+pub fn carry_over(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = fs::metadata(from)?;
+    let times = FileTimes::new()
+        .set_modified(metadata.modified()?)
+        .set_accessed(metadata.accessed()?);
+    File::open(to)?.set_times(times)?;
+    fs::set_permissions(to, metadata.permissions())
+}
+
+/// The mode a received file gets for the `sent` one.
+fn received_mode(sent: u32, umask: u32) -> u32 {
+    (sent & 0o777 & !(umask | 0o022)) | 0o600
+}
+
+/// Reads the umask from `/proc`: setting it to read the old value back would
+/// race with other threads creating files.
+#[cfg(target_os = "linux")]
+fn umask() -> u32 {
+    fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            let mask = status.lines().find_map(|line| line.strip_prefix("Umask:"))?;
+            u32::from_str_radix(mask.trim(), 8).ok()
+        })
+        .unwrap_or(0o022)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn umask() -> u32 {
+    0o022
+}
+
+fn to_nanos(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => i64::try_from(after.as_nanos()).unwrap_or(i64::MAX),
+        Err(before) => i64::try_from(before.duration().as_nanos()).map_or(i64::MIN, |n| -n),
+    }
+}
+
+fn from_nanos(nanos: i64) -> SystemTime {
+    let offset = Duration::from_nanos(nanos.unsigned_abs());
+    if nanos >= 0 {
+        UNIX_EPOCH + offset
+    } else {
+        UNIX_EPOCH - offset
+    }
+}
+
+#[cfg(unix)]
+fn mode_of(permissions: &fs::Permissions) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    permissions.mode() & 0o7777
+}
+
+/// Windows only knows read-only; report it the way a Unix peer expects.
+#[cfg(not(unix))]
+fn mode_of(permissions: &fs::Permissions) -> u32 {
+    if permissions.readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o222 == 0);
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(unix)]
+fn read_xattrs(path: &Path) -> BTreeMap<String, String> {
+    let mut xattrs = BTreeMap::new();
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(e) => {
+            println!("Couldn't list extended attributes of {}: {}", path.display(), e);
+            return xattrs;
+        }
+    };
+    let mut total = 0;
+    for name in names {
+        let Some(name) = name.to_str().filter(|name| name.starts_with(XATTR_NAMESPACE)) else {
+            continue;
+        };
+        let Ok(Some(value)) = xattr::get(path, name) else {
+            continue;
+        };
+        total += name.len() + value.len();
+        if total > MAX_XATTR_BYTES {
+            println!("Not sending the rest of {}'s extended attributes: over {} bytes", path.display(), MAX_XATTR_BYTES);
+            break;
+        }
+        xattrs.insert(name.to_string(), STANDARD.encode(value));
+    }
+    xattrs
+}
+
+#[cfg(not(unix))]
+fn read_xattrs(_path: &Path) -> BTreeMap<String, String> {
+    BTreeMap::new()
+}
+
+/// Returns how many attributes were set.
+#[cfg(unix)]
+fn write_xattrs(path: &Path, xattrs: &BTreeMap<String, String>) -> usize {
+    let mut count = 0;
+    for (name, value) in xattrs {
+        if !name.starts_with(XATTR_NAMESPACE) {
+            println!("Ignoring extended attribute {} outside the {} namespace", name, XATTR_NAMESPACE);
+            continue;
+        }
+        let result = STANDARD
+            .decode(value)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .and_then(|value| xattr::set(path, name, &value));
+        match result {
+            Ok(()) => count += 1,
+            Err(e) => println!("Couldn't set extended attribute {} on {}: {}", name, path.display(), e),
+        }
+    }
+    count
+}
+
+#[cfg(not(unix))]
+fn write_xattrs(_path: &Path, _xattrs: &BTreeMap<String, String>) -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("unishare-metadata-{}-{}", name, std::process::id()));
+        fs::write(&path, b"#!/bin/sh\n").unwrap();
+        path
+    }
+
+    #[test]
+    fn round_trips_times_before_and_after_the_epoch() {
+        for nanos in [0, 1_700_000_000_123_456_789, -86_400_000_000_001] {
+            assert_eq!(to_nanos(from_nanos(nanos)), nanos);
+        }
+    }
+
+    #[test]
+    fn leaves_out_fields_the_policy_skips() {
+        let path = temp_file("skip");
+        let policy = MetadataPolicy {
+            times: false,
+            permissions: true,
+            xattrs: false,
+        };
+        let read = policy.read(&path).unwrap();
+        assert_eq!(read.modified, None);
+        assert!(read.mode.is_some());
+        assert_eq!(serde_json::to_value(&read).unwrap().as_object().unwrap().len(), 1);
+        assert_eq!(serde_json::from_str::<FileMetadata>("{}").unwrap(), FileMetadata::default());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn applies_times_and_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_file("apply");
+        let sent = FileMetadata {
+            modified: Some(1_600_000_000_000_000_000),
+            accessed: Some(1_600_000_100_000_000_000),
+            mode: Some(0o4755),
+            xattrs: BTreeMap::new(),
+        };
+        let applied = MetadataPolicy::default().apply(&sent, &path);
+        let mode = 0o755 & !umask();
+        assert_eq!(applied, ["times".to_string(), format!("permissions {:o}", mode)]);
+
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(to_nanos(metadata.modified().unwrap()), 1_600_000_000_000_000_000);
+        assert_eq!(to_nanos(metadata.accessed().unwrap()), 1_600_000_100_000_000_000);
+        // The setuid bit is dropped.
+        assert_eq!(metadata.permissions().mode() & 0o7777, mode);
+        assert_eq!(MetadataPolicy::default().read(&path).unwrap().mode, Some(mode));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn received_modes_follow_the_umask_and_stay_writable() {
+        assert_eq!(received_mode(0o4755, 0o022), 0o755);
+        assert_eq!(received_mode(0o777, 0o022), 0o755);
+        assert_eq!(received_mode(0o777, 0o077), 0o700);
+        // No umask still keeps group and others from writing.
+        assert_eq!(received_mode(0o666, 0), 0o644);
+        assert_eq!(received_mode(0o444, 0o022), 0o644);
+        assert_eq!(received_mode(0, 0o022), 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn carries_permissions_and_times_over_to_a_rewritten_copy() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let original = temp_file("carry-original");
+        let copy = temp_file("carry-copy");
+        let sent = FileMetadata {
+            modified: Some(1_600_000_000_000_000_000),
+            mode: Some(0o700),
+            ..FileMetadata::default()
+        };
+        MetadataPolicy::default().apply(&sent, &original);
+        carry_over(&original, &copy).unwrap();
+
+        let metadata = fs::metadata(&copy).unwrap();
+        assert_eq!(to_nanos(metadata.modified().unwrap()), 1_600_000_000_000_000_000);
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o700);
+        fs::remove_file(original).unwrap();
+        fs::remove_file(copy).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copies_user_extended_attributes() {
+        let source = temp_file("xattr-source");
+        let dest = temp_file("xattr-dest");
+        if xattr::set(&source, "user.unishare.test", b"kept").is_err() {
+            // The temp filesystem doesn't support them.
+            return;
+        }
+        let policy = MetadataPolicy {
+            xattrs: true,
+            ..MetadataPolicy::default()
+        };
+        let mut sent = policy.read(&source).unwrap();
+        assert_eq!(sent.xattrs["user.unishare.test"], STANDARD.encode("kept"));
+
+        sent.xattrs.insert("security.selinux".to_string(), STANDARD.encode("x"));
+        policy.apply(&sent, &dest);
+        assert_eq!(xattr::get(&dest, "user.unishare.test").unwrap(), Some(b"kept".to_vec()));
+        assert_eq!(xattr::get(&dest, "security.selinux").unwrap_or_default(), None);
+        fs::remove_file(source).unwrap();
+        fs::remove_file(dest).unwrap();
+    }
+
+    #[test]
+    fn applies_nothing_when_disabled() {
+        let path = temp_file("disabled");
+        let before = fs::metadata(&path).unwrap().modified().unwrap();
+        let policy = MetadataPolicy {
+            times: false,
+            permissions: false,
+            xattrs: false,
+        };
+        let sent = FileMetadata {
+            modified: Some(0),
+            mode: Some(0o400),
+            ..FileMetadata::default()
+        };
+        assert!(policy.apply(&sent, &path).is_empty());
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), before);
+        fs::remove_file(path).unwrap();
+    }
+}
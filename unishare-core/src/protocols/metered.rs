@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, oneshot};
+
+use crate::tools::connectivity::{network_interfaces, LinkKind};
+
+const MIB: u64 = 1024 * 1024;
+/// How long a confirmation prompt waits for an answer before the send is refused.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// NetworkManager's `NMMetered` values.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Metered {
+    Unknown,
+    Yes,
+    No,
+    /// NetworkManager guessed, e.g. from a phone's hotspot advertising itself.
+    GuessYes,
+    GuessNo,
+}
+
+impl Metered {
+    pub fn is_metered(self) -> bool {
+        matches!(self, Metered::Yes | Metered::GuessYes)
+    }
+
+    fn from_nm(value: u32) -> Self {
+        match value {
+            1 => Metered::Yes,
+            2 => Metered::No,
+            3 => Metered::GuessYes,
+            4 => Metered::GuessNo,
+            _ => Metered::Unknown,
+        }
+    }
+}
+
+/// Where we learn whether the current connection is metered, so tests can
+/// swap in a fake.
+pub trait MeteredSource: Send + Sync {
+    fn metered(&self) -> Result<Metered, Box<dyn Error>>;
+}
+
+/// Reads NetworkManager's global `Metered` property over D-Bus, which
+/// reflects the primary connection.
+pub struct NetworkManagerMetered;
+
+impl NetworkManagerMetered {
+    fn query(&self) -> Result<Metered, Box<dyn Error>> {
+        let output = Command::new("busctl")
+            .args([
+                "--system",
+                "get-property",
+                "org.freedesktop.NetworkManager",
+                "/org/freedesktop/NetworkManager",
+                "org.freedesktop.NetworkManager",
+                "Metered",
+            ])
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Can't read NetworkManager's Metered property: {}", stderr.trim()).into());
+        }
+        parse_property(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+impl MeteredSource for NetworkManagerMetered {
+    /// Without NetworkManager, a connection that only goes out through a
+    /// tethered phone or a modem is guessed to be metered.
+    fn metered(&self) -> Result<Metered, Box<dyn Error>> {
+        self.query().or_else(|_| {
+            let interfaces: Vec<LinkKind> = network_interfaces()
+                .into_iter()
+                .filter(|interface| interface.receivers_reachable)
+                .map(|interface| interface.kind)
+                .collect();
+            let mobile = |kind: &LinkKind| matches!(kind, LinkKind::Tethered | LinkKind::Cellular);
+            Ok(if interfaces.is_empty() {
+                Metered::Unknown
+            } else if interfaces.iter().all(mobile) {
+                Metered::GuessYes
+            } else {
+                Metered::GuessNo
+            })
+        })
+    }
+}
+
+/// Parses `busctl get-property` output, e.g. `u 4`.
+fn parse_property(output: &str) -> Result<Metered, Box<dyn Error>> {
+    let value = output
+        .trim()
+        .strip_prefix("u ")
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("Unexpected Metered value: {}", output.trim()))?;
+    Ok(Metered::from_nm(value))
+}
+
+/// Limits for sends over metered connections.
+#[derive(Clone, Debug)]
+pub struct MeteredPolicy {
+    /// Larger sends ask the user first.
+    pub confirm_above: u64,
+    /// Larger sends are refused outright.
+    pub max_bytes: u64,
+}
+
+impl Default for MeteredPolicy {
+    fn default() -> Self {
+        MeteredPolicy {
+            confirm_above: 10 * MIB,
+            max_bytes: 200 * MIB,
+        }
+    }
+}
+
+impl MeteredPolicy {
+    /// Reads `UNISHARE_METERED_CONFIRM_BYTES` and `UNISHARE_METERED_MAX_BYTES`.
+    pub fn from_env() -> Self {
+        let mut policy = MeteredPolicy::default();
+        let bytes = |name| std::env::var(name).ok().and_then(|value| value.parse::<u64>().ok());
+        if let Some(confirm_above) = bytes("UNISHARE_METERED_CONFIRM_BYTES") {
+            policy.confirm_above = confirm_above;
+        }
+        if let Some(max_bytes) = bytes("UNISHARE_METERED_MAX_BYTES") {
+            policy.max_bytes = max_bytes;
+        }
+        policy
+    }
+}
+
+/// Sent to the UI when a send needs the user's go-ahead; answer with
+/// [`MeteredGuard::answer`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmationRequest {
+    pub id: String,
+    pub file_name: String,
+    pub size: u64,
+    pub metered: Metered,
+}
+
+/// Applies [`MeteredPolicy`] to sends that go over the internet.
+pub struct MeteredGuard {
+    source: Arc<dyn MeteredSource>,
+    policy: MeteredPolicy,
+    confirm_timeout: Duration,
+    pending: Mutex<HashMap<String, oneshot::Sender<bool>>>,
+    requests: broadcast::Sender<ConfirmationRequest>,
+}
+
+static GUARD: LazyLock<MeteredGuard> =
+    LazyLock::new(|| MeteredGuard::new(Arc::new(NetworkManagerMetered), MeteredPolicy::from_env()));
+
+/// The guard used by the mobile-data path.
+pub fn guard() -> &'static MeteredGuard {
+    &GUARD
+}
+
+impl MeteredGuard {
+    pub fn new(source: Arc<dyn MeteredSource>, policy: MeteredPolicy) -> Self {
+        MeteredGuard {
+            source,
+            policy,
+            confirm_timeout: CONFIRM_TIMEOUT,
+            pending: Mutex::new(HashMap::new()),
+            requests: broadcast::channel(16).0,
+        }
+    }
+
+    /// Receives every confirmation request from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<ConfirmationRequest> {
+        self.requests.subscribe()
+    }
+
+    /// The current connection's metered state.
+    pub async fn metered(&self) -> Metered {
+        let source = self.source.clone();
+        tokio::task::spawn_blocking(move || source.metered().map_err(|e| e.to_string()))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|metered| metered)
+            .unwrap_or_else(|e| {
+                println!("Metered check failed: {}", e);
+                Metered::Unknown
+            })
+    }
+
+    /// Decides whether `size` bytes of `file_name` may go out now.
+    ///
+    /// - Unmetered (or unknown) connections and small sends pass.
+    /// - Sends over `max_bytes` on a metered connection are refused.
+    /// - Anything in between waits for the user to confirm; no answer in
+    ///   time counts as a refusal.
+    pub async fn check_send(&self, file_name: &str, size: u64) -> Result<(), String> {
+        let metered = self.metered().await;
+        if !metered.is_metered() || size <= self.policy.confirm_above {
+            return Ok(());
+        }
+        if size > self.policy.max_bytes {
+            return Err(format!(
+                "{} is {} MiB, over the {} MiB limit for metered connections.",
+                file_name,
+                size / MIB,
+                self.policy.max_bytes / MIB
+            ));
+        }
+
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        let id = format!("metered-{}", NEXT.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id.clone(), tx);
+        let request = ConfirmationRequest {
+            id: id.clone(),
+            file_name: file_name.to_string(),
+            size,
+            metered,
+        };
+        println!("📶 Asking before sending {} ({} bytes) over a metered connection", file_name, size);
+        if self.requests.send(request).is_err() {
+            self.pending.lock().unwrap().remove(&id);
+            return Err("Can't ask for confirmation to send over a metered connection.".to_string());
+        }
+
+        let answer = tokio::time::timeout(self.confirm_timeout, rx).await;
+        self.pending.lock().unwrap().remove(&id);
+        match answer {
+            Ok(Ok(true)) => Ok(()),
+            Ok(_) => Err(format!("Sending {} over a metered connection was declined.", file_name)),
+            Err(_) => Err(format!(
+                "No confirmation to send {} over a metered connection.",
+                file_name
+            )),
+        }
+    }
+
+    /// Answers a [`ConfirmationRequest`].
+    pub fn answer(&self, id: &str, allow: bool) -> Result<(), String> {
+        let tx = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(id)
+            .ok_or_else(|| format!("No pending confirmation {}", id))?;
+        let _ = tx.send(allow);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeMetered(Metered);
+
+    impl MeteredSource for FakeMetered {
+        fn metered(&self) -> Result<Metered, Box<dyn Error>> {
+            Ok(self.0)
+        }
+    }
+
+    fn guard(metered: Metered) -> MeteredGuard {
+        let policy = MeteredPolicy {
+            confirm_above: 10 * MIB,
+            max_bytes: 100 * MIB,
+        };
+        let mut guard = MeteredGuard::new(Arc::new(FakeMetered(metered)), policy);
+        guard.confirm_timeout = Duration::from_millis(200);
+        guard
+    }
+
+    /// Answers the next confirmation request with `allow`.
+    fn answer_next(guard: &'static MeteredGuard, allow: bool) -> tokio::task::JoinHandle<ConfirmationRequest> {
+        let mut requests = guard.subscribe();
+        tokio::spawn(async move {
+            let request = requests.recv().await.unwrap();
+            guard.answer(&request.id, allow).unwrap();
+            request
+        })
+    }
+
+    #[test]
+    fn parses_network_manager_property() {
+        assert_eq!(parse_property("u 1\n").unwrap(), Metered::Yes);
+        assert_eq!(parse_property("u 4").unwrap(), Metered::GuessNo);
+        assert_eq!(parse_property("u 9").unwrap(), Metered::Unknown);
+        assert!(parse_property("Failed to get property").is_err());
+    }
+
+    #[tokio::test]
+    async fn unmetered_sends_pass() {
+        for metered in [Metered::No, Metered::GuessNo, Metered::Unknown] {
+            assert!(guard(metered).check_send("big.iso", 4096 * MIB).await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn small_metered_sends_pass() {
+        assert!(guard(Metered::Yes).check_send("note.txt", 10 * MIB).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn oversized_metered_sends_are_refused() {
+        let err = guard(Metered::GuessYes).check_send("big.iso", 101 * MIB).await.unwrap_err();
+        assert!(err.contains("100 MiB limit"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn large_metered_sends_wait_for_confirmation() {
+        let guard: &'static MeteredGuard = Box::leak(Box::new(guard(Metered::Yes)));
+
+        let prompt = answer_next(guard, true);
+        assert!(guard.check_send("video.mp4", 50 * MIB).await.is_ok());
+        let request = prompt.await.unwrap();
+        assert_eq!(request.file_name, "video.mp4");
+        assert_eq!(request.size, 50 * MIB);
+        assert_eq!(request.metered, Metered::Yes);
+
+        let prompt = answer_next(guard, false);
+        assert!(guard.check_send("video.mp4", 50 * MIB).await.unwrap_err().contains("declined"));
+        prompt.await.unwrap();
+        assert!(guard.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unanswered_confirmation_refuses() {
+        let guard = guard(Metered::Yes);
+        let mut requests = guard.subscribe();
+
+        let err = guard.check_send("video.mp4", 50 * MIB).await.unwrap_err();
+        assert!(err.contains("No confirmation"), "{}", err);
+        // A late answer finds nothing to confirm.
+        let request = requests.recv().await.unwrap();
+        assert!(guard.answer(&request.id, true).is_err());
+    }
+
+    #[tokio::test]
+    async fn no_listener_refuses() {
+        let err = guard(Metered::Yes).check_send("video.mp4", 50 * MIB).await.unwrap_err();
+        assert!(err.contains("Can't ask"), "{}", err);
+    }
+}
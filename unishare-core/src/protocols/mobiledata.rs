@@ -0,0 +1,97 @@
+use std::error::Error;
+use std::path::Path;
+use unishare_relay::client::{self, Ticket};
+use crate::protocols::approval;
+use crate::protocols::filename::{self, ReceivedFile};
+use crate::protocols::metadata::{self, FileMetadata};
+use crate::protocols::metered;
+
+/// Returns the relay from `UNISHARE_RELAY_URL`. There's no default: files
+/// only go through a relay the user chose, e.g. one they run with the
+/// `unishare-relay` binary.
+pub fn relay_url() -> Result<String, Box<dyn Error>> {
+    match std::env::var("UNISHARE_RELAY_URL") {
+        Ok(url) if !url.trim().is_empty() => Ok(url.trim().to_string()),
+        _ => Err("No relay configured; set UNISHARE_RELAY_URL to a unishare-relay server.".into()),
+    }
+}
+
+/// Whether `destination` is a relay ticket rather than an address.
+pub fn is_ticket(destination: &str) -> bool {
+    destination.parse::<Ticket>().is_ok()
+}
+
+/// Sends a file via Mobile Data by uploading it to the relay slot named in the ticket.
+///
+/// - The destination is a ticket created by the receiver (see [`create_ticket`]).
+/// - On a metered connection, large files need the user's confirmation and
+///   oversized ones are refused (see [`metered::MeteredGuard::check_send`]).
+/// - The header carries the metadata [`metadata::policy`] keeps.
+/// - The file is split into chunks, each encrypted with the ticket's key before upload.
+/// - Returns once every chunk is on the relay; the receiver may still be downloading.
+pub async fn send_file(file_path: &str, destination: &str) -> Result<(), Box<dyn Error>> {
+    let ticket: Ticket = destination.parse()?;
+    let size = tokio::fs::metadata(file_path).await?.len();
+    metered::guard().check_send(file_path, size).await?;
+    println!("Uploading '{}' to relay {}...", file_path, ticket.relay_url);
+    let metadata = serde_json::to_value(metadata::policy().read(Path::new(file_path))?)?;
+    let header = client::upload_file_with_metadata(&ticket, file_path, Some(metadata)).await?;
+    println!("Uploaded {} bytes to relay.", header.size);
+    Ok(())
+}
+
+/// Opens a slot on the relay and returns the ticket to hand to the sender.
+pub async fn create_ticket() -> Result<String, Box<dyn Error>> {
+    let relay = relay_url()?;
+    let (ticket, info) = client::create_ticket(&relay).await?;
+    println!("Relay slot {} open for {}s.", info.id, info.expires_in_secs);
+    Ok(ticket.to_string())
+}
+
+/// Downloads the file behind `ticket`, waiting for the sender as needed.
+///
+/// - Once the sender's header arrives, the file waits for [`approval::gate`]
+///   before anything is written.
+/// - Chunks are decrypted and checked as they arrive; a tampered chunk aborts the transfer.
+/// - Downloads into a hidden partial file, then moves it to the sender's file
+///   name as placed by [`filename::policy`].
+/// - Applies the sender's file metadata per [`metadata::policy`].
+pub async fn receive_file(ticket: &str) -> Result<ReceivedFile, Box<dyn Error>> {
+    let ticket: Ticket = ticket.parse()?;
+    let policy = filename::policy();
+    tokio::fs::create_dir_all(&policy.download_dir).await?;
+    let header = client::download_header(&ticket).await?;
+    let admission = approval::gate().admit(&header.name, header.size, &ticket.relay_url, "mobile-data").await?;
+    // Reserved like any received file, so concurrent downloads get their own.
+    let partial = policy.reserve(".relay_received.part")?.path;
+    if let Err(e) = client::download_body(&ticket, &header, &partial).await {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(e.into());
+    }
+    let received = match policy.reserve(&header.name).map_err(|e| e.to_string()) {
+        Ok(received) => received,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(e.into());
+        }
+    };
+    tokio::fs::rename(&partial, &received.path).await?;
+    match header.metadata.map(serde_json::from_value::<FileMetadata>) {
+        Some(Ok(sent)) => {
+            metadata::policy().apply(&sent, &received.path);
+        }
+        Some(Err(e)) => println!("Ignoring unreadable file metadata: {}", e),
+        None => {}
+    }
+    println!("File '{}' received via relay and saved as {}", header.name, received.path.display());
+    admission.done(&received);
+    Ok(received)
+}
+
+/// Starts a receiver for incoming files via Mobile Data: opens a relay slot,
+/// prints its ticket for the sender, and waits for the file.
+pub async fn start_receiver() -> Result<ReceivedFile, Box<dyn Error>> {
+    let ticket = create_ticket().await?;
+    println!("Waiting for a sender on relay ticket {}", ticket);
+    receive_file(&ticket).await
+}
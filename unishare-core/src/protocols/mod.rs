@@ -0,0 +1,14 @@
+pub mod protocol_manager;
+pub mod wifi_direct;
+pub mod webrtc;
+pub mod bluetooth;
+pub mod mobiledata;
+pub mod capabilities;
+/// Drives wpa_supplicant over its Unix domain control socket.
+#[cfg(unix)]
+pub mod p2p;
+pub mod metered;
+pub mod filename;
+pub mod metadata;
+pub mod framing;
+pub mod approval;
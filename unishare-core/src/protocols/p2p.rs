@@ -0,0 +1,682 @@
+//! Wi‑Fi Direct group negotiation through wpa_supplicant's P2P control interface.
+//!
+//! wpa_supplicant exposes one Unix datagram socket per interface under
+//! `/var/run/wpa_supplicant`. Commands such as `P2P_FIND` get a single reply
+//! datagram; after `ATTACH`, events like `<3>P2P-GROUP-STARTED ...` arrive on
+//! the same socket, interleaved with replies.
+
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tokio::net::UnixDatagram;
+use tokio::time::{sleep, timeout, timeout_at, Duration, Instant};
+
+/// Where wpa_supplicant puts its control sockets.
+pub const CTRL_DIR: &str = "/var/run/wpa_supplicant";
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Group negotiation waits for the user to accept on the other device.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+const IP_TIMEOUT: Duration = Duration::from_secs(15);
+const MAX_DATAGRAM: usize = 8192;
+
+/// The group interface of the group we formed last, for `disconnect`.
+static ACTIVE_GROUP: Mutex<Option<String>> = Mutex::new(None);
+
+/// A device seen during `P2P_FIND`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct P2pPeer {
+    /// P2P device address, used to connect.
+    pub address: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum GroupRole {
+    /// We are the group owner (the access point).
+    Owner,
+    Client,
+}
+
+/// A P2P group we are part of.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct P2pGroup {
+    /// Network interface wpa_supplicant created for the group, e.g. `p2p-wlan0-0`.
+    pub interface: String,
+    pub role: GroupRole,
+    pub ssid: String,
+    pub freq: Option<u32>,
+    pub go_device_address: Option<String>,
+    /// Our address on the group network.
+    pub local_ip: Option<Ipv4Addr>,
+    /// The other device's address; send files here.
+    pub peer_ip: Option<Ipv4Addr>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum P2pEvent {
+    DeviceFound(P2pPeer),
+    DeviceLost(String),
+    GroupStarted(P2pGroup),
+    GroupRemoved(String),
+    GoNegotiationFailed(String),
+    GroupFormationFailed(String),
+    Other(String),
+}
+
+/// Splits `name='My Laptop' freq=2437` into key/value pairs, honouring
+/// wpa_supplicant's single and double quotes.
+fn parse_fields(text: &str) -> (Vec<String>, HashMap<String, String>) {
+    let mut positional = Vec::new();
+    let mut fields = HashMap::new();
+    let mut chars = text.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut key = String::new();
+        let mut value = None;
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            if c == '=' && value.is_none() {
+                let mut v = String::new();
+                if let Some(quote) = chars.next_if(|c| *c == '\'' || *c == '"') {
+                    for c in chars.by_ref() {
+                        if c == quote {
+                            break;
+                        }
+                        v.push(c);
+                    }
+                } else {
+                    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                        v.push(c);
+                    }
+                }
+                value = Some(v);
+                break;
+            }
+            key.push(c);
+        }
+        match value {
+            Some(value) => {
+                fields.insert(key, value);
+            }
+            None => positional.push(key),
+        }
+    }
+    (positional, fields)
+}
+
+/// Parses one event line, with or without its `<N>` priority prefix.
+pub fn parse_event(line: &str) -> P2pEvent {
+    let line = strip_priority(line.trim());
+    let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let (positional, fields) = parse_fields(rest);
+    let first = positional.first().cloned().unwrap_or_default();
+
+    match name {
+        "P2P-DEVICE-FOUND" => P2pEvent::DeviceFound(P2pPeer {
+            address: fields.get("p2p_dev_addr").cloned().unwrap_or(first),
+            name: fields.get("name").cloned().unwrap_or_default(),
+        }),
+        "P2P-DEVICE-LOST" => P2pEvent::DeviceLost(fields.get("p2p_dev_addr").cloned().unwrap_or(first)),
+        "P2P-GROUP-STARTED" => {
+            let role = match positional.get(1).map(String::as_str) {
+                Some("GO") => GroupRole::Owner,
+                _ => GroupRole::Client,
+            };
+            let ip = |key: &str| fields.get(key).and_then(|ip| ip.parse().ok());
+            P2pEvent::GroupStarted(P2pGroup {
+                interface: first,
+                role,
+                ssid: fields.get("ssid").cloned().unwrap_or_default(),
+                freq: fields.get("freq").and_then(|f| f.parse().ok()),
+                go_device_address: fields.get("go_dev_addr").cloned(),
+                // Present when wpa_supplicant allocates addresses over EAPOL.
+                local_ip: ip("ip_addr"),
+                peer_ip: match role {
+                    GroupRole::Client => ip("go_ip_addr"),
+                    GroupRole::Owner => None,
+                },
+            })
+        }
+        "P2P-GROUP-REMOVED" => P2pEvent::GroupRemoved(first),
+        "P2P-GO-NEG-FAILURE" => P2pEvent::GoNegotiationFailed(rest.trim().to_string()),
+        "P2P-GROUP-FORMATION-FAILURE" => P2pEvent::GroupFormationFailed(rest.trim().to_string()),
+        _ => P2pEvent::Other(line.to_string()),
+    }
+}
+
+fn strip_priority(line: &str) -> &str {
+    match line.strip_prefix('<').and_then(|rest| rest.split_once('>')) {
+        Some((level, rest)) if level.chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => line,
+    }
+}
+
+/// A client connection to one wpa_supplicant control socket.
+pub struct WpaCtrl {
+    socket: UnixDatagram,
+    local_path: PathBuf,
+    /// Events that arrived while waiting for a command reply.
+    pending_events: VecDeque<String>,
+}
+
+impl WpaCtrl {
+    /// Connects to the control socket at `path`. wpa_supplicant replies to the
+    /// sender's address, so we bind a socket of our own in the temp directory.
+    pub async fn connect(path: &Path) -> Result<WpaCtrl, Box<dyn Error>> {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+        let local_path = std::env::temp_dir().join(format!(
+            "unishare-wpa-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&local_path);
+        let socket = UnixDatagram::bind(&local_path)?;
+        let ctrl = WpaCtrl {
+            socket,
+            local_path,
+            pending_events: VecDeque::new(),
+        };
+        ctrl.socket
+            .connect(path)
+            .map_err(|e| format!("Can't reach wpa_supplicant at {}: {}", path.display(), e))?;
+        Ok(ctrl)
+    }
+
+    /// Sends a command and returns its reply. `FAIL` and unknown-command
+    /// replies become errors.
+    pub async fn request(&mut self, command: &str) -> Result<String, Box<dyn Error>> {
+        self.socket.send(command.as_bytes()).await?;
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        let mut buffer = vec![0u8; MAX_DATAGRAM];
+        loop {
+            let len = timeout_at(deadline, self.socket.recv(&mut buffer))
+                .await
+                .map_err(|_| format!("wpa_supplicant didn't answer {}", command))??;
+            let message = String::from_utf8_lossy(&buffer[..len]).into_owned();
+            if message.starts_with('<') {
+                self.pending_events.push_back(message);
+                continue;
+            }
+            let reply = message.trim_end().to_string();
+            if reply.starts_with("FAIL") || reply == "UNKNOWN COMMAND" {
+                return Err(format!("wpa_supplicant rejected {}: {}", command, reply).into());
+            }
+            return Ok(reply);
+        }
+    }
+
+    /// Subscribes to events on this connection.
+    pub async fn attach(&mut self) -> Result<(), Box<dyn Error>> {
+        self.request("ATTACH").await?;
+        Ok(())
+    }
+
+    /// Waits for the next event until `deadline`; `None` when it passes.
+    pub async fn next_event(&mut self, deadline: Instant) -> Result<Option<P2pEvent>, Box<dyn Error>> {
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(Some(parse_event(&event)));
+        }
+        let mut buffer = vec![0u8; MAX_DATAGRAM];
+        match timeout_at(deadline, self.socket.recv(&mut buffer)).await {
+            Ok(len) => Ok(Some(parse_event(&String::from_utf8_lossy(&buffer[..len?])))),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl Drop for WpaCtrl {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.local_path);
+    }
+}
+
+/// P2P operations on one wireless interface.
+pub struct WpaP2p {
+    ctrl: WpaCtrl,
+}
+
+impl WpaP2p {
+    /// Opens the P2P control socket for `interface`. Drivers with a dedicated
+    /// P2P device expose it as `p2p-dev-<interface>`; others use the interface itself.
+    pub async fn open(interface: &str) -> Result<WpaP2p, Box<dyn Error>> {
+        let dedicated = Path::new(CTRL_DIR).join(format!("p2p-dev-{}", interface));
+        let path = if dedicated.exists() {
+            dedicated
+        } else {
+            Path::new(CTRL_DIR).join(interface)
+        };
+        Self::with_socket(&path).await
+    }
+
+    /// Opens a specific control socket.
+    pub async fn with_socket(path: &Path) -> Result<WpaP2p, Box<dyn Error>> {
+        let mut ctrl = WpaCtrl::connect(path).await?;
+        ctrl.attach().await?;
+        Ok(WpaP2p { ctrl })
+    }
+
+    /// Searches for P2P devices for `duration` and returns those still visible.
+    pub async fn find(&mut self, duration: Duration) -> Result<Vec<P2pPeer>, Box<dyn Error>> {
+        self.ctrl.request(&format!("P2P_FIND {}", duration.as_secs().max(1))).await?;
+        let deadline = Instant::now() + duration;
+        let mut peers: Vec<P2pPeer> = Vec::new();
+        while let Some(event) = self.ctrl.next_event(deadline).await? {
+            match event {
+                P2pEvent::DeviceFound(peer) => {
+                    peers.retain(|p| p.address != peer.address);
+                    peers.push(peer);
+                }
+                P2pEvent::DeviceLost(address) => peers.retain(|p| p.address != address),
+                _ => {}
+            }
+        }
+        let _ = self.ctrl.request("P2P_STOP_FIND").await;
+        Ok(peers)
+    }
+
+    /// Negotiates a group with `peer` using push-button config and waits for it
+    /// to come up, then finds both ends' IP addresses on it.
+    ///
+    /// `go_intent` (0–15) is how much we want to be the group owner.
+    pub async fn connect(&mut self, peer: &str, go_intent: u8) -> Result<P2pGroup, Box<dyn Error>> {
+        self.ctrl
+            .request(&format!("P2P_CONNECT {} pbc go_intent={}", peer, go_intent.min(15)))
+            .await?;
+
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        let group = loop {
+            match self.ctrl.next_event(deadline).await? {
+                Some(P2pEvent::GroupStarted(group)) => break group,
+                Some(P2pEvent::GoNegotiationFailed(reason)) => {
+                    return Err(format!("Wi‑Fi Direct negotiation with {} failed: {}", peer, reason).into())
+                }
+                Some(P2pEvent::GroupFormationFailed(reason)) => {
+                    return Err(format!("Wi‑Fi Direct group formation with {} failed: {}", peer, reason).into())
+                }
+                Some(_) => continue,
+                None => return Err(format!("Timed out waiting for {} to accept the connection", peer).into()),
+            }
+        };
+        println!(
+            "📶 Wi‑Fi Direct group '{}' up on {} as {:?}",
+            group.ssid, group.interface, group.role
+        );
+        *ACTIVE_GROUP.lock().unwrap() = Some(group.interface.clone());
+        Ok(discover_ips(group, &SystemNet).await)
+    }
+
+    /// Leaves or dissolves the group on `interface`.
+    pub async fn remove_group(&mut self, interface: &str) -> Result<(), Box<dyn Error>> {
+        self.ctrl.request(&format!("P2P_GROUP_REMOVE {}", interface)).await?;
+        let mut active = ACTIVE_GROUP.lock().unwrap();
+        if active.as_deref() == Some(interface) {
+            *active = None;
+        }
+        Ok(())
+    }
+}
+
+/// The group interface of the group [`WpaP2p::connect`] formed last, if any.
+pub fn active_group() -> Option<String> {
+    ACTIVE_GROUP.lock().unwrap().clone()
+}
+
+/// Where addresses on a group interface come from, so tests can supply them.
+pub trait NetInfo {
+    /// Our IPv4 address on `interface`, once assigned.
+    fn interface_ipv4(&self, interface: &str) -> Option<Ipv4Addr>;
+    /// IPv4 neighbours seen on `interface`.
+    fn neighbours(&self, interface: &str) -> Vec<Ipv4Addr>;
+}
+
+/// Reads addresses from `ip` and the kernel ARP table.
+pub struct SystemNet;
+
+impl NetInfo for SystemNet {
+    fn interface_ipv4(&self, interface: &str) -> Option<Ipv4Addr> {
+        let output = std::process::Command::new("ip")
+            .args(["-4", "-o", "addr", "show", "dev", interface])
+            .output()
+            .ok()?;
+        parse_ip_addr(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn neighbours(&self, interface: &str) -> Vec<Ipv4Addr> {
+        std::fs::read_to_string("/proc/net/arp")
+            .map(|arp| parse_arp(&arp, interface))
+            .unwrap_or_default()
+    }
+}
+
+/// Fills in missing group addresses. Addressing itself comes from
+/// wpa_supplicant's IP allocation or from NetworkManager/DHCP; the peer shows
+/// up in the ARP table once it has talked to us.
+pub async fn discover_ips(mut group: P2pGroup, net: &impl NetInfo) -> P2pGroup {
+    let deadline = Instant::now() + IP_TIMEOUT;
+    while Instant::now() < deadline {
+        if group.local_ip.is_none() {
+            group.local_ip = net.interface_ipv4(&group.interface);
+        }
+        if group.peer_ip.is_none() {
+            group.peer_ip = net
+                .neighbours(&group.interface)
+                .into_iter()
+                .find(|ip| Some(*ip) != group.local_ip);
+        }
+        if group.local_ip.is_some() && group.peer_ip.is_some() {
+            break;
+        }
+        sleep(Duration::from_millis(250)).await;
+    }
+    group
+}
+
+/// Parses `ip -4 -o addr show` output: `5: p2p-wlan0-0    inet 192.168.49.1/24 brd ...`.
+pub fn parse_ip_addr(output: &str) -> Option<Ipv4Addr> {
+    output.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        words.find(|w| *w == "inet")?;
+        words.next()?.split('/').next()?.parse().ok()
+    })
+}
+
+/// Parses `/proc/net/arp`, keeping complete entries on `interface`.
+pub fn parse_arp(table: &str, interface: &str) -> Vec<Ipv4Addr> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            // IP address, HW type, Flags, HW address, Mask, Device
+            if columns.len() < 6 || columns[5] != interface || columns[2] == "0x0" {
+                return None;
+            }
+            columns[0].parse().ok()
+        })
+        .collect()
+}
+
+/// The first wireless interface, or `UNISHARE_P2P_INTERFACE` if set.
+pub fn default_interface() -> Option<String> {
+    if let Ok(interface) = std::env::var("UNISHARE_P2P_INTERFACE") {
+        return Some(interface);
+    }
+    let mut wireless: Vec<String> = std::fs::read_dir("/sys/class/net")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("wireless").exists())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.starts_with("p2p-"))
+        .collect();
+    wireless.sort();
+    wireless.into_iter().next()
+}
+
+/// Whether wpa_supplicant offers a control socket we could drive P2P through.
+pub fn control_socket_available() -> bool {
+    default_interface().is_some_and(|interface| {
+        Path::new(CTRL_DIR).join(format!("p2p-dev-{}", interface)).exists()
+            || Path::new(CTRL_DIR).join(&interface).exists()
+    })
+}
+
+/// Opens the control socket for the default interface.
+pub async fn open_default() -> Result<WpaP2p, Box<dyn Error>> {
+    let interface = default_interface().ok_or("No wireless interface found")?;
+    timeout(REPLY_TIMEOUT, WpaP2p::open(&interface))
+        .await
+        .map_err(|_| "wpa_supplicant didn't respond")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One step of a scripted wpa_supplicant: the command it expects, its
+    /// reply, and events it sends afterwards.
+    struct Step {
+        expect: &'static str,
+        reply: &'static str,
+        events: Vec<&'static str>,
+    }
+
+    fn step(expect: &'static str, reply: &'static str, events: Vec<&'static str>) -> Step {
+        Step { expect, reply, events }
+    }
+
+    /// Binds a fake control socket that plays `script` and returns its path.
+    /// The task panics (failing the test) on an unexpected command.
+    fn fake_wpa_supplicant(script: Vec<Step>) -> (PathBuf, tokio::task::JoinHandle<()>) {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "unishare-fake-wpa-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&path);
+        let server = UnixDatagram::bind(&path).unwrap();
+        let task = tokio::spawn(async move {
+            let mut buffer = [0u8; 1024];
+            for step in script {
+                let (len, client) = server.recv_from(&mut buffer).await.unwrap();
+                let client = client.as_pathname().unwrap().to_path_buf();
+                assert_eq!(std::str::from_utf8(&buffer[..len]).unwrap(), step.expect);
+                server.send_to(step.reply.as_bytes(), &client).await.unwrap();
+                for event in step.events {
+                    server.send_to(event.as_bytes(), &client).await.unwrap();
+                }
+            }
+        });
+        (path, task)
+    }
+
+    #[test]
+    fn parses_device_found() {
+        let event = parse_event(
+            "<3>P2P-DEVICE-FOUND 02:11:22:33:44:55 p2p_dev_addr=02:11:22:33:44:55 \
+             pri_dev_type=1-0050F204-1 name='Living Room PC' config_methods=0x188 dev_capab=0x25",
+        );
+        assert_eq!(
+            event,
+            P2pEvent::DeviceFound(P2pPeer {
+                address: "02:11:22:33:44:55".into(),
+                name: "Living Room PC".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn parses_group_started_as_client_with_ip_allocation() {
+        let event = parse_event(
+            "<3>P2P-GROUP-STARTED p2p-wlan0-0 client ssid=\"DIRECT-ab Unishare\" freq=2437 \
+             psk=0123 go_dev_addr=02:aa:bb:cc:dd:ee ip_addr=192.168.49.10 \
+             ip_mask=255.255.255.0 go_ip_addr=192.168.49.1",
+        );
+        let P2pEvent::GroupStarted(group) = event else {
+            panic!("expected a group: {:?}", event);
+        };
+        assert_eq!(group.interface, "p2p-wlan0-0");
+        assert_eq!(group.role, GroupRole::Client);
+        assert_eq!(group.ssid, "DIRECT-ab Unishare");
+        assert_eq!(group.freq, Some(2437));
+        assert_eq!(group.go_device_address.as_deref(), Some("02:aa:bb:cc:dd:ee"));
+        assert_eq!(group.local_ip, Some(Ipv4Addr::new(192, 168, 49, 10)));
+        assert_eq!(group.peer_ip, Some(Ipv4Addr::new(192, 168, 49, 1)));
+    }
+
+    #[test]
+    fn parses_group_started_as_owner() {
+        let event = parse_event(
+            "P2P-GROUP-STARTED p2p-wlan0-1 GO ssid=\"DIRECT-xy\" freq=5180 passphrase=\"secret12\" \
+             go_dev_addr=02:11:22:33:44:55",
+        );
+        let P2pEvent::GroupStarted(group) = event else {
+            panic!("expected a group: {:?}", event);
+        };
+        assert_eq!(group.role, GroupRole::Owner);
+        assert_eq!(group.local_ip, None);
+        assert_eq!(group.peer_ip, None);
+    }
+
+    #[test]
+    fn parses_failures_and_unknown_events() {
+        assert_eq!(
+            parse_event("<3>P2P-GO-NEG-FAILURE status=1"),
+            P2pEvent::GoNegotiationFailed("status=1".into())
+        );
+        assert_eq!(
+            parse_event("<3>P2P-GROUP-REMOVED p2p-wlan0-0 GO reason=REQUESTED"),
+            P2pEvent::GroupRemoved("p2p-wlan0-0".into())
+        );
+        assert_eq!(parse_event("<2>CTRL-EVENT-SCAN-STARTED"), P2pEvent::Other("CTRL-EVENT-SCAN-STARTED".into()));
+    }
+
+    #[test]
+    fn parses_addresses() {
+        let ip = "7: p2p-wlan0-0    inet 192.168.49.1/24 brd 192.168.49.255 scope global p2p-wlan0-0";
+        assert_eq!(parse_ip_addr(ip), Some(Ipv4Addr::new(192, 168, 49, 1)));
+        assert_eq!(parse_ip_addr(""), None);
+
+        let arp = "IP address       HW type     Flags       HW address            Mask     Device\n\
+                   192.168.1.1      0x1         0x2         aa:bb:cc:dd:ee:ff     *        wlan0\n\
+                   192.168.49.10    0x1         0x2         02:11:22:33:44:55     *        p2p-wlan0-0\n\
+                   192.168.49.11    0x1         0x0         00:00:00:00:00:00     *        p2p-wlan0-0\n";
+        assert_eq!(parse_arp(arp, "p2p-wlan0-0"), vec![Ipv4Addr::new(192, 168, 49, 10)]);
+    }
+
+    #[tokio::test]
+    async fn find_collects_peers() {
+        let (path, fake) = fake_wpa_supplicant(vec![
+            step("ATTACH", "OK\n", vec![]),
+            step(
+                "P2P_FIND 1",
+                "OK\n",
+                vec![
+                    "<3>P2P-DEVICE-FOUND 02:00:00:00:00:01 p2p_dev_addr=02:00:00:00:00:01 name='Phone'",
+                    "<3>P2P-DEVICE-FOUND 02:00:00:00:00:02 p2p_dev_addr=02:00:00:00:00:02 name='Tablet'",
+                    "<3>P2P-DEVICE-FOUND 02:00:00:00:00:01 p2p_dev_addr=02:00:00:00:00:01 name='Phone'",
+                    "<3>P2P-DEVICE-LOST p2p_dev_addr=02:00:00:00:00:02",
+                ],
+            ),
+            step("P2P_STOP_FIND", "OK\n", vec![]),
+        ]);
+
+        let mut p2p = WpaP2p::with_socket(&path).await.unwrap();
+        let peers = p2p.find(Duration::from_millis(300)).await.unwrap();
+        assert_eq!(
+            peers,
+            vec![P2pPeer {
+                address: "02:00:00:00:00:01".into(),
+                name: "Phone".into()
+            }]
+        );
+        fake.await.unwrap();
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn connect_waits_for_group() {
+        let (path, fake) = fake_wpa_supplicant(vec![
+            step("ATTACH", "OK\n", vec![]),
+            step(
+                "P2P_CONNECT 02:00:00:00:00:01 pbc go_intent=0",
+                "OK\n",
+                vec![
+                    "<3>P2P-GO-NEG-SUCCESS role=client freq=2437",
+                    "<3>P2P-GROUP-STARTED p2p-wlan0-0 client ssid=\"DIRECT-ab\" freq=2437 \
+                     go_dev_addr=02:00:00:00:00:01 ip_addr=192.168.49.10 ip_mask=255.255.255.0 \
+                     go_ip_addr=192.168.49.1",
+                ],
+            ),
+            step("P2P_GROUP_REMOVE p2p-wlan0-0", "OK\n", vec![]),
+        ]);
+
+        let mut p2p = WpaP2p::with_socket(&path).await.unwrap();
+        let group = p2p.connect("02:00:00:00:00:01", 0).await.unwrap();
+        assert_eq!(group.interface, "p2p-wlan0-0");
+        assert_eq!(group.peer_ip, Some(Ipv4Addr::new(192, 168, 49, 1)));
+        assert_eq!(active_group().as_deref(), Some("p2p-wlan0-0"));
+
+        p2p.remove_group("p2p-wlan0-0").await.unwrap();
+        assert_eq!(active_group(), None);
+        fake.await.unwrap();
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn connect_reports_negotiation_failure() {
+        let (path, fake) = fake_wpa_supplicant(vec![
+            step("ATTACH", "OK\n", vec![]),
+            step(
+                "P2P_CONNECT 02:00:00:00:00:09 pbc go_intent=15",
+                "OK\n",
+                vec!["<3>P2P-GO-NEG-FAILURE status=9"],
+            ),
+        ]);
+
+        let mut p2p = WpaP2p::with_socket(&path).await.unwrap();
+        let err = p2p.connect("02:00:00:00:00:09", 20).await.unwrap_err();
+        assert!(err.to_string().contains("status=9"), "{}", err);
+        fake.await.unwrap();
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn rejected_commands_are_errors() {
+        let (path, fake) = fake_wpa_supplicant(vec![
+            step("ATTACH", "OK\n", vec![]),
+            step("P2P_FIND 1", "FAIL\n", vec![]),
+        ]);
+
+        let mut p2p = WpaP2p::with_socket(&path).await.unwrap();
+        assert!(p2p.find(Duration::from_millis(100)).await.is_err());
+        fake.await.unwrap();
+        let _ = std::fs::remove_file(path);
+    }
+
+    struct FakeNet {
+        local: Option<Ipv4Addr>,
+        neighbours: Vec<Ipv4Addr>,
+    }
+
+    impl NetInfo for FakeNet {
+        fn interface_ipv4(&self, _interface: &str) -> Option<Ipv4Addr> {
+            self.local
+        }
+
+        fn neighbours(&self, _interface: &str) -> Vec<Ipv4Addr> {
+            self.neighbours.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn owner_discovers_ips_from_interface_and_arp() {
+        let P2pEvent::GroupStarted(group) =
+            parse_event("P2P-GROUP-STARTED p2p-wlan0-1 GO ssid=\"DIRECT-xy\" freq=2437")
+        else {
+            unreachable!()
+        };
+        let net = FakeNet {
+            local: Some(Ipv4Addr::new(192, 168, 49, 1)),
+            neighbours: vec![Ipv4Addr::new(192, 168, 49, 1), Ipv4Addr::new(192, 168, 49, 23)],
+        };
+        let group = discover_ips(group, &net).await;
+        assert_eq!(group.local_ip, Some(Ipv4Addr::new(192, 168, 49, 1)));
+        assert_eq!(group.peer_ip, Some(Ipv4Addr::new(192, 168, 49, 23)));
+    }
+}
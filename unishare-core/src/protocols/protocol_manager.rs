@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+
+use crate::device_discovery::DiscoveredDevice;
+use crate::image_converter::ImageReport;
+use crate::normalize::NormalizationRecord;
+use crate::post_receive::{self, PostReceive, TransferResult};
+use crate::protocols::{wifi_direct, webrtc, bluetooth, mobiledata, capabilities};
+use crate::protocols::capabilities::Hello;
+use crate::protocols::filename::ReceivedFile;
+use crate::tools::connectivity::{address_score, network_interfaces};
+use crate::tools::watcher::watcher;
+
+/// How to reach a discovered device: which of its addresses, over which transport.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub address: String,
+    pub transport: &'static str,
+}
+
+/// Transports tried for direct transfers, best first.
+const TRANSPORT_PRIORITY: [&str; 3] = ["webrtc", "wifi-direct", "bluetooth"];
+
+/// Whether this device can send over `transport` right now.
+async fn supported_locally(transport: &str) -> bool {
+    match transport {
+        "webrtc" => true,
+        "wifi-direct" => wifi_direct::is_available().await,
+        "bluetooth" => bluetooth::is_available().await,
+        _ => false,
+    }
+}
+
+/// The best transport both we and the peer can use, going by the peer's
+/// hello: it must have a receiver running on it, not just support it.
+async fn pick_transport(hello: &Hello) -> Option<&'static str> {
+    for transport in TRANSPORT_PRIORITY {
+        if hello.is_receiving(transport) && supported_locally(transport).await {
+            return Some(transport);
+        }
+    }
+    None
+}
+
+/// Sends over one transport and returns its display name.
+pub async fn send_via(transport: &str, file_path: &str, address: &str) -> Result<&'static str, Box<dyn std::error::Error>> {
+    match transport {
+        "webrtc" => {
+            webrtc::send_file(file_path, address).await?;
+            Ok("WebRTC")
+        }
+        "wifi-direct" => {
+            wifi_direct::send_file(file_path, address).await?;
+            Ok("Wi‑Fi Direct")
+        }
+        "bluetooth" => {
+            bluetooth::send_file(file_path, address).await?;
+            Ok("Bluetooth")
+        }
+        other => Err(format!("Unknown transport '{}'", other).into()),
+    }
+}
+
+/// Picks the best way to reach `device`, using the same priority as
+/// [`send_file_via_best`].
+///
+/// - Only IPv4 addresses are considered; the transports dial `address:port`.
+///   They are tried in order of the local link that reaches them (see
+///   [`address_score`]), so a wired or Wi‑Fi Direct path beats Wi‑Fi.
+/// - If the device answers a capability query, only transports it has a
+///   receiver running on are used.
+/// - Otherwise (older versions) WebRTC wins on the first address where its
+///   receiver answers, then Wi‑Fi Direct and Bluetooth going by discovery metadata.
+pub async fn resolve_route(device: &DiscoveredDevice) -> Result<Route, String> {
+    let mut addresses: Vec<(&String, Ipv4Addr)> = device
+        .addresses
+        .iter()
+        .filter_map(|address| address.parse::<Ipv4Addr>().ok().map(|ip| (address, ip)))
+        .collect();
+    // Try addresses on our best local link first.
+    let interfaces = network_interfaces();
+    addresses.sort_by_key(|(_, ip)| std::cmp::Reverse(address_score((*ip).into(), &interfaces)));
+    let addresses: Vec<&String> = addresses.into_iter().map(|(address, _)| address).collect();
+
+    for address in &addresses {
+        if let Some(hello) = capabilities::query(address).await {
+            return match pick_transport(&hello).await {
+                Some(transport) => Ok(Route { address: address.to_string(), transport }),
+                None => Err(format!(
+                    "{} isn't receiving on any transport this device supports (receiving: {:?})",
+                    device.name, hello.receiving
+                )),
+            };
+        }
+    }
+
+    let supports = |transport: &str| device.transports.iter().any(|t| t == transport);
+    if supports("webrtc") {
+        for address in &addresses {
+            if webrtc::is_available(address).await {
+                return Ok(Route { address: address.to_string(), transport: "webrtc" });
+            }
+        }
+    }
+    let address = addresses
+        .first()
+        .ok_or_else(|| format!("No reachable address for {}", device.name))?
+        .to_string();
+    ["wifi-direct", "bluetooth"]
+        .into_iter()
+        .find(|transport| supports(transport))
+        .map(|transport| Route { address, transport })
+        .ok_or_else(|| format!("{} doesn't support any direct transport", device.name))
+}
+
+/// Sends a file to a discovered device over the route [`resolve_route`] picks.
+pub async fn send_file_to_device(file_path: &str, device: &DiscoveredDevice) -> Result<String, Box<dyn std::error::Error>> {
+    let route = resolve_route(device).await?;
+    println!("Sending to {} at {} via {}.", device.name, route.address, route.transport);
+    let via = send_via(route.transport, file_path, &route.address).await?;
+    Ok(format!("File sent to {} via {}", device.name, via))
+}
+
+
+pub async fn send_file_via_best(file_path: &str, destination: &str) -> Result<String, Box<dyn std::error::Error>> {
+    // A relay ticket can only be reached through the relay.
+    if mobiledata::is_ticket(destination) {
+        mobiledata::relay_url().map_err(|e| e.to_string())?;
+        if !watcher().internet_likely() {
+            return Err("No internet connection to reach the relay.".into());
+        }
+        println!("Using Mobile Data for file transfer.");
+        mobiledata::send_file(file_path, destination).await?;
+        return Ok("File sent via Mobile Data".to_string());
+    }
+    // Ask the destination what it is listening on, so we never pick a
+    // transport it can't receive.
+    if let Some(hello) = capabilities::query(destination).await {
+        let transport = pick_transport(&hello).await.ok_or_else(|| {
+            format!(
+                "{} isn't receiving on any transport this device supports (receiving: {:?})",
+                hello.name, hello.receiving
+            )
+        })?;
+        println!("Using {} for file transfer to {}.", transport, hello.name);
+        let via = send_via(transport, file_path, destination).await?;
+        return Ok(format!("File sent via {}", via));
+    }
+
+    // No answer: an older peer without capability queries.
+    // WebRTC is checked first: its check confirms a receiver is listening at
+    // the destination, whereas the Wi‑Fi Direct check only looks at this device.
+    if webrtc::is_available(destination).await {
+        println!("Using WebRTC for file transfer.");
+        webrtc::send_file(file_path, destination).await?;
+        return Ok("File sent via WebRTC".to_string());
+    }
+    if wifi_direct::is_available().await {
+        println!("Using Wi‑Fi Direct for file transfer.");
+        wifi_direct::send_file(file_path, destination).await?;
+        return Ok("File sent via Wi‑Fi Direct".to_string());
+    }
+    if bluetooth::is_available().await {
+        println!("Using Bluetooth for file transfer.");
+        bluetooth::send_file(file_path, destination).await?;
+        return Ok("File sent via Bluetooth".to_string());
+    }
+    Err("No available protocol found for file transfer.".into())
+}
+
+/// What [`start_receiver`] did.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Received {
+    pub message: String,
+    /// Where the file ended up, after any post-receive steps.
+    pub file: ReceivedFile,
+    pub normalization: Option<NormalizationRecord>,
+    pub image_conversion: Option<ImageReport>,
+}
+
+/// Receives one file on the best receivers this device can run, then runs
+/// the [`post_receive::steps`] configured for this process on it.
+pub async fn start_receiver() -> Result<Received, Box<dyn std::error::Error>> {
+    start_receiver_with(post_receive::steps()).await
+}
+
+/// Like [`start_receiver`], running `steps` on the received file.
+pub async fn start_receiver_with(steps: PostReceive) -> Result<Received, Box<dyn std::error::Error>> {
+    let (message, mut file) = receive_on_best().await?;
+    let result = TransferResult::new(message, Some(file.path.display().to_string()))
+        .finish(steps)
+        .await;
+    if let Some(path) = &result.file {
+        file.path = PathBuf::from(path);
+    }
+    Ok(Received {
+        message: result.message,
+        file,
+        normalization: result.normalization,
+        image_conversion: result.image_conversion,
+    })
+}
+
+/// A file one of the background receivers got, or why one stopped.
+type Completion = Result<(&'static str, ReceivedFile), String>;
+
+/// The receivers [`receive_on_best`] starts. Each runs in a task of its own
+/// until it fails, so a transfer arriving on one isn't cut off when another
+/// finishes first; files nobody is waiting for stay queued for the next call.
+struct Receivers {
+    running: Mutex<HashMap<&'static str, AbortHandle>>,
+    tx: mpsc::UnboundedSender<Completion>,
+    rx: tokio::sync::Mutex<mpsc::UnboundedReceiver<Completion>>,
+}
+
+static RECEIVERS: LazyLock<Receivers> = LazyLock::new(|| {
+    let (tx, rx) = mpsc::unbounded_channel();
+    Receivers {
+        running: Mutex::new(HashMap::new()),
+        tx,
+        rx: tokio::sync::Mutex::new(rx),
+    }
+});
+
+fn receivers() -> &'static Receivers {
+    &RECEIVERS
+}
+
+/// Stops the background receivers, along with any file they're receiving.
+/// The next [`start_receiver`] starts them again.
+pub fn stop_receivers() {
+    for (_, receiver) in receivers().running.lock().unwrap().drain() {
+        receiver.abort();
+    }
+}
+
+impl Receivers {
+    /// Starts `transport`'s receiver unless it's already running.
+    fn ensure(&'static self, transport: &'static str) {
+        let mut running = self.running.lock().unwrap();
+        if running.contains_key(transport) {
+            return;
+        }
+        let tx = self.tx.clone();
+        let task = tokio::spawn(async move {
+            let stopped = loop {
+                match receive_one(transport).await.map_err(|e| e.to_string()) {
+                    Ok(file) => {
+                        let _ = tx.send(Ok((transport, file)));
+                    }
+                    Err(e) => break Some(e),
+                }
+                // A relay slot carries one file; the next call opens another.
+                if transport == "mobile-data" {
+                    break None;
+                }
+            };
+            let id = tokio::task::id();
+            self.running.lock().unwrap().retain(|_, task| task.id() != id);
+            if let Some(e) = stopped {
+                let _ = tx.send(Err(format!("{} receiver stopped: {}", transport_name(transport), e)));
+            }
+        });
+        running.insert(transport, task.abort_handle());
+    }
+
+    /// Waits for the next file from any receiver. Fails once every receiver
+    /// has stopped.
+    async fn next(&self) -> Result<(&'static str, ReceivedFile), String> {
+        let mut rx = self.rx.lock().await;
+        loop {
+            match rx.recv().await.ok_or("receivers gone")? {
+                Ok(done) => return Ok(done),
+                Err(e) if self.running.lock().unwrap().is_empty() => return Err(e),
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+}
+
+/// Runs `transport`'s receiver for one file.
+async fn receive_one(transport: &str) -> Result<ReceivedFile, Box<dyn std::error::Error>> {
+    match transport {
+        "wifi-direct" => wifi_direct::start_receiver().await,
+        "webrtc" => webrtc::start_receiver().await,
+        "bluetooth" => bluetooth::start_receiver().await,
+        "mobile-data" => mobiledata::start_receiver().await,
+        other => Err(format!("Unknown transport '{}'", other).into()),
+    }
+}
+
+fn transport_name(transport: &str) -> &'static str {
+    match transport {
+        "wifi-direct" => "Wi‑Fi Direct",
+        "webrtc" => "WebRTC",
+        "bluetooth" => "Bluetooth",
+        "mobile-data" => "Mobile Data",
+        _ => "Unknown transport",
+    }
+}
+
+/// Receives one file, returning how and where. The receivers it starts keep
+/// running in the background (see [`stop_receivers`]).
+async fn receive_on_best() -> Result<(String, ReceivedFile), Box<dyn std::error::Error>> {
+    let wifi_direct = wifi_direct::is_available().await;
+    let bluetooth = bluetooth::is_available().await;
+    let transports = receivers_for(wifi_direct, bluetooth, watcher().internet_likely())?;
+    println!(
+        "Starting {} receivers.",
+        transports.iter().map(|t| transport_name(t)).collect::<Vec<_>>().join(" and ")
+    );
+    let receivers = receivers();
+    for transport in transports {
+        receivers.ensure(transport);
+    }
+    let (transport, file) = receivers.next().await?;
+    Ok((format!("Receiver started using {}", transport_name(transport)), file))
+}
+
+/// The receivers to start, best first. WebRTC runs alongside a local
+/// transport so senders can pick either; the relay is the last resort,
+/// unless we already know we're offline.
+fn receivers_for(wifi_direct: bool, bluetooth: bool, internet_likely: bool) -> Result<&'static [&'static str], &'static str> {
+    if wifi_direct {
+        Ok(&["wifi-direct", "webrtc"])
+    } else if bluetooth {
+        Ok(&["bluetooth", "webrtc"])
+    } else if internet_likely {
+        Ok(&["mobile-data"])
+    } else {
+        Err("No receiver available: Bluetooth is off and there's no internet connection for the relay.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_the_relay_without_local_transports() {
+        assert_eq!(receivers_for(true, true, true), Ok(&["wifi-direct", "webrtc"][..]));
+        assert_eq!(receivers_for(false, true, true), Ok(&["bluetooth", "webrtc"][..]));
+        assert_eq!(receivers_for(false, true, false), Ok(&["bluetooth", "webrtc"][..]));
+        assert_eq!(receivers_for(false, false, true), Ok(&["mobile-data"][..]));
+        assert!(receivers_for(false, false, false).is_err());
+    }
+}
@@ -0,0 +1,1002 @@
+use std::error::Error;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, watch, Notify};
+use tokio::time::{sleep, timeout, Duration};
+
+use webrtc::data_channel::data_channel_message::DataChannelMessage;
+use webrtc::data_channel::data_channel_state::RTCDataChannelState;
+use webrtc::data_channel::RTCDataChannel;
+use webrtc::peer_connection::offer_answer_options::RTCOfferOptions;
+use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use webrtc::peer_connection::RTCPeerConnection;
+
+use crate::webrtc_transfer::{new_session, set_local_and_gather, WebRtcSession};
+use crate::protocols::{approval, capabilities, framing};
+use crate::protocols::approval::Admission;
+use crate::protocols::filename::{self, ReceivedFile};
+use crate::protocols::metadata::{self, FileMetadata};
+
+/// TCP port the receiver listens on for the offer/answer exchange.
+pub const SIGNALING_PORT: u16 = 9002;
+
+/// Size of each file chunk written to the data channel.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// Every chunk starts with its file offset (8 bytes, big‑endian).
+const OFFSET_LEN: usize = 8;
+
+/// The receiver acknowledges progress every time this many bytes are on disk.
+const ACK_INTERVAL: u64 = 1024 * 1024;
+
+/// Sending pauses while more than this many bytes are queued on the data channel.
+const MAX_BUFFERED_AMOUNT: usize = 1024 * 1024;
+
+/// Upper bound for a single signaling message, to reject garbage early.
+const MAX_SIGNAL_LEN: usize = 64 * 1024;
+
+/// How long to wait for the peer connection and data channel to come up.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long either side waits for progress before giving up. This has to
+/// outlast ICE failure detection plus a restart.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long a disconnected connection may try to heal by itself before the
+/// sender restarts ICE.
+const DISCONNECT_GRACE: Duration = Duration::from_secs(3);
+
+/// Give up after this many ICE restart attempts within one transfer, whether
+/// they succeeded or not.
+const MAX_ICE_RESTARTS: u32 = 5;
+
+/// Wait before retrying a failed ICE restart, doubled after every failure.
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+
+/// How long the receiver lingers after confirming, in case the confirmation
+/// was lost and the sender resumes.
+const CONFIRM_GRACE: Duration = Duration::from_secs(10);
+
+/// Messages on the signaling connection. Every offer names its transfer so an
+/// ICE restart arriving on a new connection reaches the right session.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Signal {
+    Offer {
+        transfer_id: String,
+        sdp: RTCSessionDescription,
+    },
+    Answer {
+        sdp: RTCSessionDescription,
+    },
+}
+
+/// Text messages on the data channel. File contents travel as binary chunks.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Control {
+    /// Sender → receiver, before the first chunk. Older senders don't
+    /// include metadata.
+    Header {
+        name: String,
+        size: u64,
+        #[serde(default)]
+        metadata: FileMetadata,
+    },
+    /// Sender → receiver, after an ICE restart: chunks restart at `offset`.
+    Resume { offset: u64 },
+    /// Receiver → sender: everything before `offset` is written to disk.
+    Ack { offset: u64 },
+    /// Receiver → sender: the whole file is written to disk.
+    Done,
+    /// Sender → receiver: confirmation seen, the session can end.
+    Bye,
+}
+
+/// Sender-side view of what the receiver has confirmed so far.
+#[derive(Default)]
+struct SendProgress {
+    acked: AtomicU64,
+    done: Notify,
+    buffer_low: Notify,
+}
+
+/// Checks for WebRTC connectivity by probing the receiver's signaling port.
+///
+/// Unlike a constant, this only reports `true` when a WebRTC receiver is
+/// actually running at `destination`.
+pub async fn is_available(destination: &str) -> bool {
+    let addr = format!("{}:{}", destination, SIGNALING_PORT);
+    matches!(
+        timeout(Duration::from_secs(1), TcpStream::connect(addr)).await,
+        Ok(Ok(_))
+    )
+}
+
+/// Sends a file over a WebRTC data channel.
+///
+/// - Exchanges SDP offer/answer with the receiver over its signaling port.
+/// - Sends a header with the file name, size and the metadata
+///   [`metadata::policy`] keeps, then offset-tagged chunks.
+/// - If the connection drops (e.g. after switching networks), restarts ICE
+///   through the signaling port and resumes from the last acknowledged byte.
+/// - Waits for the receiver to confirm that every byte was written.
+pub async fn send_file(file_path: &str, destination: &str) -> Result<(), Box<dyn Error>> {
+    let outgoing = OutgoingFile::open(file_path).await?;
+    let session = new_session("sender").await?;
+    let mut channel = SendChannel::create(&session.pc).await?;
+    let signaling = format!("{}:{}", destination, SIGNALING_PORT);
+
+    // Errors are flattened to strings so the peer connection can be closed
+    // on every path without holding a non-Send error across an await.
+    let outcome = async {
+        let offer = session.pc.create_offer(None).await?;
+        exchange_offer(&session, &signaling, offer).await?;
+        println!("Negotiated WebRTC session with {}.", destination);
+        channel.send(&session, Some(&signaling), outgoing).await
+    }
+    .await
+    .map_err(|e| e.to_string());
+
+    session.pc.close().await?;
+    outcome?;
+    println!("File sent successfully via WebRTC.");
+    Ok(())
+}
+
+/// Starts a receiver that waits for a WebRTC sender on the signaling port.
+///
+/// Connections that go away without completing a transfer (for example the
+/// sender's availability probe) are logged and the receiver keeps listening.
+/// While a transfer runs, offers carrying its transfer ID are ICE restarts
+/// and are handed to the running session; once it has ended, they're turned
+/// away so the sender gives up. Files wait for [`approval::gate`] before
+/// anything is written. Returns once a file has arrived.
+pub async fn start_receiver() -> Result<ReceivedFile, Box<dyn Error>> {
+    let listener = TcpListener::bind(("0.0.0.0", SIGNALING_PORT)).await?;
+    println!("WebRTC receiver listening for signaling on port {}...", SIGNALING_PORT);
+    let _receiving = capabilities::mark_receiving("webrtc");
+    let mut ended: Option<String> = None;
+
+    loop {
+        let (mut socket, addr) = listener.accept().await?;
+        let (transfer_id, offer) = match read_offer(&mut socket).await {
+            Ok((id, _)) if ended.as_ref() == Some(&id) => {
+                println!("Turning away transfer {} from {}: it has ended", id, addr);
+                continue;
+            }
+            Ok(offered) => offered,
+            Err(e) => {
+                println!("Ignoring signaling connection from {}: {}", addr, e);
+                continue;
+            }
+        };
+        println!("WebRTC transfer {} offered by {}", transfer_id, addr);
+
+        let (restart_tx, mut restart_rx) = mpsc::channel(4);
+        // The error is stringified so the select below stays Send while the
+        // accept branch awaits.
+        let session = async {
+            receive_session(socket, offer, addr.ip().to_string(), &mut restart_rx)
+                .await
+                .map_err(|e| e.to_string())
+        };
+        tokio::pin!(session);
+
+        loop {
+            tokio::select! {
+                res = &mut session => {
+                    match res {
+                        Ok(received) => {
+                            println!(
+                                "File '{}' received via WebRTC and saved as {}",
+                                received.original_name,
+                                received.path.display()
+                            );
+                            return Ok(received);
+                        }
+                        Err(e) => {
+                            println!("WebRTC transfer {} ended without a file: {}", transfer_id, e);
+                            ended = Some(transfer_id);
+                            break;
+                        }
+                    }
+                }
+                accepted = listener.accept() => {
+                    let (mut socket, addr) = accepted?;
+                    let offered = read_offer(&mut socket).await.map_err(|e| e.to_string());
+                    match offered {
+                        Ok((id, offer)) if id == transfer_id => {
+                            let _ = restart_tx.send((offer, socket)).await;
+                        }
+                        Ok((id, _)) => println!(
+                            "Rejecting transfer {} from {}: another transfer is in progress",
+                            id, addr
+                        ),
+                        Err(e) => println!("Ignoring signaling connection from {}: {}", addr, e),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Answers the initial offer, then writes the incoming file to disk while
+/// answering any ICE restart offers for the same transfer.
+async fn receive_session(
+    mut signaling: TcpStream,
+    offer: RTCSessionDescription,
+    from: String,
+    restarts: &mut mpsc::Receiver<(RTCSessionDescription, TcpStream)>,
+) -> Result<ReceivedFile, Box<dyn Error>> {
+    let session = new_session("receiver").await?;
+    let pc = session.pc.clone();
+    let channels = accept_data_channels(&pc);
+
+    let outcome = async {
+        answer_offer(&pc, offer, &mut signaling).await?;
+        drop(signaling);
+        receive_file(&pc, channels, CONNECT_TIMEOUT, from, restarts).await
+    }
+    .await
+    .map_err(|e| e.to_string());
+
+    pc.close().await?;
+    Ok(outcome?)
+}
+
+/// Writes the file arriving on the sender's data channels (see
+/// [`accept_data_channels`]) to disk and applies its metadata, answering ICE
+/// restart offers that arrive on `restarts` along the way. The first data
+/// channel has to open within `open_within`.
+pub(crate) async fn receive_file(
+    pc: &RTCPeerConnection,
+    (mut dc_rx, mut msg_rx): (mpsc::Receiver<Arc<RTCDataChannel>>, mpsc::Receiver<DataChannelMessage>),
+    open_within: Duration,
+    from: String,
+    restarts: &mut mpsc::Receiver<(RTCSessionDescription, TcpStream)>,
+) -> Result<ReceivedFile, Box<dyn Error>> {
+    let mut dc = timeout(open_within, dc_rx.recv())
+        .await?
+        .ok_or("peer closed before opening a data channel")?;
+    let mut incoming = IncomingFile::new(from);
+
+    loop {
+        let wait = if incoming.is_complete() { CONFIRM_GRACE } else { IDLE_TIMEOUT };
+        tokio::select! {
+            Some(new_dc) = dc_rx.recv() => dc = new_dc,
+            Some((offer, mut socket)) = restarts.recv() => {
+                println!("Sender requested an ICE restart.");
+                answer_offer(pc, offer, &mut socket).await?;
+            }
+            msg = timeout(wait, msg_rx.recv()) => {
+                let msg = match msg {
+                    Ok(msg) => msg.ok_or("data channel closed mid-transfer")?,
+                    // Confirmed and nothing more from the sender: we're done.
+                    Err(_) if incoming.is_complete() => break,
+                    Err(elapsed) => return Err(elapsed.into()),
+                };
+                if incoming.handle(&dc, msg).await? {
+                    break;
+                }
+            }
+        }
+    }
+    // Closed first, so nothing written afterwards touches the times.
+    let received = incoming.finish().ok_or("session ended before the file header")?;
+    metadata::policy().apply(&incoming.metadata, &received.path);
+    if let Some(admission) = incoming.admission.take() {
+        admission.done(&received);
+    }
+    Ok(received)
+}
+
+/// Queues the data channels the sender opens, and the messages on all of
+/// them. After an ICE restart the sender may open a fresh data channel; all
+/// of them feed the same message queue.
+pub(crate) fn accept_data_channels(
+    pc: &RTCPeerConnection,
+) -> (mpsc::Receiver<Arc<RTCDataChannel>>, mpsc::Receiver<DataChannelMessage>) {
+    let (dc_tx, dc_rx) = mpsc::channel::<Arc<RTCDataChannel>>(4);
+    let (msg_tx, msg_rx) = mpsc::channel::<DataChannelMessage>(64);
+    pc.on_data_channel(Box::new(move |dc| {
+        let dc_tx = dc_tx.clone();
+        let msg_tx = msg_tx.clone();
+        Box::pin(async move {
+            dc.on_message(Box::new(move |msg| {
+                let msg_tx = msg_tx.clone();
+                Box::pin(async move {
+                    let _ = msg_tx.send(msg).await;
+                })
+            }));
+            let _ = dc_tx.send(dc).await;
+        })
+    }));
+    (dc_rx, msg_rx)
+}
+
+/// Receiver-side state of the file being written. Dropping it before
+/// [`IncomingFile::finish`] deletes what was written so far.
+struct IncomingFile {
+    /// The sender's address, for the approval prompt.
+    from: String,
+    admission: Option<Admission>,
+    file: Option<File>,
+    /// Where the sender's file name led, once the header arrived.
+    received: Option<ReceivedFile>,
+    metadata: FileMetadata,
+    size: u64,
+    written: u64,
+    acked: u64,
+}
+
+impl IncomingFile {
+    fn new(from: String) -> Self {
+        IncomingFile {
+            from,
+            admission: None,
+            file: None,
+            received: None,
+            metadata: FileMetadata::default(),
+            size: 0,
+            written: 0,
+            acked: 0,
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.file.is_some() && self.written == self.size
+    }
+
+    /// Closes the file and hands it over, so it's kept.
+    fn finish(&mut self) -> Option<ReceivedFile> {
+        self.file = None;
+        self.received.take()
+    }
+
+    /// Handles one data channel message. Returns `true` once the sender has
+    /// seen our confirmation and the session can end.
+    async fn handle(
+        &mut self,
+        dc: &Arc<RTCDataChannel>,
+        msg: DataChannelMessage,
+    ) -> Result<bool, Box<dyn Error>> {
+        if msg.is_string {
+            match serde_json::from_slice::<Control>(&msg.data)? {
+                Control::Header { name, size, metadata } if self.file.is_none() => {
+                    if size > framing::max_file_bytes() {
+                        return Err(format!("Refusing '{}': {} bytes is more than the {} byte limit", name, size, framing::max_file_bytes()).into());
+                    }
+                    self.admission = Some(approval::gate().admit(&name, size, &self.from, "webrtc").await?);
+                    println!("Receiving '{}' ({} bytes) via WebRTC.", name, size);
+                    let received = self.received.insert(filename::policy().reserve(&name)?);
+                    self.file = Some(File::create(&received.path).await?);
+                    self.metadata = metadata;
+                    self.size = size;
+                    if size == 0 {
+                        send_control(dc, &Control::Done).await;
+                    }
+                }
+                Control::Resume { offset } => {
+                    println!("Sender resumed from byte {}.", offset);
+                    // The sender only resumes at offsets we acknowledged, so
+                    // re-confirm in case the last ack or Done went missing.
+                    send_control(dc, &Control::Ack { offset: self.written }).await;
+                    if self.is_complete() {
+                        send_control(dc, &Control::Done).await;
+                    }
+                }
+                Control::Bye => return Ok(self.is_complete()),
+                _ => {}
+            }
+            return Ok(false);
+        }
+
+        let file = self.file.as_mut().ok_or("received file data before the header")?;
+        if msg.data.len() < OFFSET_LEN {
+            return Err("malformed chunk".into());
+        }
+        let (prefix, payload) = msg.data.split_at(OFFSET_LEN);
+        let offset = u64::from_be_bytes(prefix.try_into()?);
+        if offset > self.written {
+            return Err(format!("missing data between bytes {} and {}", self.written, offset).into());
+        }
+
+        // After a resume the sender may repeat bytes we already have.
+        let skip = ((self.written - offset) as usize).min(payload.len());
+        let fresh = &payload[skip..];
+        if self.written + fresh.len() as u64 > self.size {
+            return Err(format!("sender sent more than the {} bytes it announced", self.size).into());
+        }
+        file.write_all(fresh).await?;
+        self.written += fresh.len() as u64;
+
+        if self.written - self.acked >= ACK_INTERVAL || self.written == self.size {
+            file.flush().await?;
+            self.acked = self.written;
+            send_control(dc, &Control::Ack { offset: self.acked }).await;
+            if self.written == self.size {
+                send_control(dc, &Control::Done).await;
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl Drop for IncomingFile {
+    fn drop(&mut self) {
+        if let Some(received) = self.received.take() {
+            self.file = None;
+            println!("Deleting unfinished {}", received.path.display());
+            let _ = std::fs::remove_file(&received.path);
+        }
+    }
+}
+
+/// Sends a control message, ignoring failures: a lost ack only means the
+/// sender resumes from an earlier offset.
+async fn send_control(dc: &RTCDataChannel, control: &Control) {
+    if let Ok(text) = serde_json::to_string(control) {
+        let _ = dc.send_text(text).await;
+    }
+}
+
+/// A file about to be sent: the open file and what its header announces.
+pub(crate) struct OutgoingFile {
+    file: File,
+    name: String,
+    size: u64,
+    metadata: FileMetadata,
+}
+
+impl OutgoingFile {
+    /// Opens `file_path` and reads the metadata [`metadata::policy`] keeps.
+    pub(crate) async fn open(file_path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(file_path).await?;
+        let name = Path::new(file_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let size = file.metadata().await?.len();
+        let metadata = metadata::policy().read(Path::new(file_path))?;
+        Ok(OutgoingFile { file, name, size, metadata })
+    }
+}
+
+/// The sender's file-transfer data channel and what the receiver has
+/// confirmed on it.
+pub(crate) struct SendChannel {
+    dc: Arc<RTCDataChannel>,
+    progress: Arc<SendProgress>,
+    opened: mpsc::Receiver<()>,
+}
+
+impl SendChannel {
+    /// Creates the data channel on `pc`. It opens once the peers connect.
+    pub(crate) async fn create(pc: &RTCPeerConnection) -> Result<Self, Box<dyn Error>> {
+        let progress = Arc::new(SendProgress::default());
+        let (dc, opened) = open_data_channel(pc, &progress).await?;
+        Ok(SendChannel { dc, progress, opened })
+    }
+
+    /// Waits for the channel to open, sends the header, then streams the file
+    /// until the receiver confirms it (see [`stream_file`]).
+    pub(crate) async fn send(
+        &mut self,
+        session: &WebRtcSession,
+        signaling: Option<&str>,
+        outgoing: OutgoingFile,
+    ) -> Result<(), Box<dyn Error>> {
+        timeout(CONNECT_TIMEOUT, self.opened.recv())
+            .await?
+            .ok_or("data channel closed before opening")?;
+        let OutgoingFile { mut file, name, size, metadata } = outgoing;
+        println!("WebRTC data channel open. Sending {} bytes...", size);
+
+        self.dc
+            .send_text(serde_json::to_string(&Control::Header { name, size, metadata })?)
+            .await?;
+        stream_file(session, signaling, &mut self.dc, &self.progress, &mut file, size).await?;
+
+        // The receiver only needs this to stop waiting, so losing it is harmless.
+        let _ = self.dc.send_text(serde_json::to_string(&Control::Bye)?).await;
+        Ok(())
+    }
+}
+
+/// Creates the file-transfer data channel and wires its acks, confirmation
+/// and buffer notifications into `progress`.
+async fn open_data_channel(
+    pc: &RTCPeerConnection,
+    progress: &Arc<SendProgress>,
+) -> Result<(Arc<RTCDataChannel>, mpsc::Receiver<()>), Box<dyn Error>> {
+    let dc = pc.create_data_channel("file-transfer", None).await?;
+
+    let (open_tx, open_rx) = mpsc::channel::<()>(1);
+    dc.on_open(Box::new(move || {
+        Box::pin(async move {
+            let _ = open_tx.send(()).await;
+        })
+    }));
+
+    let on_message = progress.clone();
+    dc.on_message(Box::new(move |msg| {
+        if msg.is_string {
+            match serde_json::from_slice::<Control>(&msg.data) {
+                Ok(Control::Ack { offset }) => {
+                    on_message.acked.fetch_max(offset, Ordering::SeqCst);
+                }
+                Ok(Control::Done) => on_message.done.notify_one(),
+                _ => {}
+            }
+        }
+        Box::pin(async {})
+    }));
+
+    dc.set_buffered_amount_low_threshold(MAX_BUFFERED_AMOUNT / 2).await;
+    let on_low = progress.clone();
+    dc.on_buffered_amount_low(Box::new(move || {
+        on_low.buffer_low.notify_one();
+        Box::pin(async {})
+    }))
+    .await;
+
+    Ok((dc, open_rx))
+}
+
+/// Streams offset-tagged chunks until the receiver confirms the whole file,
+/// resuming from the last acknowledged byte whenever the connection drops.
+/// ICE is restarted through the receiver's `signaling` address; without one
+/// (manually signaled sessions), a drop can only heal by itself.
+async fn stream_file(
+    session: &WebRtcSession,
+    signaling: Option<&str>,
+    dc: &mut Arc<RTCDataChannel>,
+    progress: &Arc<SendProgress>,
+    file: &mut File,
+    size: u64,
+) -> Result<(), Box<dyn Error>> {
+    let mut state = session.watch_state();
+    let mut offset = 0u64;
+    let mut restarts = 0;
+    let mut buf = vec![0u8; OFFSET_LEN + CHUNK_SIZE];
+
+    loop {
+        let current = *state.borrow_and_update();
+        if matches!(
+            current,
+            RTCPeerConnectionState::Disconnected | RTCPeerConnectionState::Failed
+        ) {
+            reconnect(session, signaling, &mut state, &mut restarts).await?;
+
+            if dc.ready_state() != RTCDataChannelState::Open {
+                let (new_dc, mut open_rx) = open_data_channel(&session.pc, progress).await?;
+                timeout(CONNECT_TIMEOUT, open_rx.recv())
+                    .await?
+                    .ok_or("data channel closed before opening")?;
+                *dc = new_dc;
+            }
+
+            offset = progress.acked.load(Ordering::SeqCst);
+            file.seek(SeekFrom::Start(offset)).await?;
+            println!("Resuming WebRTC transfer from byte {}.", offset);
+            dc.send_text(serde_json::to_string(&Control::Resume { offset })?)
+                .await?;
+            continue;
+        }
+
+        if offset >= size {
+            tokio::select! {
+                _ = progress.done.notified() => return Ok(()),
+                changed = state.changed() => changed?,
+                _ = sleep(IDLE_TIMEOUT) => return Err("timed out waiting for the receiver to confirm".into()),
+            }
+            continue;
+        }
+
+        if dc.buffered_amount().await > MAX_BUFFERED_AMOUNT {
+            tokio::select! {
+                _ = progress.buffer_low.notified() => {}
+                changed = state.changed() => changed?,
+                _ = sleep(IDLE_TIMEOUT) => return Err("timed out waiting for the data channel to drain".into()),
+            }
+            continue;
+        }
+
+        let n = file.read(&mut buf[OFFSET_LEN..]).await?;
+        if n == 0 {
+            return Err("file is shorter than when the transfer started".into());
+        }
+        buf[..OFFSET_LEN].copy_from_slice(&offset.to_be_bytes());
+
+        if let Err(e) = dc.send(&Bytes::copy_from_slice(&buf[..OFFSET_LEN + n])).await {
+            // If the connection is going down, the next pass restarts ICE
+            // and rewinds to the last acknowledged offset.
+            if session.pc.connection_state() == RTCPeerConnectionState::Connected {
+                return Err(e.into());
+            }
+            timeout(IDLE_TIMEOUT, state.changed()).await??;
+            continue;
+        }
+        offset += n as u64;
+    }
+}
+
+/// Keeps trying to [`recover`] a dropped connection, backing off after every
+/// failed attempt. Every ICE restart, failed or not, counts towards
+/// [`MAX_ICE_RESTARTS`] in `restarts`; the transfer is given up once they're
+/// spent.
+async fn reconnect(
+    session: &WebRtcSession,
+    signaling: Option<&str>,
+    state: &mut watch::Receiver<RTCPeerConnectionState>,
+    restarts: &mut u32,
+) -> Result<(), Box<dyn Error>> {
+    let mut backoff = RESTART_BACKOFF;
+    loop {
+        if *restarts == MAX_ICE_RESTARTS {
+            return Err(format!("connection {} after {} ICE restarts", *state.borrow(), restarts).into());
+        }
+        // Stringified so no error is held across the backoff below.
+        let failure = match recover(session, signaling, state).await.map_err(|e| e.to_string()) {
+            Ok(false) => return Ok(()),
+            Ok(true) => {
+                *restarts += 1;
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+        *restarts += 1;
+        if *restarts == MAX_ICE_RESTARTS {
+            return Err(format!("giving up after {} ICE restarts: {}", restarts, failure).into());
+        }
+        println!("ICE restart failed: {}; retrying in {:?}...", failure, backoff);
+        sleep(backoff).await;
+        backoff *= 2;
+    }
+}
+
+/// Brings a dropped connection back. A disconnect gets a short grace period
+/// to heal by itself; otherwise ICE is restarted through the receiver's
+/// `signaling` address, if there is one. Returns whether an ICE restart was
+/// needed.
+async fn recover(
+    session: &WebRtcSession,
+    signaling: Option<&str>,
+    state: &mut watch::Receiver<RTCPeerConnectionState>,
+) -> Result<bool, Box<dyn Error>> {
+    let current = *state.borrow();
+    if current == RTCPeerConnectionState::Connected {
+        // Healed while we were backing off.
+        return Ok(false);
+    }
+    if current == RTCPeerConnectionState::Disconnected
+        && timeout(
+            DISCONNECT_GRACE,
+            state.wait_for(|s| *s == RTCPeerConnectionState::Connected),
+        )
+        .await
+        .is_ok()
+    {
+        return Ok(false);
+    }
+
+    let signaling = signaling
+        .ok_or_else(|| format!("connection {} and no signaling channel to restart ICE", current))?;
+    println!("WebRTC connection {}; restarting ICE...", current);
+    let offer = session
+        .pc
+        .create_offer(Some(RTCOfferOptions {
+            ice_restart: true,
+            ..Default::default()
+        }))
+        .await?;
+    exchange_offer(session, signaling, offer).await?;
+
+    // The state may still read "failed" from before the restart, so wait
+    // for "connected" specifically.
+    timeout(
+        CONNECT_TIMEOUT,
+        state.wait_for(|s| *s == RTCPeerConnectionState::Connected),
+    )
+    .await
+    .map_err(|_| "timed out reconnecting after ICE restart")?
+    .map(|_| ())?;
+    println!("ICE restart succeeded.");
+    Ok(true)
+}
+
+/// Sends `offer` for this session over a fresh connection to the receiver's
+/// `signaling` address and applies the receiver's answer. Connects first, so
+/// an unreachable receiver fails before any ICE gathering.
+async fn exchange_offer(
+    session: &WebRtcSession,
+    signaling: &str,
+    offer: RTCSessionDescription,
+) -> Result<(), Box<dyn Error>> {
+    let mut signaling = timeout(CONNECT_TIMEOUT, TcpStream::connect(signaling)).await??;
+    let offer = set_local_and_gather(&session.pc, offer).await?;
+
+    let signal = Signal::Offer {
+        transfer_id: session.id.clone(),
+        sdp: offer,
+    };
+    write_signal(&mut signaling, &signal).await?;
+
+    let Signal::Answer { sdp } = read_signal(&mut signaling).await? else {
+        return Err("expected an answer from the receiver".into());
+    };
+    session.pc.set_remote_description(sdp).await?;
+    Ok(())
+}
+
+/// Applies a remote offer (initial or ICE restart) and writes back the answer.
+async fn answer_offer(
+    pc: &RTCPeerConnection,
+    offer: RTCSessionDescription,
+    signaling: &mut TcpStream,
+) -> Result<(), Box<dyn Error>> {
+    pc.set_remote_description(offer).await?;
+    let answer = pc.create_answer(None).await?;
+    let answer = set_local_and_gather(pc, answer).await?;
+    write_signal(signaling, &Signal::Answer { sdp: answer }).await
+}
+
+/// Reads the offer that opens every signaling connection.
+async fn read_offer(stream: &mut TcpStream) -> Result<(String, RTCSessionDescription), Box<dyn Error>> {
+    match timeout(Duration::from_secs(5), read_signal(stream)).await?? {
+        Signal::Offer { transfer_id, sdp } => Ok((transfer_id, sdp)),
+        Signal::Answer { .. } => Err("expected an offer".into()),
+    }
+}
+
+/// Writes a signaling message as length‑prefixed (4 bytes, big‑endian) JSON.
+async fn write_signal(stream: &mut TcpStream, signal: &Signal) -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_vec(signal)?;
+    stream.write_all(&(json.len() as u32).to_be_bytes()).await?;
+    stream.write_all(&json).await?;
+    Ok(())
+}
+
+/// Reads a signaling message written by [`write_signal`].
+async fn read_signal(stream: &mut TcpStream) -> Result<Signal, Box<dyn Error>> {
+    let mut len_buf = [0u8; 4];
+    stream.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    if len > MAX_SIGNAL_LEN {
+        return Err(format!("signaling message too large ({} bytes)", len).into());
+    }
+
+    let mut json = vec![0u8; len];
+    stream.read_exact(&mut json).await?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    /// A sender and receiver connected in-process: the sender's data channel
+    /// is open and the receiver's messages are queued.
+    struct Pair {
+        sender: WebRtcSession,
+        receiver: WebRtcSession,
+        dc: Arc<RTCDataChannel>,
+        progress: Arc<SendProgress>,
+        incoming_dc: Arc<RTCDataChannel>,
+        messages: mpsc::Receiver<DataChannelMessage>,
+    }
+
+    impl Pair {
+        async fn connect() -> Pair {
+            let sender = new_session("sender").await.unwrap();
+            let receiver = new_session("receiver").await.unwrap();
+            let (mut dc_rx, messages) = accept_data_channels(&receiver.pc);
+            let progress = Arc::new(SendProgress::default());
+            let (dc, mut open_rx) = open_data_channel(&sender.pc, &progress).await.unwrap();
+
+            negotiate(&sender, &receiver, None).await;
+            timeout(CONNECT_TIMEOUT, open_rx.recv()).await.unwrap().unwrap();
+            let incoming_dc = timeout(CONNECT_TIMEOUT, dc_rx.recv()).await.unwrap().unwrap();
+            Pair { sender, receiver, dc, progress, incoming_dc, messages }
+        }
+
+        async fn send_chunk(&self, offset: u64, payload: &[u8]) {
+            let mut chunk = offset.to_be_bytes().to_vec();
+            chunk.extend_from_slice(payload);
+            self.dc.send(&Bytes::from(chunk)).await.unwrap();
+        }
+
+        async fn close(self) {
+            let _ = self.sender.pc.close().await;
+            let _ = self.receiver.pc.close().await;
+        }
+    }
+
+    /// Hands the next queued message to `incoming`, as `receive_session` does.
+    async fn deliver(
+        messages: &mut mpsc::Receiver<DataChannelMessage>,
+        dc: &Arc<RTCDataChannel>,
+        incoming: &mut IncomingFile,
+    ) -> Result<bool, String> {
+        let msg = timeout(CONNECT_TIMEOUT, messages.recv()).await.unwrap().unwrap();
+        incoming.handle(dc, msg).await.map_err(|e| e.to_string())
+    }
+
+    /// Runs an offer/answer round from `offerer` to `answerer` without a
+    /// signaling connection.
+    async fn negotiate(offerer: &WebRtcSession, answerer: &WebRtcSession, options: Option<RTCOfferOptions>) {
+        let offer = offerer.pc.create_offer(options).await.unwrap();
+        let offer = set_local_and_gather(&offerer.pc, offer).await.unwrap();
+        answerer.pc.set_remote_description(offer).await.unwrap();
+        let answer = answerer.pc.create_answer(None).await.unwrap();
+        let answer = set_local_and_gather(&answerer.pc, answer).await.unwrap();
+        offerer.pc.set_remote_description(answer).await.unwrap();
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("unishare-webrtc-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("file.bin")
+    }
+
+    /// An incoming file whose header was already accepted.
+    async fn incoming(path: &Path, size: u64) -> IncomingFile {
+        let mut incoming = IncomingFile::new("127.0.0.1".to_string());
+        incoming.file = Some(File::create(path).await.unwrap());
+        incoming.received = Some(ReceivedFile {
+            original_name: "file.bin".to_string(),
+            path: path.to_path_buf(),
+            renamed: false,
+        });
+        incoming.size = size;
+        incoming
+    }
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    async fn wait_acked(progress: &SendProgress, offset: u64) {
+        timeout(CONNECT_TIMEOUT, async {
+            while progress.acked.load(Ordering::SeqCst) < offset {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn streams_offset_chunks_until_confirmed() {
+        let data = sample(2 * ACK_INTERVAL as usize + 5000);
+        let size = data.len() as u64;
+        let source = temp_path("stream-source");
+        std::fs::write(&source, &data).unwrap();
+        let target = temp_path("stream-target");
+
+        let Pair { sender, receiver, mut dc, progress, incoming_dc, mut messages } = Pair::connect().await;
+        let mut file = incoming(&target, size).await;
+        let mut sent = File::open(&source).await.unwrap();
+        let sending = async {
+            stream_file(&sender, None, &mut dc, &progress, &mut sent, size)
+                .await
+                .map_err(|e| e.to_string())?;
+            send_control(&dc, &Control::Bye).await;
+            Ok::<(), String>(())
+        };
+        let receiving = async {
+            while !deliver(&mut messages, &incoming_dc, &mut file).await? {}
+            Ok::<(), String>(())
+        };
+        let (sent_result, received_result) = tokio::join!(sending, receiving);
+        sent_result.unwrap();
+        received_result.unwrap();
+
+        assert_eq!(progress.acked.load(Ordering::SeqCst), size);
+        file.finish().unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), data);
+        let _ = sender.pc.close().await;
+        let _ = receiver.pc.close().await;
+    }
+
+    #[tokio::test]
+    async fn resumes_after_an_ice_restart_without_duplicating_bytes() {
+        let data = sample(3 * CHUNK_SIZE);
+        let target = temp_path("resume");
+        let mut pair = Pair::connect().await;
+        let mut file = incoming(&target, data.len() as u64).await;
+
+        pair.send_chunk(0, &data[..CHUNK_SIZE]).await;
+        pair.send_chunk(CHUNK_SIZE as u64, &data[CHUNK_SIZE..2 * CHUNK_SIZE]).await;
+        for _ in 0..2 {
+            assert!(!deliver(&mut pair.messages, &pair.incoming_dc, &mut file).await.unwrap());
+        }
+
+        negotiate(&pair.sender, &pair.receiver, Some(RTCOfferOptions { ice_restart: true, ..Default::default() })).await;
+        pair.sender.wait_connected(CONNECT_TIMEOUT).await.unwrap();
+
+        // The sender only knows about the first chunk, so it repeats the second.
+        pair.dc
+            .send_text(serde_json::to_string(&Control::Resume { offset: CHUNK_SIZE as u64 }).unwrap())
+            .await
+            .unwrap();
+        assert!(!deliver(&mut pair.messages, &pair.incoming_dc, &mut file).await.unwrap());
+        wait_acked(&pair.progress, 2 * CHUNK_SIZE as u64).await;
+
+        pair.send_chunk(CHUNK_SIZE as u64, &data[CHUNK_SIZE..]).await;
+        assert!(!deliver(&mut pair.messages, &pair.incoming_dc, &mut file).await.unwrap());
+        wait_acked(&pair.progress, data.len() as u64).await;
+        timeout(CONNECT_TIMEOUT, pair.progress.done.notified()).await.unwrap();
+
+        assert_eq!(file.written, data.len() as u64);
+        file.finish().unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), data);
+        pair.close().await;
+    }
+
+    #[tokio::test]
+    async fn retries_an_ice_restart_whose_signaling_was_refused() {
+        let pair = Pair::connect().await;
+        // Nobody listens here until the first attempt has been refused.
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let (state_tx, mut state) = watch::channel(RTCPeerConnectionState::Failed);
+
+        let receiver = pair.receiver.pc.clone();
+        let answering = tokio::spawn(async move {
+            sleep(RESTART_BACKOFF / 4).await;
+            let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let (_, offer) = read_offer(&mut socket).await.unwrap();
+            answer_offer(&receiver, offer, &mut socket).await.unwrap();
+            state_tx.send(RTCPeerConnectionState::Connected).unwrap();
+        });
+
+        let mut restarts = 0;
+        let signaling = format!("127.0.0.1:{}", port);
+        reconnect(&pair.sender, Some(&signaling), &mut state, &mut restarts).await.unwrap();
+        answering.await.unwrap();
+        assert_eq!(restarts, 2);
+        pair.close().await;
+    }
+
+    #[tokio::test]
+    async fn gives_up_once_the_ice_restarts_are_spent() {
+        let pair = Pair::connect().await;
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let (_state_tx, mut state) = watch::channel(RTCPeerConnectionState::Failed);
+
+        let mut restarts = MAX_ICE_RESTARTS - 1;
+        let signaling = format!("127.0.0.1:{}", port);
+        let err = reconnect(&pair.sender, Some(&signaling), &mut state, &mut restarts).await.unwrap_err();
+        assert!(err.to_string().contains("giving up"), "{}", err);
+        assert_eq!(restarts, MAX_ICE_RESTARTS);
+        pair.close().await;
+    }
+
+    #[tokio::test]
+    async fn refuses_more_data_than_the_header_announced() {
+        let target = temp_path("overflow");
+        let mut pair = Pair::connect().await;
+        let mut file = incoming(&target, 10).await;
+
+        pair.send_chunk(0, &sample(20)).await;
+        let err = deliver(&mut pair.messages, &pair.incoming_dc, &mut file).await.unwrap_err();
+        assert!(err.contains("more than the 10 bytes"), "{}", err);
+        assert_eq!(file.written, 0);
+        drop(file);
+        assert!(!target.exists(), "the unfinished file is deleted");
+        pair.close().await;
+    }
+}
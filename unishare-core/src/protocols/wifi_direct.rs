@@ -0,0 +1,78 @@
+use std::error::Error;
+use tokio::net::{TcpListener, TcpStream};
+use std::path::Path;
+use crate::protocols::{approval, capabilities, filename, metadata};
+#[cfg(unix)]
+use crate::protocols::p2p;
+use crate::tools::connectivity;
+use crate::protocols::filename::ReceivedFile;
+use crate::protocols::framing::StreamHeader;
+
+/// Whether Wi‑Fi Direct can be used: a group formed through [`p2p`] is up,
+/// or this device has a P2P-capable radio and a wpa_supplicant to drive it
+/// (see [`connectivity::check_wifi_direct`]). The radio check reads sysfs
+/// and runs `iw`, so it runs on the blocking pool.
+pub async fn is_available() -> bool {
+    #[cfg(unix)]
+    if p2p::active_group().is_some() {
+        return true;
+    }
+    tokio::task::spawn_blocking(|| connectivity::check_wifi_direct().unwrap_or(false))
+        .await
+        .unwrap_or(false)
+}
+
+/// Sends a file over TCP port 9000 to `destination`.
+///
+/// The transfer runs over whichever link reaches `destination`: the group
+/// interface when it's the peer's address on a Wi‑Fi Direct group formed
+/// through [`p2p`], otherwise the existing network.
+///
+/// - Sends a [`StreamHeader`] with the file name, size and metadata,
+///   followed by the file data, streamed from disk.
+pub async fn send_file(file_path: &str, destination: &str) -> Result<(), Box<dyn Error>> {
+    let header = StreamHeader::for_file(Path::new(file_path))?;
+
+    // Connect to the destination on port 9000.
+    let dest_addr = format!("{}:9000", destination);
+    let mut stream = TcpStream::connect(dest_addr).await?;
+    println!("Connected to destination. Sending file...");
+
+    header.write(&mut stream).await?;
+    header.write_file(&mut stream, Path::new(file_path)).await?;
+    println!("File sent successfully.");
+
+    Ok(())
+}
+
+/// Starts a receiver that listens on port 9000 for an incoming file transfer.
+///
+/// - Binds a TCP listener on port 9000 on every interface, including a
+///   Wi‑Fi Direct group interface.
+/// - Accepts an incoming connection.
+/// - Reads the [`StreamHeader`] and asks [`approval::gate`].
+/// - Streams the file data into the file [`filename::policy`] picks for the
+///   sender's name, applies its metadata, and returns where it went.
+pub async fn start_receiver() -> Result<ReceivedFile, Box<dyn Error>> {
+    // Bind a TCP listener on port 9000 (all interfaces).
+    let listener = TcpListener::bind("0.0.0.0:9000").await?;
+    println!("Receiver listening on port 9000...");
+    let _receiving = capabilities::mark_receiving("wifi-direct");
+    
+    // Accept an incoming connection.
+    let (mut socket, addr) = listener.accept().await?;
+    println!("Received connection from {}", addr);
+    
+    // Read the header.
+    let header = StreamHeader::read(&mut socket).await?;
+    let admission = approval::gate().admit(&header.name, header.size, &addr.ip().to_string(), "wifi-direct").await?;
+    
+    let received = filename::policy().reserve(&header.name)?;
+    header.read_file(&mut socket, &received.path).await?;
+    metadata::policy().apply(&header.metadata, &received.path);
+    
+    println!("File received and saved as {}", received.path.display());
+    admission.done(&received);
+    
+    Ok(received)
+}
@@ -0,0 +1,846 @@
+use std::net::IpAddr;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::internet::{self, InternetConfig, InternetStatus};
+
+// -----------------------
+// BLUETOOTH CHECK
+// -----------------------
+
+#[cfg(target_os = "windows")]
+pub fn check_bluetooth() -> Option<bool> {
+    use std::process::Command;
+    let output = Command::new("powershell")
+        .arg("-Command")
+        .arg("Get-PnpDevice -Class Bluetooth | Where-Object { $_.Status -eq 'OK' }")
+        .output()
+        .ok()?;
+
+    Some(!output.stdout.is_empty())
+}
+
+#[cfg(target_os = "macos")]
+pub fn check_bluetooth() -> Option<bool> {
+    use std::process::Command;
+    let output = Command::new("system_profiler")
+        .arg("SPBluetoothDataType")
+        .output()
+        .ok()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(stdout.contains("Bluetooth Power: On"))
+}
+
+#[cfg(target_os = "linux")]
+pub fn check_bluetooth() -> Option<bool> {
+    let report = linux::report(Path::new("/"), linux::wiphy_info);
+    Some(report.bluetooth.iter().any(|adapter| adapter.available))
+}
+
+// -----------------------
+// WIFI DIRECT CHECK
+// -----------------------
+
+#[cfg(target_os = "linux")]
+pub fn check_wifi_direct() -> Option<bool> {
+    let report = linux::report(Path::new("/"), linux::wiphy_info);
+    Some(report.wifi_direct)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn check_wifi_direct() -> Option<bool> {
+    // Wi-Fi Direct is not commonly supported on desktop OSes.
+    // You can optionally return `false` or use platform-specific tools.
+    Some(false) // Placeholder
+}
+
+// -----------------------
+// PER-ADAPTER REPORT
+// -----------------------
+
+/// A Bluetooth controller and whether it can be used.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BluetoothAdapter {
+    /// Kernel name, e.g. `hci0`.
+    pub name: String,
+    pub address: Option<String>,
+    pub soft_blocked: bool,
+    pub hard_blocked: bool,
+    /// Present and not blocked by any rfkill switch.
+    pub available: bool,
+}
+
+/// A Wi‑Fi radio (wiphy) with its network interfaces.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WifiAdapter {
+    /// Kernel name, e.g. `phy0`.
+    pub phy: String,
+    /// Network interfaces on this radio, e.g. `wlan0`.
+    pub interfaces: Vec<String>,
+    pub soft_blocked: bool,
+    pub hard_blocked: bool,
+    /// Supported interface modes as `iw` lists them, e.g. `managed`, `AP`, `P2P-GO`.
+    pub interface_modes: Vec<String>,
+    /// The radio can act as both P2P client and group owner.
+    pub p2p: bool,
+    pub available: bool,
+}
+
+/// Every radio adapter we found and whether Wi‑Fi Direct is usable.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AdapterReport {
+    pub bluetooth: Vec<BluetoothAdapter>,
+    pub wifi: Vec<WifiAdapter>,
+    /// An unblocked P2P-capable radio and a wpa_supplicant control socket to drive it.
+    pub wifi_direct: bool,
+}
+
+/// What `check_connectivity_status` returns.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectivityReport {
+    #[serde(flatten)]
+    pub adapters: AdapterReport,
+    pub internet: InternetStatus,
+}
+
+/// Inspects every adapter. Blocking: it reads sysfs and runs `iw`.
+#[cfg(target_os = "linux")]
+pub fn adapter_report() -> AdapterReport {
+    linux::report(Path::new("/"), linux::wiphy_info)
+}
+
+/// Inspects every adapter. Other platforms only tell us whether Bluetooth is
+/// on, so it is reported as a single adapter.
+#[cfg(not(target_os = "linux"))]
+pub fn adapter_report() -> AdapterReport {
+    let on = check_bluetooth().unwrap_or(false);
+    AdapterReport {
+        bluetooth: vec![BluetoothAdapter {
+            name: "bluetooth".to_string(),
+            address: None,
+            soft_blocked: !on,
+            hard_blocked: false,
+            available: on,
+        }],
+        wifi: Vec::new(),
+        wifi_direct: check_wifi_direct().unwrap_or(false),
+    }
+}
+
+/// Inspects the adapters and checks internet access at the same time.
+pub async fn connectivity_report(internet: &InternetConfig) -> Result<ConnectivityReport, String> {
+    let adapters = tokio::task::spawn_blocking(adapter_report);
+    let internet = internet::check(internet).await;
+    Ok(ConnectivityReport {
+        adapters: adapters.await.map_err(|e| e.to_string())?,
+        internet,
+    })
+}
+
+// -----------------------
+// NETWORK INTERFACES
+// -----------------------
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkKind {
+    Wired,
+    Wireless,
+    /// A Wi‑Fi Direct group interface.
+    P2p,
+    /// A phone sharing its connection over USB.
+    Tethered,
+    /// A mobile broadband modem.
+    Cellular,
+    Loopback,
+    /// Bridges, tunnels, container networks and other software interfaces.
+    Virtual,
+}
+
+impl LinkKind {
+    /// How much we'd rather reach a peer over this kind of link.
+    fn preference(self) -> u32 {
+        match self {
+            LinkKind::Wired => 4,
+            LinkKind::P2p => 3,
+            LinkKind::Wireless => 2,
+            LinkKind::Virtual => 1,
+            LinkKind::Tethered | LinkKind::Cellular | LinkKind::Loopback => 0,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InterfaceAddress {
+    pub address: IpAddr,
+    /// Network prefix length, e.g. 24 for a /24.
+    pub prefix: u8,
+}
+
+impl InterfaceAddress {
+    /// Whether `ip` is on this address's network.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip) {
+            (IpAddr::V4(own), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix.min(32))).unwrap_or(0);
+                u32::from(own) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(own), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix.min(128))).unwrap_or(0);
+                u128::from(own) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// One network interface and what it's connected through.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkInterface {
+    pub name: String,
+    pub kind: LinkKind,
+    pub mac: Option<String>,
+    pub addresses: Vec<InterfaceAddress>,
+    pub mtu: Option<u32>,
+    /// Negotiated link speed; wireless drivers usually don't report one.
+    pub speed_mbps: Option<u32>,
+    pub up: bool,
+    /// Our receivers listen on every IPv4 address, so peers can reach them
+    /// through any interface that is up and has one (loopback aside).
+    pub receivers_reachable: bool,
+}
+
+/// Every network interface with its addresses and link details.
+#[cfg(target_os = "linux")]
+pub fn network_interfaces() -> Vec<NetworkInterface> {
+    linux::interfaces(Path::new("/"), &linux::addresses())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn network_interfaces() -> Vec<NetworkInterface> {
+    Vec::new()
+}
+
+/// The interface whose network `ip` is on.
+pub fn interface_for(ip: IpAddr, interfaces: &[NetworkInterface]) -> Option<&NetworkInterface> {
+    interfaces
+        .iter()
+        .filter(|interface| interface.up && interface.kind != LinkKind::Loopback)
+        .find(|interface| interface.addresses.iter().any(|address| address.contains(ip)))
+}
+
+/// Ranks a peer address by the local link we'd reach it through: wired
+/// before Wi‑Fi Direct before Wi‑Fi, then faster links first. Addresses on
+/// no local network score zero.
+pub fn address_score(ip: IpAddr, interfaces: &[NetworkInterface]) -> u32 {
+    interface_for(ip, interfaces).map_or(0, |interface| {
+        let speed = interface.speed_mbps.unwrap_or(0).min(99_999);
+        (interface.kind.preference() + 1) * 100_000 + speed
+    })
+}
+
+#[cfg(target_os = "linux")]
+pub mod linux {
+    //! Adapter discovery from sysfs. Everything is read relative to a root
+    //! directory so tests can supply a fake tree.
+
+    use std::collections::HashMap;
+    use std::ffi::CStr;
+    use std::fs;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use super::{AdapterReport, BluetoothAdapter, InterfaceAddress, LinkKind, NetworkInterface, WifiAdapter};
+
+    /// One rfkill switch, as found under `/sys/class/rfkill`.
+    #[derive(Debug, Clone, PartialEq)]
+    struct Switch {
+        name: String,
+        /// `wlan`, `bluetooth`, `all`, ...
+        kind: String,
+        soft: bool,
+        hard: bool,
+    }
+
+    fn read(path: &Path) -> Option<String> {
+        fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+    }
+
+    fn read_switch(dir: &Path) -> Option<Switch> {
+        Some(Switch {
+            name: dir.file_name()?.to_string_lossy().into_owned(),
+            kind: read(&dir.join("type"))?,
+            soft: read(&dir.join("soft")).as_deref() == Some("1"),
+            hard: read(&dir.join("hard")).as_deref() == Some("1"),
+        })
+    }
+
+    /// Entries of `dir`, sorted by name.
+    fn entries(dir: &Path) -> Vec<(String, PathBuf)> {
+        let mut entries: Vec<(String, PathBuf)> = fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| (entry.file_name().to_string_lossy().into_owned(), entry.path()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.sort();
+        entries
+    }
+
+    /// The rfkill switches registered under an adapter's sysfs directory.
+    fn own_switches(device: &Path) -> Vec<Switch> {
+        entries(device)
+            .into_iter()
+            .filter(|(name, _)| name.starts_with("rfkill"))
+            .filter_map(|(_, path)| read_switch(&path))
+            .collect()
+    }
+
+    /// Combines an adapter's own switches with platform switches (laptop
+    /// keys and the like) that cover its kind of radio.
+    fn blocked(own: &[Switch], platform: &[Switch], kind: &str) -> (bool, bool) {
+        let relevant = own
+            .iter()
+            .chain(platform.iter().filter(|switch| switch.kind == kind || switch.kind == "all"));
+        relevant.fold((false, false), |(soft, hard), switch| (soft || switch.soft, hard || switch.hard))
+    }
+
+    /// Parses the `Supported interface modes:` list of `iw phy <phy> info`.
+    pub fn parse_interface_modes(info: &str) -> Vec<String> {
+        let mut lines = info.lines().skip_while(|line| line.trim() != "Supported interface modes:");
+        lines.next();
+        lines
+            .map_while(|line| line.trim().strip_prefix("* "))
+            .map(|mode| mode.trim().to_string())
+            .collect()
+    }
+
+    /// `iw phy <phy> info`, or `None` without `iw`.
+    pub fn wiphy_info(phy: &str) -> Option<String> {
+        let output = Command::new("iw").args(["phy", phy, "info"]).output().ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Builds the adapter report from the sysfs tree under `root`.
+    pub fn report(root: &Path, wiphy_info: impl Fn(&str) -> Option<String>) -> AdapterReport {
+        let sys = root.join("sys/class");
+        let bluetooth_dirs: Vec<(String, PathBuf)> = entries(&sys.join("bluetooth"))
+            .into_iter()
+            // `hci0:12` entries are connections, not controllers.
+            .filter(|(name, _)| name.starts_with("hci") && !name.contains(':'))
+            .collect();
+        let phy_dirs = entries(&sys.join("ieee80211"));
+
+        let mut attached = Vec::new();
+        for (_, dir) in bluetooth_dirs.iter().chain(phy_dirs.iter()) {
+            attached.extend(own_switches(dir).into_iter().map(|switch| switch.name));
+        }
+        let platform: Vec<Switch> = entries(&sys.join("rfkill"))
+            .into_iter()
+            .filter(|(name, _)| !attached.contains(name))
+            .filter_map(|(_, path)| read_switch(&path))
+            .collect();
+
+        let bluetooth = bluetooth_dirs
+            .iter()
+            .map(|(name, dir)| {
+                let (soft_blocked, hard_blocked) = blocked(&own_switches(dir), &platform, "bluetooth");
+                BluetoothAdapter {
+                    name: name.clone(),
+                    address: read(&dir.join("address")),
+                    soft_blocked,
+                    hard_blocked,
+                    available: !soft_blocked && !hard_blocked,
+                }
+            })
+            .collect();
+
+        let net = entries(&sys.join("net"));
+        let wifi: Vec<WifiAdapter> = phy_dirs
+            .iter()
+            .map(|(phy, dir)| {
+                let (soft_blocked, hard_blocked) = blocked(&own_switches(dir), &platform, "wlan");
+                let interfaces = net
+                    .iter()
+                    .filter(|(_, path)| read(&path.join("phy80211/name")).as_deref() == Some(phy.as_str()))
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                let interface_modes = wiphy_info(phy).map(|info| parse_interface_modes(&info)).unwrap_or_default();
+                let p2p = ["P2P-client", "P2P-GO"]
+                    .iter()
+                    .all(|mode| interface_modes.iter().any(|m| m == mode));
+                WifiAdapter {
+                    phy: phy.clone(),
+                    interfaces,
+                    soft_blocked,
+                    hard_blocked,
+                    interface_modes,
+                    p2p,
+                    available: !soft_blocked && !hard_blocked,
+                }
+            })
+            .collect();
+
+        let wifi_direct = wifi.iter().any(|adapter| adapter.available && adapter.p2p)
+            && crate::protocols::p2p::control_socket_available();
+        AdapterReport {
+            bluetooth,
+            wifi,
+            wifi_direct,
+        }
+    }
+
+    /// USB network drivers phones use for tethering.
+    const TETHERING_DRIVERS: [&str; 4] = ["rndis_host", "cdc_ether", "cdc_ncm", "ipheth"];
+    const ARPHRD_LOOPBACK: &str = "772";
+    const IFF_UP: u32 = 0x1;
+
+    fn link_kind(name: &str, dir: &Path) -> LinkKind {
+        let devtype = read(&dir.join("uevent")).and_then(|uevent| {
+            uevent
+                .lines()
+                .find_map(|line| line.strip_prefix("DEVTYPE=").map(str::to_string))
+        });
+        let driver = fs::read_link(dir.join("device/driver"))
+            .ok()
+            .and_then(|driver| driver.file_name().map(|name| name.to_string_lossy().into_owned()));
+
+        if read(&dir.join("type")).as_deref() == Some(ARPHRD_LOOPBACK) {
+            LinkKind::Loopback
+        } else if name.starts_with("p2p-") {
+            LinkKind::P2p
+        } else if dir.join("wireless").exists() || dir.join("phy80211").exists() {
+            LinkKind::Wireless
+        } else if devtype.as_deref() == Some("wwan") {
+            LinkKind::Cellular
+        } else if driver.is_some_and(|driver| TETHERING_DRIVERS.contains(&driver.as_str())) {
+            LinkKind::Tethered
+        } else if !dir.join("device").exists() {
+            LinkKind::Virtual
+        } else {
+            LinkKind::Wired
+        }
+    }
+
+    /// Reads each interface under `root`'s `/sys/class/net`, taking
+    /// addresses from `addresses` (keyed by interface name).
+    pub fn interfaces(root: &Path, addresses: &HashMap<String, Vec<InterfaceAddress>>) -> Vec<NetworkInterface> {
+        entries(&root.join("sys/class/net"))
+            .into_iter()
+            .map(|(name, dir)| {
+                let kind = link_kind(&name, &dir);
+                let operstate = read(&dir.join("operstate")).unwrap_or_default();
+                let flags = read(&dir.join("flags"))
+                    .and_then(|flags| u32::from_str_radix(flags.trim_start_matches("0x"), 16).ok())
+                    .unwrap_or(0);
+                // Tunnels and loopback report "unknown" while working fine.
+                let up = operstate == "up" || (operstate == "unknown" && flags & IFF_UP != 0);
+                let addresses = addresses.get(&name).cloned().unwrap_or_default();
+                let has_ipv4 = addresses.iter().any(|address| address.address.is_ipv4());
+                NetworkInterface {
+                    kind,
+                    mac: read(&dir.join("address")).filter(|mac| !mac.is_empty() && mac != "00:00:00:00:00:00"),
+                    mtu: read(&dir.join("mtu")).and_then(|mtu| mtu.parse().ok()),
+                    speed_mbps: read(&dir.join("speed"))
+                        .and_then(|speed| speed.parse::<i64>().ok())
+                        .filter(|speed| *speed > 0)
+                        .map(|speed| speed as u32),
+                    up,
+                    receivers_reachable: up && has_ipv4 && kind != LinkKind::Loopback,
+                    addresses,
+                    name,
+                }
+            })
+            .collect()
+    }
+
+    /// Every interface address, from `getifaddrs`.
+    pub fn addresses() -> HashMap<String, Vec<InterfaceAddress>> {
+        let mut addresses: HashMap<String, Vec<InterfaceAddress>> = HashMap::new();
+        // SAFETY: the list comes from getifaddrs and is only read before
+        // freeifaddrs; each sockaddr is cast according to its family.
+        unsafe {
+            let mut list: *mut libc::ifaddrs = std::ptr::null_mut();
+            if libc::getifaddrs(&mut list) != 0 {
+                return addresses;
+            }
+            let mut entry = list;
+            while let Some(ifa) = entry.as_ref() {
+                entry = ifa.ifa_next;
+                if ifa.ifa_addr.is_null() {
+                    continue;
+                }
+                let address = match i32::from((*ifa.ifa_addr).sa_family) {
+                    libc::AF_INET => {
+                        let ip = |sa: *const libc::sockaddr| {
+                            Ipv4Addr::from(u32::from_be((*(sa as *const libc::sockaddr_in)).sin_addr.s_addr))
+                        };
+                        let prefix = if ifa.ifa_netmask.is_null() {
+                            32
+                        } else {
+                            u32::from(ip(ifa.ifa_netmask)).count_ones() as u8
+                        };
+                        InterfaceAddress {
+                            address: IpAddr::V4(ip(ifa.ifa_addr)),
+                            prefix,
+                        }
+                    }
+                    libc::AF_INET6 => {
+                        let ip = |sa: *const libc::sockaddr| {
+                            Ipv6Addr::from((*(sa as *const libc::sockaddr_in6)).sin6_addr.s6_addr)
+                        };
+                        let prefix = if ifa.ifa_netmask.is_null() {
+                            128
+                        } else {
+                            u128::from(ip(ifa.ifa_netmask)).count_ones() as u8
+                        };
+                        InterfaceAddress {
+                            address: IpAddr::V6(ip(ifa.ifa_addr)),
+                            prefix,
+                        }
+                    }
+                    _ => continue,
+                };
+                let name = CStr::from_ptr(ifa.ifa_name).to_string_lossy().into_owned();
+                addresses.entry(name).or_default().push(address);
+            }
+            libc::freeifaddrs(list);
+        }
+        addresses
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::linux::{interfaces, parse_interface_modes, report};
+    use super::{address_score, interface_for, InterfaceAddress, LinkKind, NetworkInterface};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::net::IpAddr;
+    use std::path::{Path, PathBuf};
+
+    const IW_INFO: &str = "Wiphy phy0
+\tmax # scan SSIDs: 4
+\tSupported interface modes:
+\t\t * IBSS
+\t\t * managed
+\t\t * AP
+\t\t * AP/VLAN
+\t\t * monitor
+\t\t * P2P-client
+\t\t * P2P-GO
+\t\t * P2P-device
+\tBand 1:
+\t\tCapabilities: 0x1062
+";
+
+    /// A throwaway sysfs tree.
+    struct FakeSys(PathBuf);
+
+    impl FakeSys {
+        fn new(name: &str) -> FakeSys {
+            let root = std::env::temp_dir().join(format!("unishare-sysfs-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&root);
+            FakeSys(root)
+        }
+
+        fn write(&self, path: &str, contents: &str) {
+            let path = self.0.join("sys/class").join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, format!("{}\n", contents)).unwrap();
+        }
+
+        fn dir(&self, path: &str) {
+            fs::create_dir_all(self.0.join("sys/class").join(path)).unwrap();
+        }
+
+        /// A symlink at `path`, as sysfs uses for drivers.
+        fn link(&self, path: &str, target: &str) {
+            let path = self.0.join("sys/class").join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::os::unix::fs::symlink(target, path).unwrap();
+        }
+
+        /// An rfkill switch, listed globally and under `device` if given.
+        fn switch(&self, name: &str, kind: &str, soft: bool, hard: bool, device: Option<&str>) {
+            let mut dirs = vec![format!("rfkill/{}", name)];
+            dirs.extend(device.map(|device| format!("{}/{}", device, name)));
+            for dir in dirs {
+                self.write(&format!("{}/type", dir), kind);
+                self.write(&format!("{}/soft", dir), if soft { "1" } else { "0" });
+                self.write(&format!("{}/hard", dir), if hard { "1" } else { "0" });
+            }
+        }
+
+        fn root(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for FakeSys {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn laptop(name: &str, wlan_soft: bool, bluetooth_soft: bool) -> FakeSys {
+        let sys = FakeSys::new(name);
+        sys.write("bluetooth/hci0/address", "00:1a:7d:da:71:13");
+        sys.write("bluetooth/hci0:256/type", "ACL");
+        sys.switch("rfkill1", "bluetooth", bluetooth_soft, false, Some("bluetooth/hci0"));
+        sys.write("ieee80211/phy0/name", "phy0");
+        sys.switch("rfkill0", "wlan", wlan_soft, false, Some("ieee80211/phy0"));
+        sys.write("net/wlan0/phy80211/name", "phy0");
+        sys.write("net/eth0/type", "1");
+        sys
+    }
+
+    #[test]
+    fn parses_interface_modes() {
+        let modes = parse_interface_modes(IW_INFO);
+        assert_eq!(
+            modes,
+            ["IBSS", "managed", "AP", "AP/VLAN", "monitor", "P2P-client", "P2P-GO", "P2P-device"]
+        );
+        assert!(parse_interface_modes("Wiphy phy1\n").is_empty());
+    }
+
+    #[test]
+    fn blocked_wifi_does_not_hide_bluetooth() {
+        let sys = laptop("wifi-blocked", true, false);
+        let report = report(sys.root(), |_| Some(IW_INFO.to_string()));
+
+        assert_eq!(report.bluetooth.len(), 1);
+        let bluetooth = &report.bluetooth[0];
+        assert_eq!(bluetooth.name, "hci0");
+        assert_eq!(bluetooth.address.as_deref(), Some("00:1a:7d:da:71:13"));
+        assert!(bluetooth.available);
+
+        assert_eq!(report.wifi.len(), 1);
+        let wifi = &report.wifi[0];
+        assert_eq!(wifi.phy, "phy0");
+        assert_eq!(wifi.interfaces, ["wlan0"]);
+        assert!(wifi.soft_blocked);
+        assert!(!wifi.available);
+        assert!(wifi.p2p);
+        assert!(!report.wifi_direct);
+    }
+
+    #[test]
+    fn blocked_bluetooth_is_reported_per_adapter() {
+        let sys = laptop("bluetooth-blocked", false, true);
+        sys.write("bluetooth/hci1/address", "5c:f3:70:00:00:01");
+        sys.switch("rfkill2", "bluetooth", false, false, Some("bluetooth/hci1"));
+        let report = report(sys.root(), |_| None);
+
+        let available: Vec<(&str, bool)> = report
+            .bluetooth
+            .iter()
+            .map(|adapter| (adapter.name.as_str(), adapter.available))
+            .collect();
+        assert_eq!(available, [("hci0", false), ("hci1", true)]);
+        assert!(report.wifi[0].available);
+        // Without `iw` we can't tell, so P2P isn't claimed.
+        assert!(!report.wifi[0].p2p);
+    }
+
+    #[test]
+    fn platform_switch_blocks_matching_radios() {
+        let sys = laptop("platform-switch", false, false);
+        sys.switch("rfkill3", "wlan", false, true, None);
+        let report = report(sys.root(), |_| Some(IW_INFO.to_string()));
+
+        assert!(report.wifi[0].hard_blocked);
+        assert!(!report.wifi[0].available);
+        assert!(report.bluetooth[0].available);
+
+        sys.switch("rfkill4", "all", true, false, None);
+        let report = super::linux::report(sys.root(), |_| None);
+        assert!(report.bluetooth[0].soft_blocked);
+    }
+
+    /// A network interface that is up, with a device behind it unless it's
+    /// virtual.
+    fn interface(sys: &FakeSys, name: &str, device: bool) {
+        sys.write(&format!("net/{}/type", name), "1");
+        sys.write(&format!("net/{}/operstate", name), "up");
+        sys.write(&format!("net/{}/address", name), "02:00:00:00:00:01");
+        if device {
+            sys.dir(&format!("net/{}/device", name));
+        }
+    }
+
+    /// Interfaces with one address each, as `(name, address, prefix)`.
+    fn with_addresses(sys: &FakeSys, addresses: &[(&str, &str, u8)]) -> Vec<NetworkInterface> {
+        let mut by_name: HashMap<String, Vec<InterfaceAddress>> = HashMap::new();
+        for (name, address, prefix) in addresses {
+            by_name.entry(name.to_string()).or_default().push(InterfaceAddress {
+                address: address.parse().unwrap(),
+                prefix: *prefix,
+            });
+        }
+        interfaces(sys.root(), &by_name)
+    }
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn classifies_links() {
+        let sys = FakeSys::new("link-kinds");
+        interface(&sys, "eth0", true);
+        interface(&sys, "wlan0", true);
+        sys.dir("net/wlan0/wireless");
+        interface(&sys, "p2p-wlan0-0", false);
+        interface(&sys, "usb0", true);
+        sys.link("net/usb0/device/driver", "../../../bus/usb/drivers/rndis_host");
+        interface(&sys, "wwan0", true);
+        sys.write("net/wwan0/uevent", "INTERFACE=wwan0\nIFINDEX=7\nDEVTYPE=wwan");
+        interface(&sys, "docker0", false);
+        interface(&sys, "lo", false);
+        sys.write("net/lo/type", "772");
+
+        let kinds: Vec<(String, LinkKind)> = with_addresses(&sys, &[])
+            .into_iter()
+            .map(|interface| (interface.name, interface.kind))
+            .collect();
+        let expected = [
+            ("docker0", LinkKind::Virtual),
+            ("eth0", LinkKind::Wired),
+            ("lo", LinkKind::Loopback),
+            ("p2p-wlan0-0", LinkKind::P2p),
+            ("usb0", LinkKind::Tethered),
+            ("wlan0", LinkKind::Wireless),
+            ("wwan0", LinkKind::Cellular),
+        ];
+        assert_eq!(kinds, expected.map(|(name, kind)| (name.to_string(), kind)));
+    }
+
+    #[test]
+    fn reads_link_details() {
+        let sys = FakeSys::new("link-details");
+        interface(&sys, "eth0", true);
+        sys.write("net/eth0/mtu", "9000");
+        sys.write("net/eth0/speed", "1000");
+        interface(&sys, "wlan0", true);
+        sys.dir("net/wlan0/wireless");
+        sys.write("net/wlan0/mtu", "1500");
+        // Wireless drivers report -1, and unplugged ones fail to read.
+        sys.write("net/wlan0/speed", "-1");
+        interface(&sys, "tun0", false);
+        sys.write("net/tun0/operstate", "unknown");
+        sys.write("net/tun0/flags", "0x1091");
+        sys.write("net/tun0/mtu", "not a number");
+        interface(&sys, "eth1", true);
+        sys.write("net/eth1/operstate", "down");
+        sys.write("net/eth1/address", "00:00:00:00:00:00");
+
+        let interfaces = with_addresses(
+            &sys,
+            &[("eth0", "192.168.1.10", 24), ("eth1", "10.1.0.2", 16), ("tun0", "fd00::2", 64)],
+        );
+        let details: Vec<_> = interfaces
+            .iter()
+            .map(|i| (i.name.as_str(), i.mtu, i.speed_mbps, i.up, i.receivers_reachable))
+            .collect();
+        assert_eq!(
+            details,
+            [
+                ("eth0", Some(9000), Some(1000), true, true),
+                ("eth1", None, None, false, false),
+                // Up, but without an IPv4 address our receivers can't be reached.
+                ("tun0", None, None, true, false),
+                ("wlan0", Some(1500), None, true, false),
+            ]
+        );
+        assert_eq!(interfaces[1].mac, None);
+    }
+
+    #[test]
+    fn matches_addresses_by_prefix() {
+        let v4 = InterfaceAddress { address: ip("192.168.1.10"), prefix: 24 };
+        assert!(v4.contains(ip("192.168.1.200")));
+        assert!(!v4.contains(ip("192.168.2.1")));
+        assert!(!v4.contains(ip("::ffff:192.168.1.200")));
+
+        let odd = InterfaceAddress { address: ip("10.0.0.130"), prefix: 25 };
+        assert!(odd.contains(ip("10.0.0.255")));
+        assert!(!odd.contains(ip("10.0.0.127")));
+
+        let host = InterfaceAddress { address: ip("10.0.0.1"), prefix: 32 };
+        assert!(host.contains(ip("10.0.0.1")));
+        assert!(!host.contains(ip("10.0.0.2")));
+        let everything = InterfaceAddress { address: ip("10.0.0.1"), prefix: 0 };
+        assert!(everything.contains(ip("8.8.8.8")));
+
+        let v6 = InterfaceAddress { address: ip("fe80::1"), prefix: 64 };
+        assert!(v6.contains(ip("fe80::abcd:1234")));
+        assert!(!v6.contains(ip("fe81::1")));
+        assert!(!v6.contains(ip("192.168.1.10")));
+        let v6_host = InterfaceAddress { address: ip("2001:db8::1"), prefix: 128 };
+        assert!(!v6_host.contains(ip("2001:db8::2")));
+    }
+
+    #[test]
+    fn ranks_peers_by_the_link_that_reaches_them() {
+        let sys = FakeSys::new("ranking");
+        interface(&sys, "eth0", true);
+        sys.write("net/eth0/speed", "100");
+        interface(&sys, "eth1", true);
+        sys.write("net/eth1/speed", "2500");
+        interface(&sys, "wlan0", true);
+        sys.dir("net/wlan0/wireless");
+        interface(&sys, "p2p-wlan0-0", false);
+        interface(&sys, "eth2", true);
+        sys.write("net/eth2/operstate", "down");
+        interface(&sys, "lo", false);
+        sys.write("net/lo/type", "772");
+        let interfaces = with_addresses(
+            &sys,
+            &[
+                ("eth0", "10.0.0.5", 24),
+                ("eth1", "10.0.1.5", 24),
+                ("wlan0", "192.168.1.20", 24),
+                ("p2p-wlan0-0", "192.168.49.1", 24),
+                ("eth2", "172.16.0.1", 16),
+                ("lo", "127.0.0.1", 8),
+            ],
+        );
+
+        assert_eq!(interface_for(ip("192.168.49.7"), &interfaces).unwrap().name, "p2p-wlan0-0");
+        // Down and loopback interfaces don't count.
+        assert!(interface_for(ip("172.16.3.4"), &interfaces).is_none());
+        assert!(interface_for(ip("127.0.0.1"), &interfaces).is_none());
+        assert_eq!(address_score(ip("8.8.8.8"), &interfaces), 0);
+
+        // The order resolve_route tries a peer's addresses in.
+        let mut peer = ["8.8.8.8", "192.168.1.30", "192.168.49.7", "10.0.0.9", "10.0.1.9"].map(ip);
+        peer.sort_by_key(|ip| std::cmp::Reverse(address_score(*ip, &interfaces)));
+        assert_eq!(peer, ["10.0.1.9", "10.0.0.9", "192.168.49.7", "192.168.1.30", "8.8.8.8"].map(ip));
+    }
+
+    #[test]
+    fn no_adapters() {
+        let sys = FakeSys::new("empty");
+        let report = report(sys.root(), |_| None);
+        assert!(report.bluetooth.is_empty());
+        assert!(report.wifi.is_empty());
+        assert!(!report.wifi_direct);
+    }
+}
@@ -0,0 +1,413 @@
+use std::time::Duration;
+
+use reqwest::redirect::Policy;
+use serde::Serialize;
+use tokio::time::Instant;
+
+/// Endpoints probed when `UNISHARE_INTERNET_PROBES` isn't set. Each answers
+/// with a fixed response that captive portals can't fake without knowing it.
+/// Entries are the URL and the text its body must contain, or `None` for `204 No Content`.
+const DEFAULT_PROBES: [(&str, Option<&str>); 3] = [
+    ("http://connectivitycheck.gstatic.com/generate_204", None),
+    ("http://detectportal.firefox.com/success.txt", Some("success")),
+    ("http://captive.apple.com/hotspot-detect.html", Some("Success")),
+];
+
+/// One endpoint to check and the answer it gives when the internet is reachable.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Probe {
+    pub url: String,
+    /// Text the response body must contain; `None` means the endpoint answers
+    /// `204 No Content`.
+    pub expected_body: Option<String>,
+}
+
+impl Probe {
+    /// A probe answering `204 No Content`, like Google's `generate_204`.
+    pub fn no_content(url: &str) -> Self {
+        Probe {
+            url: url.to_string(),
+            expected_body: None,
+        }
+    }
+
+    /// A probe answering `200 OK` with a body containing `text`.
+    pub fn body(url: &str, text: &str) -> Self {
+        Probe {
+            url: url.to_string(),
+            expected_body: Some(text.to_string()),
+        }
+    }
+
+    /// Parses `url` (expects 204) or `url|text` (expects a body containing `text`).
+    fn parse(entry: &str) -> Self {
+        match entry.split_once('|') {
+            Some((url, text)) => Probe::body(url.trim(), text.trim()),
+            None => Probe::no_content(entry.trim()),
+        }
+    }
+}
+
+/// Settings for the internet check.
+#[derive(Clone, Debug)]
+pub struct InternetConfig {
+    pub probes: Vec<Probe>,
+    /// Per-probe limit, covering connect and body.
+    pub timeout: Duration,
+}
+
+impl Default for InternetConfig {
+    fn default() -> Self {
+        InternetConfig {
+            probes: DEFAULT_PROBES
+                .iter()
+                .map(|(url, text)| match text {
+                    None => Probe::no_content(url),
+                    Some(text) => Probe::body(url, text),
+                })
+                .collect(),
+            timeout: Duration::from_secs(3),
+        }
+    }
+}
+
+impl InternetConfig {
+    /// Reads `UNISHARE_INTERNET_PROBES`, a comma-separated list of `url` or
+    /// `url|expected text` entries, and `UNISHARE_INTERNET_TIMEOUT_SECS`.
+    pub fn from_env() -> Self {
+        let mut config = InternetConfig::default();
+        if let Ok(probes) = std::env::var("UNISHARE_INTERNET_PROBES") {
+            let probes: Vec<Probe> = probes
+                .split(',')
+                .filter(|entry| !entry.trim().is_empty())
+                .map(Probe::parse)
+                .collect();
+            if !probes.is_empty() {
+                config.probes = probes;
+            }
+        }
+        if let Some(secs) = std::env::var("UNISHARE_INTERNET_TIMEOUT_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+        {
+            config.timeout = Duration::from_secs(secs);
+        }
+        config
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ProbeVerdict {
+    /// The endpoint gave its expected answer.
+    Online,
+    /// Something redirected us or answered in the endpoint's place.
+    CaptivePortal,
+    /// The endpoint answered with an error status.
+    Unexpected,
+    /// No answer: DNS, connection or timeout failure.
+    Unreachable,
+}
+
+/// What one probe saw.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeResult {
+    pub url: String,
+    pub verdict: ProbeVerdict,
+    pub status: Option<u16>,
+    /// Time to the full response, or to the failure.
+    pub latency_ms: u64,
+    /// Where a captive portal redirected us, if it did.
+    pub location: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum InternetState {
+    Online,
+    /// Connected to a network that wants a sign-in before letting traffic out.
+    CaptivePortal,
+    Offline,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InternetStatus {
+    pub state: InternetState,
+    /// The sign-in page, when a portal redirected a probe.
+    pub portal_url: Option<String>,
+    pub probes: Vec<ProbeResult>,
+}
+
+impl InternetStatus {
+    pub fn is_online(&self) -> bool {
+        self.state == InternetState::Online
+    }
+}
+
+/// Runs every probe concurrently and combines the results:
+///
+/// - online if any probe got its expected answer,
+/// - behind a captive portal if none did but one was intercepted,
+/// - offline otherwise.
+pub async fn check(config: &InternetConfig) -> InternetStatus {
+    let client = match reqwest::Client::builder()
+        // A portal's redirect is the evidence we're looking for.
+        .redirect(Policy::none())
+        .timeout(config.timeout)
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            println!("Internet check unavailable: {}", e);
+            return InternetStatus {
+                state: InternetState::Offline,
+                portal_url: None,
+                probes: Vec::new(),
+            };
+        }
+    };
+
+    let handles: Vec<_> = config
+        .probes
+        .iter()
+        .cloned()
+        .map(|probe| tokio::spawn(run_probe(client.clone(), probe)))
+        .collect();
+    let mut probes = Vec::with_capacity(handles.len());
+    for (handle, probe) in handles.into_iter().zip(&config.probes) {
+        probes.push(handle.await.unwrap_or_else(|e| ProbeResult {
+            url: probe.url.clone(),
+            verdict: ProbeVerdict::Unreachable,
+            status: None,
+            latency_ms: 0,
+            location: None,
+            error: Some(e.to_string()),
+        }));
+    }
+
+    let captive = probes.iter().find(|p| p.verdict == ProbeVerdict::CaptivePortal);
+    let (state, portal_url) = if probes.iter().any(|p| p.verdict == ProbeVerdict::Online) {
+        (InternetState::Online, None)
+    } else if let Some(captive) = captive {
+        (InternetState::CaptivePortal, captive.location.clone())
+    } else {
+        (InternetState::Offline, None)
+    };
+    InternetStatus {
+        state,
+        portal_url,
+        probes,
+    }
+}
+
+async fn run_probe(client: reqwest::Client, probe: Probe) -> ProbeResult {
+    let started = Instant::now();
+    let mut result = ProbeResult {
+        url: probe.url.clone(),
+        verdict: ProbeVerdict::Unreachable,
+        status: None,
+        latency_ms: 0,
+        location: None,
+        error: None,
+    };
+
+    let response = match client.get(&probe.url).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            result.latency_ms = started.elapsed().as_millis() as u64;
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+    let status = response.status();
+    result.status = Some(status.as_u16());
+    result.location = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .map(str::to_string);
+    let body = response.text().await;
+    result.latency_ms = started.elapsed().as_millis() as u64;
+
+    result.verdict = if status.is_redirection() {
+        ProbeVerdict::CaptivePortal
+    } else if status.is_client_error() || status.is_server_error() {
+        ProbeVerdict::Unexpected
+    } else {
+        match body {
+            Err(e) => {
+                result.error = Some(e.to_string());
+                ProbeVerdict::Unreachable
+            }
+            Ok(body) => {
+                let expected = match &probe.expected_body {
+                    None => status == reqwest::StatusCode::NO_CONTENT,
+                    Some(text) => status == reqwest::StatusCode::OK && body.contains(text.as_str()),
+                };
+                // Any other success answer is a page served in the endpoint's place.
+                if expected {
+                    ProbeVerdict::Online
+                } else {
+                    ProbeVerdict::CaptivePortal
+                }
+            }
+        }
+    };
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A local stand-in for probe endpoints: answers each path with a canned
+    /// raw HTTP response, or stalls on paths it doesn't know.
+    async fn stand_in(routes: Vec<(&'static str, String)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let routes = routes.clone();
+                tokio::spawn(async move {
+                    let mut request = vec![0u8; 4096];
+                    let len = socket.read(&mut request).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&request[..len]);
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    match routes.iter().find(|(route, _)| *route == path) {
+                        Some((_, response)) => {
+                            let _ = socket.write_all(response.as_bytes()).await;
+                            let _ = socket.shutdown().await;
+                        }
+                        None => tokio::time::sleep(Duration::from_secs(30)).await,
+                    }
+                });
+            }
+        });
+        base
+    }
+
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        )
+    }
+
+    fn config(probes: Vec<Probe>) -> InternetConfig {
+        InternetConfig {
+            probes,
+            timeout: Duration::from_millis(500),
+        }
+    }
+
+    #[tokio::test]
+    async fn online_when_a_probe_answers_as_expected() {
+        let base = stand_in(vec![
+            ("/generate_204", response("204 No Content", "", "")),
+            ("/success.txt", response("200 OK", "", "success\n")),
+        ])
+        .await;
+        let status = check(&config(vec![
+            Probe::no_content(&format!("{}/generate_204", base)),
+            Probe::body(&format!("{}/success.txt", base), "success"),
+        ]))
+        .await;
+
+        assert_eq!(status.state, InternetState::Online);
+        assert!(status.is_online());
+        assert_eq!(status.probes.len(), 2);
+        for probe in &status.probes {
+            assert_eq!(probe.verdict, ProbeVerdict::Online);
+            assert!(probe.latency_ms < 500);
+        }
+        assert_eq!(status.probes[0].status, Some(204));
+    }
+
+    #[tokio::test]
+    async fn redirect_is_a_captive_portal() {
+        let base = stand_in(vec![(
+            "/generate_204",
+            response("302 Found", "Location: http://portal.example/login\r\n", ""),
+        )])
+        .await;
+        let status = check(&config(vec![Probe::no_content(&format!("{}/generate_204", base))])).await;
+
+        assert_eq!(status.state, InternetState::CaptivePortal);
+        assert_eq!(status.portal_url.as_deref(), Some("http://portal.example/login"));
+        assert_eq!(status.probes[0].status, Some(302));
+    }
+
+    #[tokio::test]
+    async fn substituted_page_is_a_captive_portal() {
+        let base = stand_in(vec![
+            ("/generate_204", response("200 OK", "", "<html>Sign in to Hotel Wi-Fi</html>")),
+            ("/hotspot-detect.html", response("200 OK", "", "<html>Sign in to Hotel Wi-Fi</html>")),
+        ])
+        .await;
+        let status = check(&config(vec![
+            Probe::no_content(&format!("{}/generate_204", base)),
+            Probe::body(&format!("{}/hotspot-detect.html", base), "Success"),
+        ]))
+        .await;
+
+        assert_eq!(status.state, InternetState::CaptivePortal);
+        assert_eq!(status.portal_url, None);
+        assert!(status.probes.iter().all(|p| p.verdict == ProbeVerdict::CaptivePortal));
+    }
+
+    #[tokio::test]
+    async fn one_good_probe_outweighs_failures() {
+        let base = stand_in(vec![
+            ("/generate_204", response("204 No Content", "", "")),
+            ("/broken", response("503 Service Unavailable", "", "")),
+        ])
+        .await;
+        let status = check(&config(vec![
+            Probe::no_content(&format!("{}/broken", base)),
+            Probe::no_content(&format!("{}/stalls", base)),
+            Probe::no_content(&format!("{}/generate_204", base)),
+        ]))
+        .await;
+
+        assert_eq!(status.state, InternetState::Online);
+        let verdicts: Vec<ProbeVerdict> = status.probes.iter().map(|p| p.verdict).collect();
+        assert_eq!(
+            verdicts,
+            [ProbeVerdict::Unexpected, ProbeVerdict::Unreachable, ProbeVerdict::Online]
+        );
+        assert!(status.probes[1].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn offline_when_nothing_answers() {
+        // Bind and drop to get a port nobody listens on.
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let status = check(&config(vec![Probe::no_content(&format!("http://127.0.0.1:{}/", port))])).await;
+
+        assert_eq!(status.state, InternetState::Offline);
+        assert_eq!(status.probes[0].verdict, ProbeVerdict::Unreachable);
+        assert_eq!(status.probes[0].status, None);
+    }
+
+    #[test]
+    fn parses_probe_entries() {
+        assert_eq!(
+            Probe::parse(" http://a.example/204 "),
+            Probe::no_content("http://a.example/204")
+        );
+        assert_eq!(
+            Probe::parse("http://b.example/ok.txt|success"),
+            Probe::body("http://b.example/ok.txt", "success")
+        );
+    }
+}
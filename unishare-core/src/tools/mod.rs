@@ -0,0 +1,3 @@
+pub mod connectivity;
+pub mod internet;
+pub mod watcher;
@@ -0,0 +1,408 @@
+use std::future::Future;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::sleep;
+
+use super::connectivity::{connectivity_report, ConnectivityReport};
+use super::internet::InternetConfig;
+
+/// Bursts of link and address changes (e.g. joining a Wi‑Fi network) are
+/// collapsed into one re-check after things settle.
+const SETTLE: Duration = Duration::from_millis(500);
+/// Internet access can change without a local event, e.g. signing in to a
+/// captive portal, so we re-check this often regardless.
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+static WATCHER: LazyLock<ConnectivityWatcher> = LazyLock::new(ConnectivityWatcher::default);
+
+/// The process-wide watcher, so protocol selection can consult it without
+/// Tauri state.
+pub fn watcher() -> &'static ConnectivityWatcher {
+    &WATCHER
+}
+
+/// Keeps the latest [`ConnectivityReport`] and announces every change to it.
+pub struct ConnectivityWatcher {
+    latest: Mutex<Option<ConnectivityReport>>,
+    events: broadcast::Sender<ConnectivityReport>,
+    triggers: Mutex<Option<mpsc::UnboundedSender<()>>>,
+    settle: Duration,
+    recheck: Duration,
+}
+
+impl Default for ConnectivityWatcher {
+    fn default() -> Self {
+        ConnectivityWatcher {
+            latest: Mutex::new(None),
+            events: broadcast::channel(16).0,
+            triggers: Mutex::new(None),
+            settle: SETTLE,
+            recheck: RECHECK_INTERVAL,
+        }
+    }
+}
+
+impl ConnectivityWatcher {
+    /// Receives every new state from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<ConnectivityReport> {
+        self.events.subscribe()
+    }
+
+    /// The last state seen, or `None` before the first check finishes.
+    pub fn latest(&self) -> Option<ConnectivityReport> {
+        self.latest.lock().unwrap().clone()
+    }
+
+    /// Whether the last check found working internet access. Unknown counts
+    /// as online, so callers only skip the internet when we know it's down.
+    pub fn internet_likely(&self) -> bool {
+        self.latest().is_none_or(|report| report.internet.is_online())
+    }
+
+    /// Asks for a re-check, e.g. after a transfer failed unexpectedly.
+    pub fn refresh(&self) {
+        if let Some(triggers) = self.triggers.lock().unwrap().as_ref() {
+            let _ = triggers.send(());
+        }
+    }
+
+    /// Checks connectivity now and again whenever the system reports a link,
+    /// address, route or rfkill change. Calling it again does nothing.
+    pub fn start(&'static self, config: InternetConfig) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        {
+            let mut triggers = self.triggers.lock().unwrap();
+            if triggers.is_some() {
+                return;
+            }
+            *triggers = Some(tx.clone());
+        }
+
+        #[cfg(target_os = "linux")]
+        linux::spawn_sources(tx);
+
+        tokio::spawn(self.run(rx, move || {
+            let config = config.clone();
+            async move { connectivity_report(&config).await }
+        }));
+    }
+
+    /// Runs `check` now, once every burst of `triggers` has settled, and
+    /// every [`RECHECK_INTERVAL`] in between.
+    async fn run<F, R>(&self, mut triggers: mpsc::UnboundedReceiver<()>, check: F)
+    where
+        F: Fn() -> R,
+        R: Future<Output = Result<ConnectivityReport, String>>,
+    {
+        loop {
+            match check().await {
+                Ok(report) => {
+                    self.record(report);
+                }
+                Err(e) => println!("Connectivity check failed: {}", e),
+            }
+            tokio::select! {
+                Some(()) = triggers.recv() => {
+                    sleep(self.settle).await;
+                    while triggers.try_recv().is_ok() {}
+                }
+                _ = sleep(self.recheck) => {}
+            }
+        }
+    }
+
+    /// Keeps `report` as the latest state and announces it if it differs
+    /// from the previous one. Returns whether it did.
+    fn record(&self, report: ConnectivityReport) -> bool {
+        let changed = {
+            let mut latest = self.latest.lock().unwrap();
+            let changed = latest.as_ref().is_none_or(|old| !same_state(old, &report));
+            *latest = Some(report.clone());
+            changed
+        };
+        if changed {
+            println!(
+                "🔌 Connectivity changed: internet {:?}, Wi‑Fi Direct {}",
+                report.internet.state, report.adapters.wifi_direct
+            );
+            let _ = self.events.send(report);
+        }
+        changed
+    }
+}
+
+/// Compares what matters to callers, ignoring probe latency and the like.
+fn same_state(a: &ConnectivityReport, b: &ConnectivityReport) -> bool {
+    a.adapters == b.adapters && a.internet.state == b.internet.state && a.internet.portal_url == b.internet.portal_url
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use std::time::Duration;
+
+    use tokio::sync::mpsc::UnboundedSender;
+
+    type Opener = fn() -> io::Result<File>;
+
+    /// Wait between attempts to reopen a source that stopped.
+    const REOPEN_DELAY: Duration = Duration::from_secs(5);
+
+    /// Starts a thread per event source; each sends a trigger per event.
+    /// A source that can't be opened is skipped, leaving the periodic re-check.
+    pub fn spawn_sources(triggers: UnboundedSender<()>) {
+        let sources: [(&'static str, Opener); 2] = [("netlink", open_netlink), ("rfkill", open_rfkill)];
+        for (name, open) in sources {
+            let source = match open() {
+                Ok(source) => source,
+                Err(e) => {
+                    println!("Not watching {} events: {}", name, e);
+                    continue;
+                }
+            };
+            let triggers = triggers.clone();
+            std::thread::spawn(move || pump(name, source, open, &triggers, REOPEN_DELAY));
+        }
+    }
+
+    /// Sends a trigger per event read from `source` until nobody listens.
+    ///
+    /// - ENOBUFS means the kernel dropped events because we fell behind, so
+    ///   something changed; it counts as an event.
+    /// - EINTR is retried.
+    /// - Any other error, or the source closing, is logged and the source
+    ///   reopened with `open`, every `reopen_delay` until that works.
+    pub(super) fn pump<R: Read>(
+        name: &str,
+        mut source: R,
+        mut open: impl FnMut() -> io::Result<R>,
+        triggers: &UnboundedSender<()>,
+        reopen_delay: Duration,
+    ) {
+        let mut buffer = vec![0u8; 16 * 1024];
+        loop {
+            // We only care that something happened, not what.
+            let stopped = match source.read(&mut buffer) {
+                Ok(0) => Some("closed".to_string()),
+                Ok(_) => None,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => None,
+                Err(e) => Some(e.to_string()),
+            };
+            if let Some(reason) = stopped {
+                println!("{} events stopped ({}); reopening", name, reason);
+                source = loop {
+                    std::thread::sleep(reopen_delay);
+                    if triggers.is_closed() {
+                        return;
+                    }
+                    match open() {
+                        Ok(source) => break source,
+                        Err(e) => println!("Couldn't reopen {} events: {}", name, e),
+                    }
+                };
+                // Events may have gone missing while it was down.
+            }
+            if triggers.send(()).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// A netlink socket subscribed to link, address and route changes.
+    fn open_netlink() -> io::Result<File> {
+        // SAFETY: plain socket/bind calls; the descriptor is owned right away
+        // and `addr` is a fully initialised `sockaddr_nl`.
+        unsafe {
+            let fd = libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_ROUTE);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let socket = OwnedFd::from_raw_fd(fd);
+
+            let mut addr: libc::sockaddr_nl = std::mem::zeroed();
+            addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            addr.nl_groups = (libc::RTMGRP_LINK
+                | libc::RTMGRP_IPV4_IFADDR
+                | libc::RTMGRP_IPV6_IFADDR
+                | libc::RTMGRP_IPV4_ROUTE
+                | libc::RTMGRP_IPV6_ROUTE) as u32;
+            let bound = libc::bind(
+                fd,
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            );
+            if bound < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(File::from(socket))
+        }
+    }
+
+    /// `/dev/rfkill` yields an event whenever a switch is added, removed or
+    /// toggled.
+    fn open_rfkill() -> io::Result<File> {
+        File::open("/dev/rfkill")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::tools::connectivity::AdapterReport;
+    use crate::tools::internet::{InternetState, InternetStatus, ProbeResult, ProbeVerdict};
+
+    fn watcher(settle: Duration, recheck: Duration) -> &'static ConnectivityWatcher {
+        Box::leak(Box::new(ConnectivityWatcher {
+            settle,
+            recheck,
+            ..ConnectivityWatcher::default()
+        }))
+    }
+
+    fn report(state: InternetState, latency_ms: u64) -> ConnectivityReport {
+        ConnectivityReport {
+            adapters: AdapterReport {
+                bluetooth: Vec::new(),
+                wifi: Vec::new(),
+                wifi_direct: false,
+            },
+            internet: InternetStatus {
+                state,
+                portal_url: None,
+                probes: vec![ProbeResult {
+                    url: "http://probe.test/".to_string(),
+                    verdict: ProbeVerdict::Online,
+                    status: Some(204),
+                    latency_ms,
+                    location: None,
+                    error: None,
+                }],
+            },
+        }
+    }
+
+    /// Runs `watcher` with a check that counts its calls.
+    fn run_counting(watcher: &'static ConnectivityWatcher) -> (mpsc::UnboundedSender<()>, Arc<AtomicUsize>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let checks = Arc::new(AtomicUsize::new(0));
+        let counted = checks.clone();
+        tokio::spawn(watcher.run(rx, move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+            async { Ok(report(InternetState::Online, 10)) }
+        }));
+        (tx, checks)
+    }
+
+    #[test]
+    fn announces_only_changes_that_matter() {
+        let watcher = watcher(SETTLE, RECHECK_INTERVAL);
+        let mut events = watcher.subscribe();
+        assert!(watcher.internet_likely(), "unknown counts as online");
+
+        assert!(watcher.record(report(InternetState::Online, 10)));
+        // Only the probe latency differs.
+        assert!(!watcher.record(report(InternetState::Online, 250)));
+        assert_eq!(watcher.latest().unwrap().internet.probes[0].latency_ms, 250);
+        assert!(watcher.record(report(InternetState::Offline, 250)));
+        assert!(!watcher.internet_likely());
+
+        let mut portal = report(InternetState::CaptivePortal, 10);
+        assert!(watcher.record(portal.clone()));
+        portal.internet.portal_url = Some("http://portal.test/login".to_string());
+        assert!(watcher.record(portal));
+        let mut wifi_direct = report(InternetState::CaptivePortal, 10);
+        wifi_direct.internet.portal_url = Some("http://portal.test/login".to_string());
+        wifi_direct.adapters.wifi_direct = true;
+        assert!(watcher.record(wifi_direct));
+
+        let announced: Vec<InternetState> = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|report| report.internet.state)
+            .collect();
+        assert_eq!(
+            announced,
+            [
+                InternetState::Online,
+                InternetState::Offline,
+                InternetState::CaptivePortal,
+                InternetState::CaptivePortal,
+                InternetState::CaptivePortal,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn collapses_bursts_of_events_into_one_check() {
+        let watcher = watcher(Duration::from_millis(100), Duration::from_secs(60));
+        let (triggers, checks) = run_counting(watcher);
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(checks.load(Ordering::SeqCst), 1, "checks straight away");
+
+        for _ in 0..5 {
+            triggers.send(()).unwrap();
+            sleep(Duration::from_millis(10)).await;
+        }
+        sleep(Duration::from_millis(300)).await;
+        assert_eq!(checks.load(Ordering::SeqCst), 2);
+
+        triggers.send(()).unwrap();
+        sleep(Duration::from_millis(300)).await;
+        assert_eq!(checks.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn rechecks_without_events() {
+        let watcher = watcher(Duration::from_millis(100), Duration::from_millis(200));
+        let (_triggers, checks) = run_counting(watcher);
+        sleep(Duration::from_millis(500)).await;
+        assert!(checks.load(Ordering::SeqCst) >= 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn keeps_reading_events_through_errors() {
+        use std::collections::VecDeque;
+        use std::io::{self, Read};
+
+        /// Gives out scripted reads, then reports the end of the stream.
+        struct Script(VecDeque<io::Result<usize>>);
+
+        impl Read for Script {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                self.0.pop_front().unwrap_or(Ok(0))
+            }
+        }
+
+        let error = |code| Err(io::Error::from_raw_os_error(code));
+        let first = Script(VecDeque::from([Ok(20), error(libc::EINTR), error(libc::ENOBUFS), Ok(8), error(libc::EBADF)]));
+        let opened = Arc::new(AtomicUsize::new(0));
+        let open = {
+            let opened = opened.clone();
+            move || match opened.fetch_add(1, Ordering::SeqCst) {
+                // The first attempt fails, the second gets a fresh source.
+                0 => Err(io::Error::from_raw_os_error(libc::ENODEV)),
+                1 => Ok(Script(VecDeque::from([Ok(4)]))),
+                _ => Err(io::Error::from_raw_os_error(libc::ENODEV)),
+            }
+        };
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let pumping = std::thread::spawn(move || linux::pump("test", first, open, &tx, Duration::from_millis(10)));
+        // 20 bytes, ENOBUFS, 8 bytes, the reopen and 4 bytes; EINTR is retried.
+        for _ in 0..5 {
+            rx.blocking_recv().unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(rx.try_recv().is_err());
+        drop(rx);
+        pumping.join().unwrap();
+        assert!(opened.load(Ordering::SeqCst) >= 2);
+    }
+}
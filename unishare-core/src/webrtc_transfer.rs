@@ -0,0 +1,612 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::error::Error;
+
+use serde::Serialize;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::{timeout, Duration};
+
+use webrtc::api::APIBuilder;
+use webrtc::api::media_engine::MediaEngine;
+use webrtc::ice_transport::ice_server::RTCIceServer;
+use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::math_rand_alpha;
+use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::stats::StatsReportType;
+
+use crate::protocols::webrtc::{accept_data_channels, receive_file, OutgoingFile, SendChannel};
+use serde_json;
+
+/// Sessions that never connect, or stay disconnected or failed for this long,
+/// are closed and dropped. Long enough for an ICE restart to heal a drop.
+const SESSION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Live peer connections, keyed by session ID. Entries are dropped once
+/// closed, or once [`SESSION_TIMEOUT`] passes without a connection.
+static SESSIONS: LazyLock<Mutex<HashMap<String, WebRtcSession>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Fan-out of connection state changes for every session.
+static STATE_EVENTS: LazyLock<broadcast::Sender<WebRtcStateEvent>> =
+    LazyLock::new(|| broadcast::channel(64).0);
+
+/// A registered peer connection, addressable by its session ID.
+#[derive(Clone)]
+pub struct WebRtcSession {
+    pub id: String,
+    pub role: &'static str,
+    pub pc: Arc<RTCPeerConnection>,
+    state: watch::Receiver<RTCPeerConnectionState>,
+    /// Data channel of an offer from [`create_webrtc_offer`], until its
+    /// answer arrives in [`set_remote_description_and_send_file`].
+    outgoing: Arc<Mutex<Option<SendChannel>>>,
+}
+
+impl WebRtcSession {
+    /// Follows the peer connection state, for reacting to drops mid-transfer.
+    pub fn watch_state(&self) -> watch::Receiver<RTCPeerConnectionState> {
+        self.state.clone()
+    }
+
+    /// Waits until the peer connection is connected, failing early if it
+    /// fails or closes instead.
+    pub async fn wait_connected(&self, limit: Duration) -> Result<(), Box<dyn Error>> {
+        let mut state = self.state.clone();
+        let reached = timeout(
+            limit,
+            state.wait_for(|s| {
+                matches!(
+                    s,
+                    RTCPeerConnectionState::Connected
+                        | RTCPeerConnectionState::Failed
+                        | RTCPeerConnectionState::Closed
+                )
+            }),
+        )
+        .await
+        .map_err(|_| "WebRTC connection timeout")?
+        .map(|s| *s)?;
+
+        match reached {
+            RTCPeerConnectionState::Connected => Ok(()),
+            other => Err(format!("WebRTC connection {}", other).into()),
+        }
+    }
+}
+
+/// Emitted whenever a session's ICE or peer connection state changes.
+///
+/// `layer` is `"ice"` for ICE connection states (checking, connected,
+/// disconnected, failed, ...) and `"peer"` for the overall peer connection.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebRtcStateEvent {
+    pub session_id: String,
+    pub role: &'static str,
+    pub layer: &'static str,
+    pub state: String,
+}
+
+/// Troubleshooting snapshot of a session, distilled from `get_stats`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebRtcStats {
+    pub session_id: String,
+    pub role: &'static str,
+    pub connection_state: String,
+    pub ice_connection_state: String,
+    pub selected_candidate_pair: Option<CandidatePairStats>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub data_channels: Vec<DataChannelStats>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CandidatePairStats {
+    pub local: CandidateStats,
+    pub remote: CandidateStats,
+    pub state: String,
+    pub nominated: bool,
+    pub current_rtt_ms: f64,
+    pub total_rtt_ms: f64,
+    pub requests_sent: u64,
+    pub responses_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CandidateStats {
+    pub address: String,
+    pub port: u16,
+    pub candidate_type: String,
+    pub protocol: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataChannelStats {
+    pub label: String,
+    pub state: String,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+}
+
+/// An SDP offer or answer to hand to the peer, with the session it belongs
+/// to so its state and stats can be followed.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebRtcSignal {
+    pub session_id: String,
+    /// The session description as JSON.
+    pub sdp: String,
+}
+
+/// Subscribes to state changes of every WebRTC session.
+pub fn subscribe_state_events() -> broadcast::Receiver<WebRtcStateEvent> {
+    STATE_EVENTS.subscribe()
+}
+
+/// Looks up a live session by ID.
+pub fn session(session_id: &str) -> Option<WebRtcSession> {
+    SESSIONS.lock().unwrap().get(session_id).cloned()
+}
+
+/// Builds a peer connection with the default codecs and our STUN server, and
+/// registers it as a new session whose state changes are broadcast.
+pub async fn new_session(role: &'static str) -> Result<WebRtcSession, Box<dyn Error>> {
+    let mut m = MediaEngine::default();
+    m.register_default_codecs()?;
+    let api = APIBuilder::new().with_media_engine(m).build();
+
+    let config = RTCConfiguration {
+        ice_servers: vec![RTCIceServer {
+            urls: vec!["stun:stun.l.google.com:19302".to_string()],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let pc = Arc::new(api.new_peer_connection(config).await?);
+    let id = math_rand_alpha(16);
+    let (state_tx, state_rx) = watch::channel(RTCPeerConnectionState::New);
+
+    let session_id = id.clone();
+    pc.on_ice_connection_state_change(Box::new(move |state| {
+        println!("🧊 [{}] ICE connection state changed: {}", role, state);
+        publish_state(&session_id, role, "ice", state.to_string());
+        Box::pin(async {})
+    }));
+
+    let session_id = id.clone();
+    pc.on_peer_connection_state_change(Box::new(move |state| {
+        println!("🔄 [{}] PeerConnection state changed: {}", role, state);
+        let _ = state_tx.send(state);
+        publish_state(&session_id, role, "peer", state.to_string());
+        if state == RTCPeerConnectionState::Closed {
+            SESSIONS.lock().unwrap().remove(&session_id);
+        }
+        Box::pin(async {})
+    }));
+
+    let session = WebRtcSession {
+        id: id.clone(),
+        role,
+        pc,
+        state: state_rx,
+        outgoing: Arc::new(Mutex::new(None)),
+    };
+    SESSIONS.lock().unwrap().insert(id, session.clone());
+    tokio::spawn(reap(session.clone()));
+    Ok(session)
+}
+
+/// Drops `session` once it closes, and closes it if it goes
+/// [`SESSION_TIMEOUT`] without being connected.
+async fn reap(session: WebRtcSession) {
+    let mut state = session.watch_state();
+    let timed_out = loop {
+        let current = *state.borrow_and_update();
+        match current {
+            RTCPeerConnectionState::Closed => break false,
+            RTCPeerConnectionState::Connected => {
+                if state.changed().await.is_err() {
+                    break false;
+                }
+            }
+            // New, connecting, disconnected or failed: it has a while to
+            // (re)connect.
+            _ => {
+                let settled = state.wait_for(|s| {
+                    matches!(s, RTCPeerConnectionState::Connected | RTCPeerConnectionState::Closed)
+                });
+                match timeout(SESSION_TIMEOUT, settled).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(_)) => break false,
+                    Err(_) => break true,
+                }
+            }
+        }
+    };
+    SESSIONS.lock().unwrap().remove(&session.id);
+    if timed_out {
+        println!("⌛ [{}] Session {} timed out; closing it.", session.role, session.id);
+        let _ = session.pc.close().await;
+    }
+}
+
+/// Sets `desc` as the local description and waits for ICE gathering, so the
+/// SDP handed to the peer already carries every candidate.
+pub(crate) async fn set_local_and_gather(
+    pc: &RTCPeerConnection,
+    desc: RTCSessionDescription,
+) -> Result<RTCSessionDescription, Box<dyn Error>> {
+    let mut gathered = pc.gathering_complete_promise().await;
+    pc.set_local_description(desc).await?;
+    let _ = gathered.recv().await;
+    pc.local_description()
+        .await
+        .ok_or_else(|| "local description missing after ICE gathering".into())
+}
+
+fn publish_state(session_id: &str, role: &'static str, layer: &'static str, state: String) {
+    // Sending only fails when nobody is subscribed, which is fine.
+    let _ = STATE_EVENTS.send(WebRtcStateEvent {
+        session_id: session_id.to_string(),
+        role,
+        layer,
+        state,
+    });
+}
+
+/// Collects `get_stats` for a session: the selected candidate pair with its
+/// RTT, transport byte counters and per data channel counters.
+pub async fn session_stats(session_id: &str) -> Result<WebRtcStats, Box<dyn Error>> {
+    let session = session(session_id).ok_or_else(|| format!("Unknown WebRTC session: {}", session_id))?;
+    let report = session.pc.get_stats().await;
+
+    let mut pairs = Vec::new();
+    let mut candidates = HashMap::new();
+    let mut stats = WebRtcStats {
+        session_id: session.id.clone(),
+        role: session.role,
+        connection_state: session.pc.connection_state().to_string(),
+        ice_connection_state: session.pc.ice_connection_state().to_string(),
+        selected_candidate_pair: None,
+        bytes_sent: 0,
+        bytes_received: 0,
+        data_channels: Vec::new(),
+    };
+
+    for entry in report.reports.values() {
+        match entry {
+            StatsReportType::CandidatePair(pair) => pairs.push(pair),
+            StatsReportType::LocalCandidate(c) | StatsReportType::RemoteCandidate(c) => {
+                candidates.insert(
+                    c.id.as_str(),
+                    CandidateStats {
+                        address: c.ip.clone(),
+                        port: c.port,
+                        candidate_type: c.candidate_type.to_string(),
+                        protocol: c.network_type.to_string(),
+                    },
+                );
+            }
+            StatsReportType::Transport(t) => {
+                stats.bytes_sent += t.bytes_sent as u64;
+                stats.bytes_received += t.bytes_received as u64;
+            }
+            StatsReportType::DataChannel(dc) => stats.data_channels.push(DataChannelStats {
+                label: dc.label.clone(),
+                state: dc.state.to_string(),
+                bytes_sent: dc.bytes_sent as u64,
+                bytes_received: dc.bytes_received as u64,
+                messages_sent: dc.messages_sent as u64,
+                messages_received: dc.messages_received as u64,
+            }),
+            _ => {}
+        }
+    }
+
+    // The selected pair is the nominated one that succeeded; before
+    // nomination, fall back to any pair that has succeeded.
+    let succeeded = |p: &&&webrtc::stats::ICECandidatePairStats| p.state.to_string() == "succeeded";
+    let selected = pairs
+        .iter()
+        .filter(succeeded)
+        .find(|p| p.nominated)
+        .or_else(|| pairs.iter().find(succeeded));
+
+    if let Some(pair) = selected {
+        if let (Some(local), Some(remote)) = (
+            candidates.get(pair.local_candidate_id.as_str()),
+            candidates.get(pair.remote_candidate_id.as_str()),
+        ) {
+            stats.selected_candidate_pair = Some(CandidatePairStats {
+                local: local.clone(),
+                remote: remote.clone(),
+                state: pair.state.to_string(),
+                nominated: pair.nominated,
+                current_rtt_ms: pair.current_round_trip_time * 1000.0,
+                total_rtt_ms: pair.total_round_trip_time * 1000.0,
+                requests_sent: pair.requests_sent,
+                responses_received: pair.responses_received,
+                bytes_sent: pair.bytes_sent,
+                bytes_received: pair.bytes_received,
+            });
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Creates a WebRTC offer on the sender side and returns it, with every ICE
+/// candidate, for the receiver.
+pub async fn create_webrtc_offer() -> Result<WebRtcSignal, Box<dyn Error>> {
+    println!("\n📡 [Sender] Initializing WebRTC offer...");
+
+    let session = new_session("sender").await?;
+    let pc = session.pc.clone();
+    println!("🆔 [Sender] Session ID: {}", session.id);
+
+    pc.on_ice_candidate(Box::new(|candidate| {
+        if let Some(c) = candidate {
+            println!("📶 [Sender] ICE Candidate: {:?}", c.to_json());
+        } else {
+            println!("✅ [Sender] ICE Gathering Complete");
+        }
+        Box::pin(async {})
+    }));
+
+    println!("📡 [Sender] Creating data channel...");
+    let channel = SendChannel::create(&pc).await?;
+
+    let offer = pc.create_offer(None).await?;
+    let offer = set_local_and_gather(&pc, offer).await?;
+    println!("📝 [Sender] Offer created and set as local description.");
+
+    *session.outgoing.lock().unwrap() = Some(channel);
+
+    println!("📤 [Sender] Returning SDP offer JSON.");
+    Ok(WebRtcSignal {
+        session_id: session.id,
+        sdp: serde_json::to_string(&offer)?,
+    })
+}
+
+/// Sets the answer as remote description of the offer's session and sends
+/// the file the same way [`crate::protocols::webrtc::send_file`] does: a
+/// header, then offset-tagged chunks until the receiver confirms every byte. There's no signaling channel to restart ICE
+/// on, so a dropped connection only gets the chance to heal by itself.
+pub async fn set_remote_description_and_send_file(
+    session_id: &str,
+    file_path: &str,
+    answer_sdp_json: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("\n📨 [Sender] Applying remote answer...");
+
+    let answer: RTCSessionDescription = serde_json::from_str(answer_sdp_json)?;
+    let session = session(session_id).ok_or_else(|| format!("Unknown WebRTC session: {}", session_id))?;
+    println!("📂 [Sender] Reading file from path: {}", file_path);
+    let outgoing = OutgoingFile::open(file_path).await?;
+    let mut channel = session
+        .outgoing
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| format!("WebRTC session {} has no offer waiting for an answer", session_id))?;
+
+    session.pc.set_remote_description(answer).await?;
+    println!("🔗 [Sender] Remote description set successfully.");
+
+    println!("⏳ [Sender] Waiting for connection to establish...");
+    if let Err(e) = session.wait_connected(Duration::from_secs(15)).await {
+        println!("❌ [Sender] Connection failed: {}", e);
+        return Err(e);
+    }
+    println!("✅ [Sender] PeerConnection state: Connected.");
+
+    let outcome = channel
+        .send(&session, None, outgoing)
+        .await
+        .map_err(|e| e.to_string());
+    session.pc.close().await?;
+    outcome?;
+    println!("🚀 [Sender] File sent via WebRTC data channel.");
+    Ok(())
+}
+
+/// Answers an offer from [`create_webrtc_offer`] and returns the answer, with
+/// every ICE candidate, for the sender. The file is received the same way
+/// [`crate::protocols::webrtc::start_receiver`] does: it waits for
+/// [`crate::protocols::approval::gate`], then is written chunk by chunk
+/// where [`crate::protocols::filename::policy`] places the sender's name,
+/// with its metadata applied.
+pub async fn create_webrtc_answer(offer_sdp_json: &str) -> Result<WebRtcSignal, Box<dyn Error>> {
+    println!("\n📡 [Receiver] Initializing WebRTC answer...");
+
+    let session = new_session("receiver").await?;
+    let pc = session.pc.clone();
+    println!("🆔 [Receiver] Session ID: {}", session.id);
+    let from = format!("WebRTC session {}", session.id);
+
+    pc.on_ice_candidate(Box::new(|candidate| {
+        if let Some(c) = candidate {
+            println!("📶 [Receiver] ICE Candidate: {:?}", c.to_json());
+        } else {
+            println!("✅ [Receiver] ICE Gathering Complete");
+        }
+        Box::pin(async {})
+    }));
+    let channels = accept_data_channels(&pc);
+
+    let offer: RTCSessionDescription = serde_json::from_str(offer_sdp_json)?;
+    pc.set_remote_description(offer).await?;
+    println!("📝 [Receiver] Offer set as remote description.");
+
+    let answer = pc.create_answer(None).await?;
+    let answer = set_local_and_gather(&pc, answer).await?;
+
+    // The sender's user still has to paste the answer, so the data channel
+    // gets as long as the session does to open.
+    tokio::spawn(async move {
+        // Without a signaling channel, no ICE restarts arrive.
+        let (_, mut restarts) = mpsc::channel(1);
+        let outcome = receive_file(&pc, channels, SESSION_TIMEOUT, from, &mut restarts)
+            .await
+            .map_err(|e| e.to_string());
+        let _ = pc.close().await;
+        match outcome {
+            Ok(received) => println!("✅ [Receiver] File saved as {}", received.path.display()),
+            Err(e) => println!("❌ [Receiver] Transfer failed: {}", e),
+        }
+    });
+
+    println!("📤 [Receiver] Returning SDP answer JSON.");
+    Ok(WebRtcSignal {
+        session_id: session.id,
+        sdp: serde_json::to_string(&answer)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+    use tokio::sync::broadcast::error::RecvError;
+
+    /// A sender with a data channel and a receiver, connected in-process.
+    async fn connected_pair() -> (WebRtcSession, WebRtcSession) {
+        let sender = new_session("sender").await.unwrap();
+        let receiver = new_session("receiver").await.unwrap();
+        let mut channels = crate::protocols::webrtc::accept_data_channels(&receiver.pc);
+        let dc = sender.pc.create_data_channel("stats", None).await.unwrap();
+
+        let offer = sender.pc.create_offer(None).await.unwrap();
+        let offer = set_local_and_gather(&sender.pc, offer).await.unwrap();
+        receiver.pc.set_remote_description(offer).await.unwrap();
+        let answer = receiver.pc.create_answer(None).await.unwrap();
+        let answer = set_local_and_gather(&receiver.pc, answer).await.unwrap();
+        sender.pc.set_remote_description(answer).await.unwrap();
+        sender.wait_connected(Duration::from_secs(10)).await.unwrap();
+        receiver.wait_connected(Duration::from_secs(10)).await.unwrap();
+
+        timeout(Duration::from_secs(10), channels.0.recv()).await.unwrap().unwrap();
+        dc.send(&Bytes::from_static(b"ping")).await.unwrap();
+        timeout(Duration::from_secs(10), channels.1.recv()).await.unwrap().unwrap();
+        (sender, receiver)
+    }
+
+    /// Waits until every `(session, layer, state)` in `expected` was
+    /// published, skipping events of other sessions.
+    async fn wait_events(events: &mut broadcast::Receiver<WebRtcStateEvent>, mut expected: Vec<(&str, &str, &str)>) {
+        let seen = timeout(Duration::from_secs(10), async {
+            while !expected.is_empty() {
+                match events.recv().await {
+                    Ok(e) => expected.retain(|&(id, layer, state)| (id, layer, state) != (&e.session_id, e.layer, &e.state)),
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => panic!("state events closed"),
+                }
+            }
+        })
+        .await;
+        assert!(seen.is_ok(), "missing state events: {:?}", expected);
+    }
+
+    #[tokio::test]
+    async fn publishes_state_changes_per_session() {
+        let mut events = subscribe_state_events();
+        let (sender, receiver) = connected_pair().await;
+
+        wait_events(
+            &mut events,
+            vec![
+                (&sender.id, "ice", "checking"),
+                (&sender.id, "ice", "connected"),
+                (&sender.id, "peer", "connected"),
+                (&receiver.id, "ice", "connected"),
+                (&receiver.id, "peer", "connected"),
+            ],
+        )
+        .await;
+
+        sender.pc.close().await.unwrap();
+        wait_events(&mut events, vec![(&sender.id, "peer", "closed")]).await;
+        assert!(session(&sender.id).is_none(), "closed sessions are dropped");
+        let _ = receiver.pc.close().await;
+    }
+
+    #[tokio::test]
+    async fn reports_stats_of_a_connected_pair() {
+        let (sender, receiver) = connected_pair().await;
+
+        let stats = session_stats(&sender.id).await.unwrap();
+        assert_eq!(stats.session_id, sender.id);
+        assert_eq!(stats.role, "sender");
+        assert_eq!(stats.connection_state, "connected");
+        assert_eq!(stats.ice_connection_state, "connected");
+        let pair = stats.selected_candidate_pair.expect("a selected candidate pair");
+        assert_eq!(pair.state, "succeeded");
+        assert_ne!(pair.local.port, 0);
+        assert_ne!(pair.remote.port, 0);
+        let channel = stats.data_channels.iter().find(|dc| dc.label == "stats").expect("the data channel");
+        assert_eq!(channel.messages_sent, 1);
+        assert_eq!(channel.bytes_sent, 4);
+
+        let _ = sender.pc.close().await;
+        let _ = receiver.pc.close().await;
+        assert!(session_stats("no-such-session").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn sends_on_the_session_the_answer_belongs_to() {
+        let dir = std::env::temp_dir().join(format!("unishare-webrtc-transfer-answer-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("note.txt");
+        std::fs::write(&path, b"sent on the first offer").unwrap();
+
+        let first = create_webrtc_offer().await.unwrap();
+        let second = create_webrtc_offer().await.unwrap();
+
+        // A bare receiver that confirms the file once every byte arrived.
+        let receiver = new_session("receiver").await.unwrap();
+        let (mut dc_rx, mut messages) = crate::protocols::webrtc::accept_data_channels(&receiver.pc);
+        let offer: RTCSessionDescription = serde_json::from_str(&first.sdp).unwrap();
+        receiver.pc.set_remote_description(offer).await.unwrap();
+        let answer = receiver.pc.create_answer(None).await.unwrap();
+        let answer = set_local_and_gather(&receiver.pc, answer).await.unwrap();
+        let receiving = tokio::spawn(async move {
+            let dc = dc_rx.recv().await.unwrap();
+            let mut received = Vec::new();
+            while received.len() < b"sent on the first offer".len() {
+                let msg = messages.recv().await.unwrap();
+                // Skip the header; chunks lead with their offset.
+                if !msg.is_string {
+                    received.extend_from_slice(&msg.data[8..]);
+                }
+            }
+            dc.send_text(r#"{"type":"done"}"#.to_string()).await.unwrap();
+            received
+        });
+
+        set_remote_description_and_send_file(&first.session_id, path.to_str().unwrap(), &serde_json::to_string(&answer).unwrap())
+            .await
+            .unwrap();
+        let received = timeout(Duration::from_secs(10), receiving).await.unwrap().unwrap();
+        assert_eq!(received, b"sent on the first offer");
+
+        let waiting = session(&second.session_id).expect("the second offer is still open");
+        assert!(waiting.outgoing.lock().unwrap().is_some(), "the second offer keeps its channel");
+        let _ = waiting.pc.close().await;
+        let _ = receiver.pc.close().await;
+        let unknown = set_remote_description_and_send_file("no-such-session", path.to_str().unwrap(), &serde_json::to_string(&first).unwrap()).await;
+        assert!(unknown.is_err());
+    }
+}
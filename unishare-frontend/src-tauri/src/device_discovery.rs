@@ -1,21 +0,0 @@
-use tauri::Manager;
-use tauri::{Emitter, Window};
-
-#[tauri::command]
-pub async fn start_hotspot_discovery(window: Window) -> Result<String, String> {
-    println!("🌐 Simulating hotspot discovery...");
-
-    for i in 1..=3 {
-        let msg = format!("Discovered Device {}", i);
-        window.emit("device-discovered", msg).unwrap();
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-    }
-
-    Ok("Discovery complete.".to_string())
-}
-
-#[tauri::command]
-pub async fn start_hotspot() -> Result<String, String> {
-    println!("📡 Simulating starting a hotspot...");
-    Ok("Hotspot started.".to_string())
-}
\ No newline at end of file
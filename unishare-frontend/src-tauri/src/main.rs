@@ -1,54 +1,110 @@
-use std::collections::HashMap;
+
+use std::sync::Arc;
+
+use tauri::{Emitter, Manager, State, Window};
+use tokio::sync::broadcast::error::RecvError;
 
 #[cfg_attr(
     all(not(debug_assertions), target_os = "windows"),
     windows_subsystem = "windows"
 )]
 
-mod protocols;
-use protocols::protocol_manager::{send_file_via_best, start_receiver};
-mod tools;
-use tools::connectivity::{check_bluetooth, check_wifi_direct, check_internet};
+use unishare_core::{device_discovery, protocols, tools, webrtc_transfer};
+#[cfg(unix)]
+use unishare_core::daemon::rpc::Event;
+#[cfg(unix)]
+use unishare_core::daemon::{Client, Transfer, TransferState};
+use protocols::protocol_manager::{send_file_to_device, send_file_via_best, start_receiver};
+use tools::connectivity::{connectivity_report, network_interfaces, ConnectivityReport, NetworkInterface};
+use tools::internet::InternetConfig;
+use tools::watcher::watcher;
 
-mod device_discovery;
-use device_discovery::{start_hotspot_discovery, start_hotspot};
-use protocols::bluetooth;
+use device_discovery::{mdns, DeviceRegistry, DiscoveryEvent, HotspotController, HotspotInfo, KnownDevice};
+use device_discovery::beacon::{self, BeaconConfig};
+use protocols::{bluetooth, metered, mobiledata};
+#[cfg(unix)]
+use protocols::p2p;
+use protocols::filename::ReceivedFile;
 
-mod webrtc_transfer;
 use webrtc_transfer::{
     create_webrtc_offer,
     set_remote_description_and_send_file,
     create_webrtc_answer,
+    session_stats,
+    subscribe_state_events,
+    WebRtcSignal,
+    WebRtcStats,
 };
 
+/// Turns a finished daemon transfer into a command result.
+#[cfg(unix)]
+fn daemon_outcome(transfer: Transfer) -> Result<String, String> {
+    let message = transfer.message.unwrap_or_default();
+    match transfer.state {
+        TransferState::Done => Ok(message),
+        _ => Err(message),
+    }
+}
+
+/// The running daemon, for commands that only make sense with one.
+#[cfg(unix)]
+async fn daemon_client() -> Result<Client, String> {
+    Client::connect_running()
+        .await
+        .ok_or_else(|| "No Unishare daemon is running.".to_string())
+}
+
+/// What the daemon commands report where there is no daemon; everything else
+/// runs in-process.
+#[cfg(not(unix))]
+const NO_DAEMON: &str = "The Unishare daemon needs Unix domain sockets, which this platform doesn't have.";
+
+/// `destination` may be a discovered device's ID or name, an address, or a relay ticket.
+/// With a daemon running, it does the sending.
 #[tauri::command]
-async fn send_file(file_path: String, destination: String) -> Result<String, String> {
-    match send_file_via_best(&file_path, &destination).await {
-        Ok(msg) => Ok(msg),
-        Err(e) => Err(e.to_string()),
+async fn send_file(
+    file_path: String,
+    destination: String,
+    registry: State<'_, DeviceRegistry>,
+) -> Result<String, String> {
+    #[cfg(unix)]
+    if let Some(mut client) = Client::connect_running().await {
+        client.subscribe().await.map_err(|e| e.to_string())?;
+        let transfer = client.send(&file_path, &destination, None).await.map_err(|e| e.to_string())?;
+        return daemon_outcome(client.wait(&transfer.id).await.map_err(|e| e.to_string())?);
     }
+    let result = match registry.resolve(&destination)? {
+        Some(known) => send_file_to_device(&file_path, &known.device).await,
+        None => send_file_via_best(&file_path, &destination).await,
+    };
+    result.map_err(|e| e.to_string())
 }
 
+/// With a daemon running, its receivers already hold the ports, so this
+/// accepts the next file it's offered instead.
 #[tauri::command]
 async fn receive_file() -> Result<String, String> {
+    #[cfg(unix)]
+    if let Some(mut client) = Client::connect_running().await {
+        return daemon_outcome(client.receive_next().await.map_err(|e| e.to_string())?);
+    }
     match start_receiver().await {
-        Ok(msg) => Ok(msg),
+        Ok(received) => Ok(received.message),
         Err(e) => Err(e.to_string()),
     }
 }
 
 #[tauri::command]
-async fn check_connectivity_status() -> Result<HashMap<String, bool>, String> {
-    let bluetooth_enabled = check_bluetooth().unwrap_or(false);
-    let wifi_direct_enabled = check_wifi_direct().unwrap_or(false);
-    let internet_connected = check_internet().unwrap_or(false);
-
-    let mut status = HashMap::new();
-    status.insert("bluetooth".to_string(), bluetooth_enabled);
-    status.insert("wifiDirect".to_string(), wifi_direct_enabled);
-    status.insert("internet".to_string(), internet_connected);
+async fn check_connectivity_status() -> Result<ConnectivityReport, String> {
+    connectivity_report(&InternetConfig::from_env()).await
+}
 
-    Ok(status)
+/// Lists network interfaces, marking those peers can reach our receivers on.
+#[tauri::command]
+async fn list_interfaces() -> Result<Vec<NetworkInterface>, String> {
+    tauri::async_runtime::spawn_blocking(network_interfaces)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -67,43 +123,334 @@ async fn receive_file_bluetooth() -> Result<String, String> {
     }
 }
 
+/// Starts browsing for other Unishare instances. Devices already known are
+/// re-sent to `window` straight away; new and lost devices arrive as
+/// `device-discovered` / `device-lost` events.
+#[tauri::command]
+async fn start_hotspot_discovery(
+    window: Window,
+    registry: State<'_, DeviceRegistry>,
+) -> Result<String, String> {
+    println!("🌐 Browsing for Unishare devices...");
+    mdns::browse(registry.inner().clone()).map_err(|e| e.to_string())?;
+
+    for known in registry.devices() {
+        window.emit("device-discovered", known.device).map_err(|e| e.to_string())?;
+    }
+
+    Ok("Discovery started.".to_string())
+}
+
+/// Lists every device currently visible, most recently seen first.
 #[tauri::command]
-async fn start_webrtc_sending(file_path: String) -> Result<String, String> {
+async fn list_devices(registry: State<'_, DeviceRegistry>) -> Result<Vec<KnownDevice>, String> {
+    Ok(registry.devices())
+}
+
+/// Opens a Wi‑Fi access point peers can join to reach this device. Returns
+/// the SSID, passphrase and our gateway address on it.
+#[tauri::command]
+async fn start_hotspot(hotspot: State<'_, Arc<HotspotController>>) -> Result<HotspotInfo, String> {
+    println!("📡 Starting a hotspot...");
+    let hotspot = hotspot.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || hotspot.start().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn stop_hotspot(hotspot: State<'_, Arc<HotspotController>>) -> Result<String, String> {
+    let hotspot = hotspot.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || hotspot.stop().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok("Hotspot stopped.".to_string())
+}
+
+#[tauri::command]
+async fn start_webrtc_sending(file_path: String) -> Result<WebRtcSignal, String> {
     match create_webrtc_offer().await {
-        Ok(offer_sdp_json) => Ok(offer_sdp_json),
+        Ok(offer) => Ok(offer),
         Err(e) => Err(e.to_string()),
     }
 }
 
 #[tauri::command]
-async fn complete_webrtc_sending(file_path: String, answer_sdp_json: String) -> Result<String, String> {
-    match set_remote_description_and_send_file(&file_path, &answer_sdp_json).await {
+async fn complete_webrtc_sending(session_id: String, file_path: String, answer_sdp_json: String) -> Result<String, String> {
+    match set_remote_description_and_send_file(&session_id, &file_path, &answer_sdp_json).await {
         Ok(_) => Ok("File sent via WebRTC successfully".into()),
         Err(e) => Err(e.to_string()),
     }
 }
 
 #[tauri::command]
-async fn receive_webrtc_file(offer_sdp_json: String) -> Result<String, String> {
+async fn receive_webrtc_file(offer_sdp_json: String) -> Result<WebRtcSignal, String> {
     match create_webrtc_answer(&offer_sdp_json).await {
-        Ok(answer_sdp_json) => Ok(answer_sdp_json),
+        Ok(answer) => Ok(answer),
         Err(e) => Err(e.to_string()),
     }
 }
 
-fn main() {
+#[tauri::command]
+async fn webrtc_stats(session_id: String) -> Result<WebRtcStats, String> {
+    session_stats(&session_id).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn create_relay_ticket() -> Result<String, String> {
+    mobiledata::create_ticket().await.map_err(|e| format!("Relay error: {}", e))
+}
+
+#[tauri::command]
+async fn receive_file_relay(ticket: String) -> Result<ReceivedFile, String> {
+    mobiledata::receive_file(&ticket).await.map_err(|e| format!("Relay error: {}", e))
+}
+
+/// Answers a `metered-confirmation` prompt for a send over a metered
+/// connection, ours or the daemon's.
+#[tauri::command]
+async fn answer_metered_confirmation(id: String, allow: bool) -> Result<(), String> {
+    let answered = metered::guard().answer(&id, allow);
+    #[cfg(unix)]
+    if answered.is_err() {
+        return daemon_client().await?.confirm(&id, allow).await.map_err(|e| e.to_string());
+    }
+    answered
+}
+
+/// Lists the daemon's transfers.
+#[cfg(unix)]
+#[tauri::command]
+async fn list_transfers() -> Result<Vec<Transfer>, String> {
+    daemon_client().await?.transfers().await.map_err(|e| e.to_string())
+}
+
+/// Accepts or rejects a file the daemon was offered (a `transfer-updated`
+/// event in the `pending` state).
+#[cfg(unix)]
+#[tauri::command]
+async fn answer_transfer(id: String, accept: bool) -> Result<Transfer, String> {
+    let mut client = daemon_client().await?;
+    let answered = if accept { client.accept(&id).await } else { client.reject(&id).await };
+    answered.map_err(|e| e.to_string())
+}
+
+#[cfg(unix)]
+#[tauri::command]
+async fn cancel_transfer(id: String) -> Result<Transfer, String> {
+    daemon_client().await?.cancel(&id).await.map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+#[tauri::command]
+async fn list_transfers() -> Result<Vec<serde_json::Value>, String> {
+    Err(NO_DAEMON.to_string())
+}
+
+#[cfg(not(unix))]
+#[tauri::command]
+async fn answer_transfer(id: String, accept: bool) -> Result<serde_json::Value, String> {
+    let _ = (id, accept);
+    Err(NO_DAEMON.to_string())
+}
+
+#[cfg(not(unix))]
+#[tauri::command]
+async fn cancel_transfer(id: String) -> Result<serde_json::Value, String> {
+    let _ = id;
+    Err(NO_DAEMON.to_string())
+}
+
+#[cfg(unix)]
+#[tauri::command]
+async fn wifi_direct_find(timeout_secs: Option<u64>) -> Result<Vec<p2p::P2pPeer>, String> {
+    let mut p2p = p2p::open_default().await.map_err(|e| format!("Wi‑Fi Direct error: {}", e))?;
+    let duration = std::time::Duration::from_secs(timeout_secs.unwrap_or(10));
+    p2p.find(duration).await.map_err(|e| format!("Wi‑Fi Direct error: {}", e))
+}
+
+#[cfg(unix)]
+#[tauri::command]
+async fn wifi_direct_connect(peer: String, go_intent: Option<u8>) -> Result<p2p::P2pGroup, String> {
+    let mut p2p = p2p::open_default().await.map_err(|e| format!("Wi‑Fi Direct error: {}", e))?;
+    p2p.connect(&peer, go_intent.unwrap_or(7))
+        .await
+        .map_err(|e| format!("Wi‑Fi Direct error: {}", e))
+}
+
+#[cfg(unix)]
+#[tauri::command]
+async fn wifi_direct_disconnect() -> Result<(), String> {
+    let Some(interface) = p2p::active_group() else {
+        return Ok(());
+    };
+    let mut p2p = p2p::open_default().await.map_err(|e| format!("Wi‑Fi Direct error: {}", e))?;
+    p2p.remove_group(&interface)
+        .await
+        .map_err(|e| format!("Wi‑Fi Direct error: {}", e))
+}
+
+/// What the Wi‑Fi Direct commands report where wpa_supplicant's Unix domain
+/// control socket doesn't exist.
+#[cfg(not(unix))]
+const NO_WIFI_DIRECT: &str = "Wi‑Fi Direct needs wpa_supplicant, which this platform doesn't have.";
+
+#[cfg(not(unix))]
+#[tauri::command]
+async fn wifi_direct_find(timeout_secs: Option<u64>) -> Result<Vec<serde_json::Value>, String> {
+    let _ = timeout_secs;
+    Err(NO_WIFI_DIRECT.to_string())
+}
+
+#[cfg(not(unix))]
+#[tauri::command]
+async fn wifi_direct_connect(peer: String, go_intent: Option<u8>) -> Result<serde_json::Value, String> {
+    let _ = (peer, go_intent);
+    Err(NO_WIFI_DIRECT.to_string())
+}
+
+#[cfg(not(unix))]
+#[tauri::command]
+async fn wifi_direct_disconnect() -> Result<(), String> {
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    // Core spawns its background work with tokio; run Tauri on the same runtime.
+    tauri::async_runtime::set(tokio::runtime::Handle::current());
     tauri::Builder::default()
+        .setup(|app| {
+            // Forward WebRTC session state changes to the UI.
+            let handle = app.app_handle().clone();
+            let mut events = subscribe_state_events();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    match events.recv().await {
+                        Ok(event) => {
+                            let _ = handle.emit("webrtc-state", event);
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Push connectivity changes to the UI as they happen.
+            watcher().start(InternetConfig::from_env());
+            let handle = app.app_handle().clone();
+            let mut connectivity = watcher().subscribe();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    match connectivity.recv().await {
+                        Ok(report) => {
+                            let _ = handle.emit("connectivity-changed", report);
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Ask the user before large sends go over a metered connection.
+            let handle = app.app_handle().clone();
+            let mut confirmations = metered::guard().subscribe();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    match confirmations.recv().await {
+                        Ok(request) => {
+                            let _ = handle.emit("metered-confirmation", request);
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Forward the daemon's transfers and metered prompts, if one is running.
+            #[cfg(unix)]
+            let handle = app.app_handle().clone();
+            #[cfg(unix)]
+            tauri::async_runtime::spawn(async move {
+                let Some(mut client) = Client::connect_running().await else {
+                    return;
+                };
+                if let Err(e) = client.subscribe().await {
+                    println!("Can't follow the daemon: {}", e);
+                    return;
+                }
+                while let Ok(event) = client.next_event().await {
+                    let _ = match event {
+                        Event::Transfer { transfer } => handle.emit("transfer-updated", transfer),
+                        Event::MeteredConfirmation { request } => handle.emit("metered-confirmation", request),
+                        _ => Ok(()),
+                    };
+                }
+            });
+
+            // Advertise this instance and forward discovered/lost devices to the UI.
+            let registry = DeviceRegistry::new();
+            app.manage(registry.clone());
+            registry.spawn_expiry();
+            app.manage(Arc::new(HotspotController::with_nmcli()));
+            if let Err(e) = mdns::advertise() {
+                println!("mDNS advertising failed: {}", e);
+            }
+            // UDP beacons cover networks that block multicast.
+            let beacon_registry = registry.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = beacon::start(BeaconConfig::from_env(), beacon_registry).await {
+                    println!("Beacon discovery unavailable: {}", e);
+                }
+            });
+            // Let senders ask which receivers we have running.
+            tauri::async_runtime::spawn(async {
+                if let Err(e) = protocols::capabilities::serve_hello().await {
+                    println!("Capability queries unavailable: {}", e);
+                }
+            });
+            let handle = app.app_handle().clone();
+            let mut discovery = registry.subscribe();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    match discovery.recv().await {
+                        Ok(DiscoveryEvent::Discovered(device)) => {
+                            let _ = handle.emit("device-discovered", device);
+                        }
+                        Ok(DiscoveryEvent::Lost { id }) => {
+                            let _ = handle.emit("device-lost", id);
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             send_file,
             receive_file,
             send_file_bluetooth,
             receive_file_bluetooth,
             check_connectivity_status,
+            list_interfaces,
             start_hotspot,
+            stop_hotspot,
             start_hotspot_discovery,
+            list_devices,
             start_webrtc_sending,
             complete_webrtc_sending,
-            receive_webrtc_file
+            receive_webrtc_file,
+            webrtc_stats,
+            create_relay_ticket,
+            receive_file_relay,
+            answer_metered_confirmation,
+            list_transfers,
+            answer_transfer,
+            cancel_transfer,
+            wifi_direct_find,
+            wifi_direct_connect,
+            wifi_direct_disconnect
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -22,11 +22,26 @@ import { Wifi, Bluetooth, Globe, Shield, ArrowUpDown } from "lucide-react";
 import { WifiDirect } from "@/components/wifi-direct";
 
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { useEffect, useState } from "react";
 import { BluetoothSend } from "@/components/bluetooth";
 import { WebRTC } from "@/components/webrtc";
 
 
+type ConnectivityReport = {
+  bluetooth: { name: string; available: boolean }[];
+  wifi: { phy: string; interfaces: string[]; p2p: boolean; available: boolean }[];
+  wifiDirect: boolean;
+  internet: { state: "online" | "captivePortal" | "offline"; portalUrl: string | null };
+};
+
+type MeteredConfirmation = {
+  id: string;
+  fileName: string;
+  size: number;
+  metered: string;
+};
+
 export default function Home() {
   const [status, setStatus] = useState({
     wifiDirect: false,
@@ -35,13 +50,39 @@ export default function Home() {
   });
 
   useEffect(() => {
-    invoke("check_connectivity_status")
-      .then((res) => {
-        setStatus(res as typeof status);
-      })
+    const applyReport = (report: ConnectivityReport) => {
+      setStatus({
+        wifiDirect: report.wifiDirect,
+        bluetooth: report.bluetooth.some((adapter) => adapter.available),
+        internet: report.internet.state === "online",
+      });
+    };
+
+    invoke<ConnectivityReport>("check_connectivity_status")
+      .then(applyReport)
       .catch((err) => {
         console.error("Failed to check status:", err);
       });
+
+    // The backend pushes a new report whenever a radio, link or route changes.
+    const unlisten = listen<ConnectivityReport>("connectivity-changed", (event) => {
+      applyReport(event.payload);
+    });
+    // Large sends over a metered connection wait for the user's go-ahead.
+    const unlistenMetered = listen<MeteredConfirmation>("metered-confirmation", (event) => {
+      const { id, fileName, size } = event.payload;
+      const megabytes = (size / (1024 * 1024)).toFixed(1);
+      const allow = window.confirm(
+        `You're on a metered connection. Send ${fileName} (${megabytes} MB) anyway?`
+      );
+      invoke("answer_metered_confirmation", { id, allow }).catch((err) => {
+        console.error("Failed to answer metered confirmation:", err);
+      });
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+      unlistenMetered.then((stop) => stop());
+    };
   }, []);
 
   const formatStatus = (value: boolean) => (value ? "Available" : "Available");
@@ -28,6 +28,25 @@ type Device = {
   type: "desktop" | "laptop" | "mobile";
   connectionType: "wifi" | "bluetooth" | "internet";
   status: "available" | "connected" | "offline";
+  address?: string;
+};
+
+// Returned by the "start_hotspot" command
+type HotspotInfo = {
+  ssid: string;
+  passphrase: string;
+  gateway: string;
+  interface: string;
+};
+
+// Payload of the "device-discovered" event
+type DiscoveredDevice = {
+  id: string;
+  name: string;
+  addresses: string[];
+  ports: Record<string, number>;
+  transports: string[];
+  sources: string[];
 };
 
 export function DeviceConnector() {
@@ -35,60 +54,38 @@ export function DeviceConnector() {
   const [connectionCode, setConnectionCode] = useState("");
   const [showQRCode, setShowQRCode] = useState(false);
 
-  // On mount, let's hardcode some initial devicess
+  // Listen for Tauri events: "device-discovered" / "device-lost"
   useEffect(() => {
-    setDevices([
-      {
-        id: "1",
-        name: "MacBook Pro",
-        type: "laptop",
-        connectionType: "wifi",
-        status: "available",
-      },
-      {
-        id: "2",
-        name: "iPhone 13",
-        type: "mobile",
-        connectionType: "wifi",
-        status: "available",
-      },
-      {
-        id: "3",
-        name: "Windows PC",
-        type: "desktop",
-        connectionType: "internet",
-        status: "available",
-      },
-      {
-        id: "4",
-        name: "Android Tablet",
-        type: "mobile",
-        connectionType: "bluetooth",
-        status: "offline",
-      },
-    ]);
-  }, []);
-
-  // Listen for Tauri events: "device-discovered"
-  useEffect(() => {
-    const unlisten = listen<string>("device-discovered", (event) => {
-      const msg = event.payload;
-      console.log("Device discovered from Rust:", msg);
-      // Parse msg if needed; For now, just push a dummy device
-      // In a real scenario, you'd parse out device info from the broadcast
-      setDevices((prev) => [
-        ...prev,
-        {
-          id: Date.now().toString(),
-          name: msg,
-          type: "desktop",
-          connectionType: "wifi",
-          status: "available",
-        },
-      ]);
+    const unlistenDiscovered = listen<DiscoveredDevice>(
+      "device-discovered",
+      (event) => {
+        const found = event.payload;
+        setDevices((prev) => {
+          const existing = prev.find((device) => device.id === found.id);
+          const device: Device = {
+            id: found.id,
+            name: found.name,
+            type: existing?.type ?? "desktop",
+            connectionType: "wifi",
+            status: existing?.status === "connected" ? "connected" : "available",
+            address: found.addresses[0],
+          };
+          return existing
+            ? prev.map((d) => (d.id === found.id ? device : d))
+            : [...prev, device];
+        });
+      }
+    );
+    const unlistenLost = listen<string>("device-lost", (event) => {
+      setDevices((prev) =>
+        prev.map((device) =>
+          device.id === event.payload ? { ...device, status: "offline" } : device
+        )
+      );
     });
     return () => {
-      unlisten.then((fn) => fn()); // Stop listening on unmount
+      unlistenDiscovered.then((fn) => fn()); // Stop listening on unmount
+      unlistenLost.then((fn) => fn());
     };
   }, []);
 
@@ -109,8 +106,10 @@ export function DeviceConnector() {
   // Tauri commands
   async function openHotspot() {
     try {
-      await invoke("start_hotspot");
-      alert("Hotspot opened successfully (placeholder).");
+      const hotspot = await invoke<HotspotInfo>("start_hotspot");
+      alert(
+        `Hotspot "${hotspot.ssid}" is open.\nPassword: ${hotspot.passphrase}\nSend to: ${hotspot.gateway}`
+      );
     } catch (error) {
       console.error("Error opening hotspot:", error);
     }
@@ -120,7 +119,7 @@ export function DeviceConnector() {
     try {
       // Start the discovery in Rust
       await invoke("start_hotspot_discovery");
-      alert("Started scanning for devices on UDP port 9001.");
+      alert("Scanning for Unishare devices on the local network.");
     } catch (error) {
       console.error("Error scanning devices:", error);
     }
@@ -8,12 +8,19 @@ import { Textarea } from "@/components/ui/textarea";
 import { Alert, AlertDescription } from "@/components/ui/alert";
 import { Copy } from "lucide-react";
 
+/** An SDP offer or answer, with the session it belongs to. */
+interface WebRtcSignal {
+    sessionId: string;
+    sdp: string;
+}
+
 export function WebRTC() {
     const [destinationIp, setDestinationIp] = useState("");
     const [tcpMessage, setTcpMessage] = useState("");
     const [filePath, setFilePath] = useState("../test.txt");
     const [webrtcOffer, setWebrtcOffer] = useState("");
     const [webrtcAnswer, setWebrtcAnswer] = useState("");
+    const [sendingSessionId, setSendingSessionId] = useState("");
     const [webrtcStatus, setWebrtcStatus] = useState("");
 
     async function sendFileTCP() {
@@ -43,9 +50,10 @@ export function WebRTC() {
 
     async function startWebrtcOffer() {
         try {
-            const offer = await invoke("start_webrtc_sending", { filePath });
-            setWebrtcOffer(offer as string);
-            setWebrtcStatus("Offer created. Copy it and send it to the receiver.");
+            const offer = await invoke<WebRtcSignal>("start_webrtc_sending", { filePath });
+            setWebrtcOffer(offer.sdp);
+            setSendingSessionId(offer.sessionId);
+            setWebrtcStatus(`Offer created for session ${offer.sessionId}. Copy it and send it to the receiver.`);
         } catch (error) {
             setWebrtcStatus(`Error creating offer: ${error}`);
         }
@@ -56,8 +64,13 @@ export function WebRTC() {
             setWebrtcStatus("Please paste the receiver's answer in the designated field.");
             return;
         }
+        if (!sendingSessionId) {
+            setWebrtcStatus("Please create an offer before sending.");
+            return;
+        }
         try {
             const response = await invoke("complete_webrtc_sending", {
+                sessionId: sendingSessionId,
                 filePath,
                 answerSdpJson: webrtcAnswer,
             });
@@ -73,11 +86,11 @@ export function WebRTC() {
             return;
         }
         try {
-            const answer = await invoke("receive_webrtc_file", {
+            const answer = await invoke<WebRtcSignal>("receive_webrtc_file", {
                 offerSdpJson: webrtcOffer,
             });
-            setWebrtcAnswer(answer as string);
-            setWebrtcStatus("Answer generated. Copy it and send it back to the sender.");
+            setWebrtcAnswer(answer.sdp);
+            setWebrtcStatus(`Answer generated for session ${answer.sessionId}. Copy it and send it back to the sender.`);
         } catch (error) {
             setWebrtcStatus(`❌ Error generating answer: ${error}`);
         }
@@ -0,0 +1,418 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::SlotInfo;
+
+/// Plaintext bytes per data chunk. Sealed chunks add a flag byte and a
+/// 16-byte tag, which stays well under the relay's default chunk limit.
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+const FLAG_MORE: u8 = 0;
+const FLAG_LAST: u8 = 1;
+const UPLOAD_ATTEMPTS: u32 = 5;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum RelayError {
+    /// The relay couldn't be reached or the connection broke.
+    Http(reqwest::Error),
+    Io(io::Error),
+    /// The relay refused a request.
+    Status(StatusCode, String),
+    InvalidTicket(&'static str),
+    /// A chunk failed authentication: wrong key, or altered in transit.
+    Decrypt(u64),
+    /// The chunk stream didn't match its header.
+    Protocol(String),
+}
+
+impl fmt::Display for RelayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayError::Http(e) => write!(f, "relay request failed: {}", e),
+            RelayError::Io(e) => write!(f, "{}", e),
+            RelayError::Status(status, body) => write!(f, "relay returned {}: {}", status, body),
+            RelayError::InvalidTicket(why) => write!(f, "invalid relay ticket: {}", why),
+            RelayError::Decrypt(index) => write!(f, "chunk {} failed to decrypt", index),
+            RelayError::Protocol(why) => write!(f, "{}", why),
+        }
+    }
+}
+
+impl std::error::Error for RelayError {}
+
+impl From<reqwest::Error> for RelayError {
+    fn from(e: reqwest::Error) -> Self {
+        RelayError::Http(e)
+    }
+}
+
+impl From<io::Error> for RelayError {
+    fn from(e: io::Error) -> Self {
+        RelayError::Io(e)
+    }
+}
+
+/// Everything a sender needs to deliver a file: where the slot is and the key
+/// to seal chunks with. Written as `<relay url>/slots/<id>#<key>`; the key sits
+/// in the URL fragment, so it is never sent to the relay.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Ticket {
+    pub relay_url: String,
+    pub slot_id: String,
+    key: [u8; 32],
+}
+
+impl fmt::Debug for Ticket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ticket")
+            .field("relay_url", &self.relay_url)
+            .field("slot_id", &self.slot_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Ticket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/slots/{}#{}",
+            self.relay_url,
+            self.slot_id,
+            URL_SAFE_NO_PAD.encode(self.key)
+        )
+    }
+}
+
+impl FromStr for Ticket {
+    type Err = RelayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (url, key) = s
+            .trim()
+            .split_once('#')
+            .ok_or(RelayError::InvalidTicket("missing key"))?;
+        let (relay_url, slot_id) = url
+            .rsplit_once("/slots/")
+            .ok_or(RelayError::InvalidTicket("missing slot"))?;
+        if !relay_url.starts_with("http://") && !relay_url.starts_with("https://") {
+            return Err(RelayError::InvalidTicket("relay URL must be http(s)"));
+        }
+        if slot_id.is_empty() || !slot_id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(RelayError::InvalidTicket("malformed slot id"));
+        }
+        let key: [u8; 32] = URL_SAFE_NO_PAD
+            .decode(key)
+            .ok()
+            .and_then(|k| k.try_into().ok())
+            .ok_or(RelayError::InvalidTicket("malformed key"))?;
+
+        Ok(Ticket {
+            relay_url: relay_url.to_string(),
+            slot_id: slot_id.to_string(),
+            key,
+        })
+    }
+}
+
+impl Ticket {
+    fn chunk_url(&self, index: u64) -> String {
+        format!("{}/slots/{}/chunks/{}", self.relay_url, self.slot_id, index)
+    }
+
+    /// Nonces only need to be unique per key, and every ticket has its own
+    /// key, so the chunk index is enough.
+    fn nonce(index: u64) -> Nonce {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&index.to_be_bytes());
+        nonce.into()
+    }
+
+    /// Encrypts a chunk as `[flag] ++ ciphertext`, authenticating the flag so
+    /// the relay can't truncate the stream by dropping the last chunk.
+    fn seal(&self, index: u64, last: bool, plaintext: &[u8]) -> Vec<u8> {
+        let flag = if last { FLAG_LAST } else { FLAG_MORE };
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&self.key));
+        let sealed = cipher
+            .encrypt(
+                &Self::nonce(index),
+                Payload {
+                    msg: plaintext,
+                    aad: &[flag],
+                },
+            )
+            .expect("ChaCha20Poly1305 encryption cannot fail for in-memory buffers");
+
+        let mut body = Vec::with_capacity(1 + sealed.len());
+        body.push(flag);
+        body.extend_from_slice(&sealed);
+        body
+    }
+
+    /// Reverses [`Ticket::seal`], returning the plaintext and whether this was
+    /// the final chunk.
+    fn open(&self, index: u64, body: &[u8]) -> Result<(Vec<u8>, bool), RelayError> {
+        let (&flag, sealed) = body.split_first().ok_or(RelayError::Decrypt(index))?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&self.key));
+        let plaintext = cipher
+            .decrypt(
+                &Self::nonce(index),
+                Payload {
+                    msg: sealed,
+                    aad: &[flag],
+                },
+            )
+            .map_err(|_| RelayError::Decrypt(index))?;
+        Ok((plaintext, flag == FLAG_LAST))
+    }
+}
+
+/// Chunk 0 of every slot. Encrypted like the data, so the relay doesn't learn
+/// the file name either.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileHeader {
+    pub name: String,
+    pub size: u64,
+    /// Whatever the sending app records about the file (times, permissions,
+    /// ...). The relay client passes it through without looking at it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+fn http() -> Result<reqwest::Client, RelayError> {
+    Ok(reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?)
+}
+
+async fn error_for_status(response: reqwest::Response) -> Result<reqwest::Response, RelayError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(RelayError::Status(status, body))
+}
+
+/// Opens a slot on the relay at `relay_url` and returns a fresh ticket for it.
+/// The receiver calls this and hands the ticket to the sender.
+pub async fn create_ticket(relay_url: &str) -> Result<(Ticket, SlotInfo), RelayError> {
+    let relay_url = relay_url.trim_end_matches('/').to_string();
+    let response = http()?.post(format!("{}/slots", relay_url)).send().await?;
+    let info: SlotInfo = error_for_status(response).await?.json().await?;
+
+    let ticket = Ticket {
+        relay_url,
+        slot_id: info.id.clone(),
+        key: rand::random(),
+    };
+    Ok((ticket, info))
+}
+
+/// Encrypts and uploads `file_path` into the ticket's slot. Chunks that fail
+/// on a flaky connection are retried; the upload finishes as soon as the last
+/// chunk is on the relay, whether or not the receiver has started downloading.
+pub async fn upload_file(ticket: &Ticket, file_path: &str) -> Result<FileHeader, RelayError> {
+    upload_file_with_metadata(ticket, file_path, None).await
+}
+
+/// Like [`upload_file`], with `metadata` carried to the receiver in the
+/// encrypted [`FileHeader`].
+pub async fn upload_file_with_metadata(
+    ticket: &Ticket,
+    file_path: &str,
+    metadata: Option<serde_json::Value>,
+) -> Result<FileHeader, RelayError> {
+    let client = http()?;
+    let mut file = tokio::fs::File::open(file_path).await?;
+    let header = FileHeader {
+        name: Path::new(file_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "file".to_string()),
+        size: file.metadata().await?.len(),
+        metadata,
+    };
+
+    let header_json = serde_json::to_vec(&header).expect("header serializes");
+    put_chunk(
+        &client,
+        ticket,
+        0,
+        ticket.seal(0, header.size == 0, &header_json),
+    )
+    .await?;
+
+    let mut sent = 0u64;
+    let mut index = 1u64;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    while sent < header.size {
+        let len = read_full(&mut file, &mut buffer).await?;
+        if len == 0 {
+            return Err(RelayError::Protocol(format!(
+                "{} shrank while uploading ({} of {} bytes)",
+                file_path, sent, header.size
+            )));
+        }
+        sent += len as u64;
+        let body = ticket.seal(index, sent >= header.size, &buffer[..len]);
+        put_chunk(&client, ticket, index, body).await?;
+        index += 1;
+    }
+
+    Ok(header)
+}
+
+/// Fills `buffer` unless the file ends first, so every chunk but the last is
+/// exactly [`CHUNK_SIZE`].
+async fn read_full(file: &mut tokio::fs::File, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let n = file.read(&mut buffer[filled..]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
+
+async fn put_chunk(
+    client: &reqwest::Client,
+    ticket: &Ticket,
+    index: u64,
+    body: Vec<u8>,
+) -> Result<(), RelayError> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let result = client
+            .put(ticket.chunk_url(index))
+            .body(body.clone())
+            .send()
+            .await;
+        let retry = match result {
+            Ok(response) => match response.status() {
+                status if status.is_success() => return Ok(()),
+                // An earlier attempt landed but its response got lost.
+                StatusCode::CONFLICT if attempt > 1 => return Ok(()),
+                status if status.is_server_error() => error_for_status(response).await.unwrap_err(),
+                _ => return Err(error_for_status(response).await.unwrap_err()),
+            },
+            Err(e) => RelayError::Http(e),
+        };
+
+        if attempt >= UPLOAD_ATTEMPTS {
+            return Err(retry);
+        }
+        println!(
+            "Relay upload of chunk {} failed ({}), retrying",
+            index, retry
+        );
+        tokio::time::sleep(RETRY_DELAY * attempt).await;
+    }
+}
+
+/// Downloads the ticket's slot into `dest_path`, waiting for chunks the sender
+/// hasn't uploaded yet. Gives up when the slot expires. The slot is deleted
+/// from the relay once the whole file has arrived.
+pub async fn download_file(ticket: &Ticket, dest_path: &Path) -> Result<FileHeader, RelayError> {
+    let header = download_header(ticket).await?;
+    download_body(ticket, &header, dest_path).await?;
+    Ok(header)
+}
+
+/// Waits for the sender's [`FileHeader`], so the receiver can decide about
+/// the file before anything is written. Continue with [`download_body`].
+pub async fn download_header(ticket: &Ticket) -> Result<FileHeader, RelayError> {
+    let client = http()?;
+    let (header_json, last) = ticket.open(0, &get_chunk(&client, ticket, 0).await?)?;
+    let header: FileHeader = serde_json::from_slice(&header_json)
+        .map_err(|e| RelayError::Protocol(format!("bad file header: {}", e)))?;
+    // An empty file has no data chunks; anything else has at least one.
+    if last != (header.size == 0) {
+        return Err(RelayError::Protocol("header doesn't match the chunk stream".into()));
+    }
+    Ok(header)
+}
+
+/// Downloads the data behind `header` into `dest_path` and deletes the slot,
+/// like [`download_file`] does after the header.
+pub async fn download_body(ticket: &Ticket, header: &FileHeader, dest_path: &Path) -> Result<(), RelayError> {
+    let client = http()?;
+    let mut last = header.size == 0;
+    let mut file = tokio::fs::File::create(dest_path).await?;
+    let mut received = 0u64;
+    let mut index = 1u64;
+    while !last {
+        let (data, is_last) = ticket.open(index, &get_chunk(&client, ticket, index).await?)?;
+        received += data.len() as u64;
+        if received > header.size {
+            return Err(RelayError::Protocol(
+                "more data than the header announced".into(),
+            ));
+        }
+        file.write_all(&data).await?;
+        last = is_last;
+        index += 1;
+    }
+    file.flush().await?;
+
+    if received != header.size {
+        return Err(RelayError::Protocol(format!(
+            "expected {} bytes, received {}",
+            header.size, received
+        )));
+    }
+
+    let _ = client
+        .delete(format!("{}/slots/{}", ticket.relay_url, ticket.slot_id))
+        .send()
+        .await;
+    Ok(())
+}
+
+async fn get_chunk(
+    client: &reqwest::Client,
+    ticket: &Ticket,
+    index: u64,
+) -> Result<Vec<u8>, RelayError> {
+    let mut failures = 0;
+    loop {
+        match client.get(ticket.chunk_url(index)).send().await {
+            Ok(response) if response.status().is_success() => match response.bytes().await {
+                Ok(bytes) => return Ok(bytes.to_vec()),
+                Err(e) if failures < UPLOAD_ATTEMPTS => {
+                    failures += 1;
+                    println!("Relay download of chunk {} failed ({}), retrying", index, e);
+                }
+                Err(e) => return Err(e.into()),
+            },
+            // Not uploaded yet; keep waiting until the slot expires.
+            Ok(response) if response.status() == StatusCode::TOO_EARLY => failures = 0,
+            Ok(response) if response.status().is_server_error() && failures < UPLOAD_ATTEMPTS => {
+                failures += 1;
+            }
+            Ok(response) => return Err(error_for_status(response).await.unwrap_err()),
+            Err(e) if failures < UPLOAD_ATTEMPTS => {
+                failures += 1;
+                println!("Relay download of chunk {} failed ({}), retrying", index, e);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
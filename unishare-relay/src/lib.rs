@@ -0,0 +1,25 @@
+//! Relay for Unishare's mobile data transport.
+//!
+//! Devices that can't reach each other directly (different networks, carrier
+//! NAT) meet at a relay instead. The receiver creates a *slot* on the relay and
+//! shares a ticket with the sender; the sender uploads encrypted chunks into
+//! the slot and the receiver downloads them as they arrive. The decryption key
+//! only ever travels inside the ticket, so the relay never sees file contents
+//! or names.
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "server")]
+pub mod server;
+
+/// Returned by `POST /slots`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotInfo {
+    pub id: String,
+    pub expires_in_secs: u64,
+    pub max_slot_bytes: u64,
+    pub max_chunk_bytes: u64,
+}
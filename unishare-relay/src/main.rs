@@ -0,0 +1,25 @@
+use std::net::SocketAddr;
+
+use tokio::net::TcpListener;
+use unishare_relay::server::{self, RelayConfig};
+
+/// Runs a relay. Listens on `UNISHARE_RELAY_ADDR` (default `0.0.0.0:8080`);
+/// see [`RelayConfig::from_env`] for the other settings.
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let addr: SocketAddr = std::env::var("UNISHARE_RELAY_ADDR")
+        .ok()
+        .and_then(|a| a.parse().ok())
+        .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 8080)));
+    let config = RelayConfig::from_env();
+
+    let listener = TcpListener::bind(addr).await?;
+    println!(
+        "Relay listening on {} (data in {}, slots live {}s, max {} bytes each)",
+        listener.local_addr()?,
+        config.data_dir.display(),
+        config.slot_ttl.as_secs(),
+        config.max_slot_bytes
+    );
+    server::serve(listener, config).await
+}
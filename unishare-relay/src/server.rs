@@ -0,0 +1,279 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use tokio::net::TcpListener;
+
+use crate::SlotInfo;
+
+/// Limits and storage location for a relay instance.
+#[derive(Clone, Debug)]
+pub struct RelayConfig {
+    /// Directory holding uploaded chunks, one subdirectory per slot.
+    pub data_dir: PathBuf,
+    /// How long a slot lives after creation, finished or not.
+    pub slot_ttl: Duration,
+    /// Total bytes that may be uploaded into one slot.
+    pub max_slot_bytes: u64,
+    /// Largest single chunk upload accepted.
+    pub max_chunk_bytes: usize,
+    /// How many slots may exist at once.
+    pub max_slots: usize,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        RelayConfig {
+            data_dir: std::env::temp_dir().join("unishare-relay"),
+            slot_ttl: Duration::from_secs(60 * 60),
+            max_slot_bytes: 4 * 1024 * 1024 * 1024,
+            max_chunk_bytes: 2 * 1024 * 1024,
+            max_slots: 256,
+        }
+    }
+}
+
+impl RelayConfig {
+    /// Reads overrides from `UNISHARE_RELAY_DATA_DIR`, `UNISHARE_RELAY_SLOT_TTL_SECS`,
+    /// `UNISHARE_RELAY_MAX_SLOT_BYTES`, `UNISHARE_RELAY_MAX_CHUNK_BYTES` and
+    /// `UNISHARE_RELAY_MAX_SLOTS`, falling back to the defaults.
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok()?.parse().ok()
+        }
+
+        let defaults = RelayConfig::default();
+        RelayConfig {
+            data_dir: var("UNISHARE_RELAY_DATA_DIR").unwrap_or(defaults.data_dir),
+            slot_ttl: var("UNISHARE_RELAY_SLOT_TTL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.slot_ttl),
+            max_slot_bytes: var("UNISHARE_RELAY_MAX_SLOT_BYTES").unwrap_or(defaults.max_slot_bytes),
+            max_chunk_bytes: var("UNISHARE_RELAY_MAX_CHUNK_BYTES")
+                .unwrap_or(defaults.max_chunk_bytes),
+            max_slots: var("UNISHARE_RELAY_MAX_SLOTS").unwrap_or(defaults.max_slots),
+        }
+    }
+}
+
+/// Book-keeping for one transfer slot. Chunk contents live on disk.
+struct Slot {
+    expires: Instant,
+    bytes: u64,
+    /// Chunks being written; they count as uploaded for duplicate checks but
+    /// can't be downloaded yet.
+    uploading: HashSet<u64>,
+    chunks: HashSet<u64>,
+}
+
+struct Relay {
+    config: RelayConfig,
+    slots: Mutex<HashMap<String, Slot>>,
+}
+
+type Shared = Arc<Relay>;
+
+impl Relay {
+    fn slot_dir(&self, id: &str) -> PathBuf {
+        self.config.data_dir.join(id)
+    }
+
+    /// Drops expired slots from the table and returns their IDs so their
+    /// chunks can be deleted.
+    fn take_expired(&self) -> Vec<String> {
+        let now = Instant::now();
+        let mut slots = self.slots.lock().unwrap();
+        let expired: Vec<String> = slots
+            .iter()
+            .filter(|(_, slot)| slot.expires <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &expired {
+            slots.remove(id);
+        }
+        expired
+    }
+}
+
+/// Errors surfaced to relay clients as an HTTP status and a short message.
+struct ApiError(StatusCode, &'static str);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, self.1).into_response()
+    }
+}
+
+impl From<io::Error> for ApiError {
+    fn from(_: io::Error) -> Self {
+        ApiError(StatusCode::INTERNAL_SERVER_ERROR, "storage error")
+    }
+}
+
+const UNKNOWN_SLOT: ApiError = ApiError(StatusCode::NOT_FOUND, "unknown or expired slot");
+
+/// Builds the relay's HTTP routes:
+///
+/// - `POST /slots` creates a slot and returns its [`SlotInfo`].
+/// - `PUT /slots/{id}/chunks/{index}` stores a chunk; each index is written once.
+/// - `GET /slots/{id}/chunks/{index}` returns a chunk, or `425 Too Early` if it
+///   hasn't been uploaded yet.
+/// - `DELETE /slots/{id}` removes a slot once the receiver is done with it.
+///
+/// Chunks are opaque to the relay; clients encrypt them end to end.
+pub fn router(config: RelayConfig) -> Router {
+    let max_chunk_bytes = config.max_chunk_bytes;
+    let relay = Arc::new(Relay {
+        config,
+        slots: Mutex::new(HashMap::new()),
+    });
+    spawn_sweeper(relay.clone());
+
+    Router::new()
+        .route("/slots", post(create_slot))
+        .route("/slots/{id}", axum::routing::delete(delete_slot))
+        .route("/slots/{id}/chunks/{index}", put(put_chunk).get(get_chunk))
+        .route("/health", get(|| async { "ok" }))
+        .layer(DefaultBodyLimit::max(max_chunk_bytes))
+        .with_state(relay)
+}
+
+/// Serves the relay on `listener` until the process exits.
+pub async fn serve(listener: TcpListener, config: RelayConfig) -> io::Result<()> {
+    tokio::fs::create_dir_all(&config.data_dir).await?;
+    axum::serve(listener, router(config)).await
+}
+
+/// Deletes expired slots and their chunks once a minute.
+fn spawn_sweeper(relay: Shared) {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            tick.tick().await;
+            for id in relay.take_expired() {
+                let _ = tokio::fs::remove_dir_all(relay.slot_dir(&id)).await;
+            }
+        }
+    });
+}
+
+async fn create_slot(State(relay): State<Shared>) -> Result<Json<SlotInfo>, ApiError> {
+    let id: String = rand::random::<[u8; 16]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    {
+        let mut slots = relay.slots.lock().unwrap();
+        let now = Instant::now();
+        // Expired slots stay in the table until the sweeper deletes their
+        // chunks, but don't count against the limit.
+        let live = slots.values().filter(|slot| slot.expires > now).count();
+        if live >= relay.config.max_slots {
+            return Err(ApiError(StatusCode::SERVICE_UNAVAILABLE, "relay is full"));
+        }
+        slots.insert(
+            id.clone(),
+            Slot {
+                expires: now + relay.config.slot_ttl,
+                bytes: 0,
+                uploading: HashSet::new(),
+                chunks: HashSet::new(),
+            },
+        );
+    }
+    tokio::fs::create_dir_all(relay.slot_dir(&id)).await?;
+
+    Ok(Json(SlotInfo {
+        id,
+        expires_in_secs: relay.config.slot_ttl.as_secs(),
+        max_slot_bytes: relay.config.max_slot_bytes,
+        max_chunk_bytes: relay.config.max_chunk_bytes as u64,
+    }))
+}
+
+async fn put_chunk(
+    State(relay): State<Shared>,
+    Path((id, index)): Path<(String, u64)>,
+    body: Bytes,
+) -> Result<StatusCode, ApiError> {
+    {
+        let mut slots = relay.slots.lock().unwrap();
+        let slot = live_slot(&mut slots, &id)?;
+        if slot.chunks.contains(&index) || slot.uploading.contains(&index) {
+            return Err(ApiError(StatusCode::CONFLICT, "chunk already uploaded"));
+        }
+        if slot.bytes + body.len() as u64 > relay.config.max_slot_bytes {
+            return Err(ApiError(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "slot size limit exceeded",
+            ));
+        }
+        // Claim the index and reserve the space under the same lock, so
+        // concurrent uploads can neither write one index twice nor overshoot
+        // the limit.
+        slot.uploading.insert(index);
+        slot.bytes += body.len() as u64;
+    }
+
+    let path = relay.slot_dir(&id).join(index.to_string());
+    if let Err(e) = tokio::fs::write(&path, &body).await {
+        if let Some(slot) = relay.slots.lock().unwrap().get_mut(&id) {
+            slot.uploading.remove(&index);
+            slot.bytes -= body.len() as u64;
+        }
+        return Err(e.into());
+    }
+
+    // Only now may downloads see the chunk, so they never read a partial file.
+    match relay.slots.lock().unwrap().get_mut(&id) {
+        Some(slot) => {
+            slot.uploading.remove(&index);
+            slot.chunks.insert(index);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Err(UNKNOWN_SLOT),
+    }
+}
+
+async fn get_chunk(
+    State(relay): State<Shared>,
+    Path((id, index)): Path<(String, u64)>,
+) -> Result<Vec<u8>, ApiError> {
+    {
+        let mut slots = relay.slots.lock().unwrap();
+        let slot = live_slot(&mut slots, &id)?;
+        if !slot.chunks.contains(&index) {
+            return Err(ApiError(StatusCode::TOO_EARLY, "chunk not uploaded yet"));
+        }
+    }
+    Ok(tokio::fs::read(relay.slot_dir(&id).join(index.to_string())).await?)
+}
+
+async fn delete_slot(
+    State(relay): State<Shared>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if relay.slots.lock().unwrap().remove(&id).is_none() {
+        return Err(UNKNOWN_SLOT);
+    }
+    tokio::fs::remove_dir_all(relay.slot_dir(&id)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Looks up a slot that hasn't expired. Slot IDs are hex, which also keeps
+/// them safe to use as directory names.
+fn live_slot<'a>(slots: &'a mut HashMap<String, Slot>, id: &str) -> Result<&'a mut Slot, ApiError> {
+    match slots.get_mut(id) {
+        Some(slot) if slot.expires > Instant::now() => Ok(slot),
+        _ => Err(UNKNOWN_SLOT),
+    }
+}
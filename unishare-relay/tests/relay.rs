@@ -0,0 +1,323 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use tokio::net::TcpListener;
+use unishare_relay::client::{self, RelayError, Ticket, CHUNK_SIZE};
+use unishare_relay::server::{self, RelayConfig};
+
+/// Starts a relay on an ephemeral localhost port and returns its base URL.
+async fn start_relay(config: RelayConfig) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(server::serve(listener, config));
+    url
+}
+
+fn config(dir: &tempfile::TempDir) -> RelayConfig {
+    RelayConfig {
+        data_dir: dir.path().to_path_buf(),
+        ..RelayConfig::default()
+    }
+}
+
+fn sample(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+#[tokio::test]
+async fn round_trip_while_uploading() {
+    let dir = tempfile::tempdir().unwrap();
+    let relay = start_relay(config(&dir)).await;
+
+    let files = tempfile::tempdir().unwrap();
+    let source = files.path().join("photo.jpg");
+    let dest = files.path().join("received.bin");
+    let data = sample(3 * CHUNK_SIZE + 12345);
+    std::fs::write(&source, &data).unwrap();
+
+    let (ticket, info) = client::create_ticket(&relay).await.unwrap();
+    assert_eq!(info.id, ticket.slot_id);
+
+    // The receiver starts first and has to wait for chunks to show up.
+    let receiver = {
+        let ticket: Ticket = ticket.to_string().parse().unwrap();
+        let dest = dest.clone();
+        tokio::spawn(async move { client::download_file(&ticket, &dest).await })
+    };
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let sent = client::upload_file(&ticket, source.to_str().unwrap())
+        .await
+        .unwrap();
+    let received = receiver.await.unwrap().unwrap();
+
+    assert_eq!(sent, received);
+    assert_eq!(received.name, "photo.jpg");
+    assert_eq!(std::fs::read(&dest).unwrap(), data);
+
+    // The slot is cleaned up once the download finishes.
+    assert!(!dir.path().join(&ticket.slot_id).exists());
+}
+
+#[tokio::test]
+async fn empty_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let relay = start_relay(config(&dir)).await;
+
+    let files = tempfile::tempdir().unwrap();
+    let source = files.path().join("empty.txt");
+    let dest = files.path().join("received.bin");
+    std::fs::write(&source, b"").unwrap();
+
+    let (ticket, _) = client::create_ticket(&relay).await.unwrap();
+    client::upload_file(&ticket, source.to_str().unwrap())
+        .await
+        .unwrap();
+    let header = client::download_file(&ticket, &dest).await.unwrap();
+
+    assert_eq!(header.size, 0);
+    assert_eq!(std::fs::read(&dest).unwrap(), b"");
+}
+
+#[tokio::test]
+async fn carries_metadata_in_the_header() {
+    let dir = tempfile::tempdir().unwrap();
+    let relay = start_relay(config(&dir)).await;
+
+    let files = tempfile::tempdir().unwrap();
+    let source = files.path().join("build.sh");
+    let dest = files.path().join("received.bin");
+    std::fs::write(&source, b"#!/bin/sh\n").unwrap();
+
+    let (ticket, _) = client::create_ticket(&relay).await.unwrap();
+    let metadata = serde_json::json!({ "modified": 1_700_000_000_000_000_000i64, "mode": 0o755 });
+    client::upload_file_with_metadata(&ticket, source.to_str().unwrap(), Some(metadata.clone()))
+        .await
+        .unwrap();
+    let header = client::download_file(&ticket, &dest).await.unwrap();
+
+    assert_eq!(header.metadata, Some(metadata));
+}
+
+#[test]
+fn headers_without_metadata_still_parse() {
+    let header: client::FileHeader = serde_json::from_str(r#"{"name":"a.txt","size":3}"#).unwrap();
+    assert_eq!(header.metadata, None);
+    assert_eq!(serde_json::to_string(&header).unwrap(), r#"{"name":"a.txt","size":3}"#);
+}
+
+#[tokio::test]
+async fn relay_only_sees_ciphertext() {
+    let dir = tempfile::tempdir().unwrap();
+    let relay = start_relay(config(&dir)).await;
+
+    let files = tempfile::tempdir().unwrap();
+    let source = files.path().join("secret-name.txt");
+    std::fs::write(&source, b"the quick brown fox jumps over the lazy dog").unwrap();
+
+    let (ticket, _) = client::create_ticket(&relay).await.unwrap();
+    client::upload_file(&ticket, source.to_str().unwrap())
+        .await
+        .unwrap();
+
+    for entry in std::fs::read_dir(dir.path().join(&ticket.slot_id)).unwrap() {
+        let stored = std::fs::read(entry.unwrap().path()).unwrap();
+        let stored = String::from_utf8_lossy(&stored);
+        assert!(!stored.contains("quick brown fox"));
+        assert!(!stored.contains("secret-name"));
+    }
+}
+
+#[tokio::test]
+async fn wrong_key_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let relay = start_relay(config(&dir)).await;
+
+    let files = tempfile::tempdir().unwrap();
+    let source = files.path().join("a.txt");
+    std::fs::write(&source, b"hello").unwrap();
+
+    let (ticket, _) = client::create_ticket(&relay).await.unwrap();
+    client::upload_file(&ticket, source.to_str().unwrap())
+        .await
+        .unwrap();
+
+    let (other, _) = client::create_ticket(&relay).await.unwrap();
+    let (ticket_text, other_text) = (ticket.to_string(), other.to_string());
+    let slot_url = ticket_text.split('#').next().unwrap();
+    let other_key = other_text.split('#').nth(1).unwrap();
+    let forged: Ticket = format!("{}#{}", slot_url, other_key).parse().unwrap();
+
+    let err = client::download_file(&forged, &files.path().join("out"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RelayError::Decrypt(0)), "{}", err);
+}
+
+#[tokio::test]
+async fn tampered_chunk_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let relay = start_relay(config(&dir)).await;
+
+    let files = tempfile::tempdir().unwrap();
+    let source = files.path().join("a.bin");
+    std::fs::write(&source, sample(1000)).unwrap();
+
+    let (ticket, _) = client::create_ticket(&relay).await.unwrap();
+    client::upload_file(&ticket, source.to_str().unwrap())
+        .await
+        .unwrap();
+
+    let chunk = dir.path().join(&ticket.slot_id).join("1");
+    let mut stored = std::fs::read(&chunk).unwrap();
+    stored[10] ^= 0xff;
+    std::fs::write(&chunk, stored).unwrap();
+
+    let err = client::download_file(&ticket, &files.path().join("out"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RelayError::Decrypt(1)), "{}", err);
+}
+
+#[tokio::test]
+async fn slots_expire() {
+    let dir = tempfile::tempdir().unwrap();
+    let relay = start_relay(RelayConfig {
+        slot_ttl: Duration::from_secs(1),
+        ..config(&dir)
+    })
+    .await;
+
+    let files = tempfile::tempdir().unwrap();
+    let source = files.path().join("a.txt");
+    std::fs::write(&source, b"too late").unwrap();
+
+    let (ticket, info) = client::create_ticket(&relay).await.unwrap();
+    assert_eq!(info.expires_in_secs, 1);
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+
+    let err = client::upload_file(&ticket, source.to_str().unwrap())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, RelayError::Status(StatusCode::NOT_FOUND, _)),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn slot_size_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let relay = start_relay(RelayConfig {
+        max_slot_bytes: (CHUNK_SIZE + CHUNK_SIZE / 2) as u64,
+        ..config(&dir)
+    })
+    .await;
+
+    let files = tempfile::tempdir().unwrap();
+    let source = files.path().join("big.bin");
+    std::fs::write(&source, sample(2 * CHUNK_SIZE)).unwrap();
+
+    let (ticket, _) = client::create_ticket(&relay).await.unwrap();
+    let err = client::upload_file(&ticket, source.to_str().unwrap())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, RelayError::Status(StatusCode::PAYLOAD_TOO_LARGE, _)),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn chunk_size_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let relay = start_relay(RelayConfig {
+        max_chunk_bytes: CHUNK_SIZE / 2,
+        ..config(&dir)
+    })
+    .await;
+
+    let files = tempfile::tempdir().unwrap();
+    let source = files.path().join("big.bin");
+    std::fs::write(&source, sample(CHUNK_SIZE)).unwrap();
+
+    let (ticket, _) = client::create_ticket(&relay).await.unwrap();
+    let err = client::upload_file(&ticket, source.to_str().unwrap())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, RelayError::Status(StatusCode::PAYLOAD_TOO_LARGE, _)),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn slot_count_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let relay = start_relay(RelayConfig {
+        max_slots: 1,
+        ..config(&dir)
+    })
+    .await;
+
+    client::create_ticket(&relay).await.unwrap();
+    let err = client::create_ticket(&relay).await.unwrap_err();
+    assert!(
+        matches!(err, RelayError::Status(StatusCode::SERVICE_UNAVAILABLE, _)),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn each_chunk_is_written_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let relay = start_relay(config(&dir)).await;
+    let (ticket, _) = client::create_ticket(&relay).await.unwrap();
+    let url = format!("{}/slots/{}/chunks/0", relay, ticket.slot_id);
+
+    let http = reqwest::Client::new();
+    let uploads: Vec<_> = (0..8u8)
+        .map(|n| tokio::spawn(http.put(&url).body(vec![n; 1024]).send()))
+        .collect();
+    let mut statuses = Vec::new();
+    for upload in uploads {
+        statuses.push(upload.await.unwrap().unwrap().status());
+    }
+    assert_eq!(
+        statuses.iter().filter(|s| **s == StatusCode::NO_CONTENT).count(),
+        1,
+        "{:?}",
+        statuses
+    );
+    assert!(statuses
+        .iter()
+        .all(|s| *s == StatusCode::NO_CONTENT || *s == StatusCode::CONFLICT));
+}
+
+#[test]
+fn ticket_parsing() {
+    let ticket: Ticket =
+        "https://relay.example.com/slots/00ff#AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"
+            .parse()
+            .unwrap();
+    assert_eq!(ticket.relay_url, "https://relay.example.com");
+    assert_eq!(ticket.slot_id, "00ff");
+    assert_eq!(ticket.to_string().parse::<Ticket>().unwrap(), ticket);
+
+    assert!("https://relay.example.com/slots/00ff"
+        .parse::<Ticket>()
+        .is_err());
+    assert!("https://relay.example.com/slots/../x#AAAA"
+        .parse::<Ticket>()
+        .is_err());
+    assert!(
+        "ftp://relay/slots/00#AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"
+            .parse::<Ticket>()
+            .is_err()
+    );
+    assert!("192.168.1.10".parse::<Ticket>().is_err());
+}
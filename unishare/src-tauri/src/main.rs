@@ -3,9 +3,8 @@
     windows_subsystem = "windows"
 )]
 
-mod protocols;
-use protocols::protocol_manager::{send_file_via_best, start_receiver};
-use protocols::bluetooth; // Import the bluetooth module
+use unishare_core::protocols::protocol_manager::{send_file_via_best, start_receiver};
+use unishare_core::protocols::bluetooth;
 
 #[tauri::command]
 async fn send_file(file_path: String, destination: String) -> Result<String, String> {
@@ -36,12 +35,15 @@ async fn receive_file_bluetooth() -> Result<String, String> {
 #[tauri::command]
 async fn receive_file() -> Result<String, String> {
     match start_receiver().await {
-        Ok(msg) => Ok(msg),
+        Ok(received) => Ok(received.message),
         Err(e) => Err(e.to_string()),
     }
 }
 
-fn main() {
+#[tokio::main]
+async fn main() {
+    // Core spawns its background work with tokio; run Tauri on the same runtime.
+    tauri::async_runtime::set(tokio::runtime::Handle::current());
     tauri::Builder::default()
         .invoke_handler(tauri::generate_handler![
             send_file,
@@ -1,5 +0,0 @@
-pub mod protocol_manager;
-pub mod wifi_direct;
-pub mod webrtc;
-pub mod bluetooth;
-pub mod mobiledata;
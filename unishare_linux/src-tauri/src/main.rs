@@ -3,9 +3,12 @@
     windows_subsystem = "windows"
 )]
 
-mod protocols;
-use protocols::protocol_manager::{send_file_via_best, start_receiver};
-use protocols::bluetooth;
+use unishare_core::protocols::protocol_manager::{send_file_via_best, start_receiver_with};
+use unishare_core::protocols::bluetooth;
+use unishare_core::converter::{convert_file, ConversionReport, ConvertOptions, Encoding, Newline};
+use unishare_core::detect::{detect_file, Detection};
+use unishare_core::image_converter::{convert_image, ImageFormat, ImageOptions, ImageReport};
+use unishare_core::post_receive::{PostReceive, TransferResult};
 
 #[tauri::command]
 async fn send_file(file_path: String, destination: String) -> Result<String, String> {
@@ -23,29 +26,131 @@ async fn send_file_bluetooth(file_path: String, destination: String) -> Result<S
     }
 }
 
+/// - `normalize_text` opts in to converting a received text file to UTF-8
+///   with this platform's line endings.
+/// - `convert_images` opts in to converting received images per the
+///   `UNISHARE_IMAGE_*` rules.
 #[tauri::command]
-async fn receive_file_bluetooth() -> Result<String, String> {
-    match bluetooth::start_receiver().await {
-        Ok(_) => Ok("Receiver started via Bluetooth".into()),
-        Err(e) => Err(format!("Bluetooth error: {}", e)),
-    }
+async fn receive_file_bluetooth(
+    normalize_text: Option<bool>,
+    convert_images: Option<bool>,
+) -> Result<TransferResult, String> {
+    let file = match bluetooth::start_receiver().await {
+        Ok(file) => file,
+        Err(e) => return Err(format!("Bluetooth error: {}", e)),
+    };
+    let steps = PostReceive {
+        normalize_text: normalize_text.unwrap_or(false),
+        convert_images: convert_images.unwrap_or(false),
+    };
+    let file = file.path.display().to_string();
+    Ok(TransferResult::new("Receiver started via Bluetooth".into(), Some(file)).finish(steps).await)
 }
 
+/// Takes the same post-receive options as [`receive_file_bluetooth`].
 #[tauri::command]
-async fn receive_file() -> Result<String, String> {
-    match start_receiver().await {
-        Ok(msg) => Ok(msg),
+async fn receive_file(normalize_text: Option<bool>, convert_images: Option<bool>) -> Result<TransferResult, String> {
+    let steps = PostReceive {
+        normalize_text: normalize_text.unwrap_or(false),
+        convert_images: convert_images.unwrap_or(false),
+    };
+    match start_receiver_with(steps).await {
+        Ok(received) => Ok(received.into()),
         Err(e) => Err(e.to_string()),
     }
 }
 
-fn main() {
+/// Converts a text file between encodings.
+///
+/// - `from` is optional; without it the input's BOM decides, else the
+///   encoding is detected from the content.
+/// - `to` and `from` take labels like `UTF-8`, `UTF-16LE`, `UTF-32BE`, `latin1`,
+///   `windows-1252`, `windows-1251`, `Shift_JIS` or `GBK`.
+/// - `newline` (`LF`, `CRLF` or `native`) rewrites line endings; without it they're kept.
+/// - `output_path` may equal `input_path` to convert in place.
+#[tauri::command]
+async fn convert_file_encoding(
+    input_path: String,
+    output_path: String,
+    from: Option<String>,
+    to: String,
+    write_bom: Option<bool>,
+    newline: Option<String>,
+) -> Result<ConversionReport, String> {
+    let options = ConvertOptions {
+        from: from.as_deref().map(str::parse::<Encoding>).transpose()?,
+        to: to.parse()?,
+        write_bom: write_bom.unwrap_or(false),
+        newline: newline.as_deref().map(str::parse::<Newline>).transpose()?,
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        convert_file(input_path.as_ref(), output_path.as_ref(), options).map_err(|e| format!("Conversion error: {}", e))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Guesses a file's text encoding, with a confidence for each candidate.
+#[tauri::command]
+async fn detect_file_encoding(path: String) -> Result<Detection, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        detect_file(path.as_ref()).map_err(|e| format!("Detection error: {}", e))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Converts an image between PNG, JPEG, WebP, BMP, TIFF and GIF.
+///
+/// - `format` defaults to the one `output_path`'s extension names.
+/// - `max_width`/`max_height` scale larger images down, keeping the aspect ratio.
+/// - `quality` (1-100, default 85) applies to JPEG.
+/// - Metadata is stripped unless `strip_metadata` is `false`.
+#[tauri::command]
+async fn convert_image_file(
+    input_path: String,
+    output_path: String,
+    format: Option<String>,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    quality: Option<u8>,
+    strip_metadata: Option<bool>,
+) -> Result<ImageReport, String> {
+    let format = match format {
+        Some(format) => format.parse()?,
+        None => ImageFormat::from_path(output_path.as_ref())
+            .ok_or_else(|| format!("Can't tell the image format of {}", output_path))?,
+    };
+    let mut options = ImageOptions::new(format);
+    options.max_width = max_width;
+    options.max_height = max_height;
+    if let Some(quality) = quality {
+        options.quality = quality;
+    }
+    if let Some(strip_metadata) = strip_metadata {
+        options.strip_metadata = strip_metadata;
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        convert_image(input_path.as_ref(), output_path.as_ref(), options)
+            .map_err(|e| format!("Image conversion error: {}", e))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tokio::main]
+async fn main() {
+    // Core spawns its background work with tokio; run Tauri on the same runtime.
+    tauri::async_runtime::set(tokio::runtime::Handle::current());
     tauri::Builder::default()
         .invoke_handler(tauri::generate_handler![
             send_file,
             receive_file,
             send_file_bluetooth,
-            receive_file_bluetooth
+            receive_file_bluetooth,
+            convert_file_encoding,
+            detect_file_encoding,
+            convert_image_file
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
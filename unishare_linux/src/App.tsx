@@ -2,12 +2,64 @@ import { useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import "./App.css";
 
+interface NormalizationRecord {
+  file: string;
+  text: boolean;
+  sourceEncoding: string | null;
+  targetEncoding: string | null;
+  lineEndingsConverted: number;
+  changes: string[];
+}
+
+interface ImageReport {
+  input: string;
+  output: string;
+  source: string;
+  target: string;
+  width: number;
+  height: number;
+  orientationApplied: boolean;
+  metadataStripped: boolean;
+}
+
+interface TransferResult {
+  message: string;
+  file: string | null;
+  normalization: NormalizationRecord | null;
+  imageConversion: ImageReport | null;
+}
+
+function describeTransfer(result: TransferResult): string {
+  const saved = result.file ? ` (saved as ${result.file})` : "";
+  const image = result.imageConversion;
+  if (image) {
+    return `${result.message}${saved} — converted ${image.source} to ${image.target} (${image.width}×${image.height})`;
+  }
+  const normalization = result.normalization;
+  if (!normalization) {
+    return `${result.message}${saved}`;
+  }
+  let note;
+  if (!normalization.text) {
+    note = "binary file left unchanged";
+  } else if (normalization.changes.length === 0) {
+    note = "text already normalized";
+  } else {
+    note = normalization.changes.join("; ");
+  }
+  return `${result.message}${saved} — ${note}`;
+}
+
 function App() {
   // Default file path for testing; ensure test.txt exists at the project root.
   const [filePath, setFilePath] = useState("../test.txt");
   // Enter the destination IP or identifier. For testing on one machine, use "127.0.0.1".
   const [destinationIp, setDestinationIp] = useState("127.0.0.1");
   const [message, setMessage] = useState("");
+  // Convert received text files to UTF-8 with this platform's line endings.
+  const [normalizeText, setNormalizeText] = useState(false);
+  // Convert received images to a widely supported format.
+  const [convertImages, setConvertImages] = useState(false);
 
   async function sendFile() {
     if (!destinationIp) {
@@ -24,8 +76,8 @@ function App() {
 
   async function receiveFile() {
     try {
-      const response = await invoke("receive_file");
-      setMessage(`📥 Wi-Fi Receiver: ${response}`);
+      const response = await invoke<TransferResult>("receive_file", { normalizeText, convertImages });
+      setMessage(`📥 Wi-Fi Receiver: ${describeTransfer(response)}`);
     } catch (error) {
       setMessage(`❌ Error starting Wi-Fi receiver: ${error}`);
     }
@@ -46,8 +98,8 @@ function App() {
 
   async function receiveFileBluetooth() {
     try {
-      const response = await invoke("receive_file_bluetooth");
-      setMessage(`📥 Bluetooth Receiver: ${response}`);
+      const response = await invoke<TransferResult>("receive_file_bluetooth", { normalizeText, convertImages });
+      setMessage(`📥 Bluetooth Receiver: ${describeTransfer(response)}`);
     } catch (error) {
       setMessage(`❌ Error starting Bluetooth receiver: ${error}`);
     }
@@ -67,6 +119,25 @@ function App() {
         />
       </div>
 
+      <div className="section">
+        <label>
+          <input
+            type="checkbox"
+            checked={normalizeText}
+            onChange={(e) => setNormalizeText(e.target.checked)}
+          />
+          Normalize received text files
+        </label>
+        <label>
+          <input
+            type="checkbox"
+            checked={convertImages}
+            onChange={(e) => setConvertImages(e.target.checked)}
+          />
+          Convert received images
+        </label>
+      </div>
+
       <div className="section">
         <button onClick={sendFile}>Send File (Wi-Fi)</button>
         <button onClick={receiveFile}>Receive File (Wi-Fi)</button>